@@ -0,0 +1,127 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// An allocation event recorded by a `TracingAllocator`.
+///
+/// Fixed size (64 bytes) and plain old data so that it can be written to and read from an `AllocationTraceRingBuffer` without allocating.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct AllocationEvent
+{
+	/// Monotonic timestamp in nanoseconds (`CLOCK_MONOTONIC`).
+	pub timestamp: u64,
+
+	/// Requested size; for reallocations, the requested new size.
+	pub size: usize,
+
+	/// Requested alignment; for reallocations, the requested new alignment.
+	pub alignment: usize,
+
+	/// For deallocations and reallocations, the size of the current memory; otherwise zero.
+	pub current_size: usize,
+
+	/// For deallocations and reallocations, the address of the current memory; otherwise zero.
+	pub current_address: usize,
+
+	/// For successful allocations and reallocations, the address of the memory; otherwise zero.
+	pub address: usize,
+
+	/// For successful allocations and reallocations, the actual size of the memory; otherwise zero.
+	pub actual_size: usize,
+
+	kind: u8,
+
+	tier: u8,
+
+	succeeded: bool,
+
+	_padding: [u8; 5],
+}
+
+impl AllocationEvent
+{
+	/// Kind.
+	#[inline(always)]
+	pub fn kind(&self) -> AllocationEventKind
+	{
+		use self::AllocationEventKind::*;
+
+		match self.kind
+		{
+			0 => Allocate,
+			1 => Deallocate,
+			2 => GrowingReallocate,
+			3 => ShrinkingReallocate,
+			_ => unreachable!("Invalid kind"),
+		}
+	}
+
+	/// Tier of the allocator, if known.
+	#[inline(always)]
+	pub fn tier(&self) -> Option<CurrentAllocatorInUse>
+	{
+		CurrentAllocatorInUse::decode(self.tier as usize)
+	}
+
+	/// Did the allocation or reallocation succeed? Deallocations always succeed.
+	#[inline(always)]
+	pub fn succeeded(&self) -> bool
+	{
+		self.succeeded
+	}
+
+	#[inline(always)]
+	pub(crate) fn new(kind: AllocationEventKind, tier: Option<CurrentAllocatorInUse>, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current: Option<(NonZeroUsize, MemoryAddress)>, result: Result<(MemoryAddress, usize), AllocError>) -> Self
+	{
+		let (current_size, current_address) = match current
+		{
+			None => (0, 0),
+			Some((non_zero_current_size, current_memory)) => (non_zero_current_size.get(), current_memory.to_usize()),
+		};
+
+		let (address, actual_size, succeeded) = match result
+		{
+			Ok((memory_address, actual_size)) => (memory_address.to_usize(), actual_size, true),
+			Err(AllocError) => (0, 0, false),
+		};
+
+		Self
+		{
+			timestamp: Self::monotonic_timestamp(),
+			size: non_zero_size.get(),
+			alignment: non_zero_power_of_two_alignment.get(),
+			current_size,
+			current_address,
+			address,
+			actual_size,
+			kind: kind as u8,
+			tier: CurrentAllocatorInUse::encode(tier) as u8,
+			succeeded,
+			_padding: [0; 5],
+		}
+	}
+
+	#[inline(always)]
+	pub(crate) fn deallocation(tier: Option<CurrentAllocatorInUse>, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: MemoryAddress) -> Self
+	{
+		Self
+		{
+			succeeded: true,
+			..Self::new(AllocationEventKind::Deallocate, tier, non_zero_size, non_zero_power_of_two_alignment, Some((non_zero_size, current_memory)), Err(AllocError))
+		}
+	}
+
+	/// Does not allocate.
+	#[inline(always)]
+	fn monotonic_timestamp() -> u64
+	{
+		let mut now = timespec
+		{
+			tv_sec: 0,
+			tv_nsec: 0,
+		};
+		unsafe { clock_gettime(CLOCK_MONOTONIC, &mut now) };
+		(now.tv_sec as u64) * 1_000_000_000 + (now.tv_nsec as u64)
+	}
+}
@@ -0,0 +1,21 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Kind of an `AllocationEvent`.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[repr(u8)]
+pub enum AllocationEventKind
+{
+	/// `Allocator::allocate()`.
+	Allocate = 0,
+
+	/// `Allocator::deallocate()`.
+	Deallocate = 1,
+
+	/// `Allocator::growing_reallocate()`.
+	GrowingReallocate = 2,
+
+	/// `Allocator::shrinking_reallocate()`.
+	ShrinkingReallocate = 3,
+}
@@ -0,0 +1,58 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// An error reading an allocation trace.
+#[derive(Debug)]
+pub enum AllocationTraceFormatError
+{
+	/// Input or output failed.
+	InputOutput(io::Error),
+
+	/// The trace does not start with `AllocationTraceWriter::Magic`.
+	NotAnAllocationTrace,
+
+	/// The trace's version is not `AllocationTraceWriter::Version`.
+	UnsupportedVersion(u32),
+
+	/// A record is corrupt or truncated.
+	CorruptRecord(&'static str),
+}
+
+impl Display for AllocationTraceFormatError
+{
+	#[inline(always)]
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result
+	{
+		Debug::fmt(self, f)
+	}
+}
+
+impl error::Error for AllocationTraceFormatError
+{
+	#[inline(always)]
+	fn source(&self) -> Option<&(dyn error::Error + 'static)>
+	{
+		use self::AllocationTraceFormatError::*;
+
+		match self
+		{
+			&InputOutput(ref error) => Some(error),
+
+			&NotAnAllocationTrace => None,
+
+			&UnsupportedVersion(_) => None,
+
+			&CorruptRecord(_) => None,
+		}
+	}
+}
+
+impl From<io::Error> for AllocationTraceFormatError
+{
+	#[inline(always)]
+	fn from(error: io::Error) -> Self
+	{
+		AllocationTraceFormatError::InputOutput(error)
+	}
+}
@@ -0,0 +1,318 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Reads `AllocationEvent`s written by `AllocationTraceWriter`.
+///
+/// Use buffered input, eg `BufReader`.
+#[derive(Debug)]
+pub struct AllocationTraceReader<R: Read>
+{
+	reader: R,
+
+	previous_timestamp: u64,
+}
+
+impl<R: Read> Iterator for AllocationTraceReader<R>
+{
+	type Item = Result<AllocationEvent, AllocationTraceFormatError>;
+
+	#[inline(always)]
+	fn next(&mut self) -> Option<Self::Item>
+	{
+		let mut flags = [0u8; 1];
+		match self.reader.read(&mut flags)
+		{
+			Ok(0) => None,
+
+			Ok(_) => Some(self.read_event(flags[0])),
+
+			Err(error) => Some(Err(AllocationTraceFormatError::InputOutput(error))),
+		}
+	}
+}
+
+impl<R: Read> AllocationTraceReader<R>
+{
+	/// Creates a new instance and reads and validates the header.
+	#[inline(always)]
+	pub fn new(mut reader: R) -> Result<Self, AllocationTraceFormatError>
+	{
+		let mut magic = [0u8; 8];
+		reader.read_exact(&mut magic)?;
+		if magic != AllocationTraceWriter::<io::Sink>::Magic
+		{
+			return Err(AllocationTraceFormatError::NotAnAllocationTrace)
+		}
+
+		let mut version = [0u8; 4];
+		reader.read_exact(&mut version)?;
+		let version = u32::from_le_bytes(version);
+		if version != AllocationTraceWriter::<io::Sink>::Version
+		{
+			return Err(AllocationTraceFormatError::UnsupportedVersion(version))
+		}
+
+		Ok
+		(
+			Self
+			{
+				reader,
+				previous_timestamp: 0,
+			}
+		)
+	}
+
+	/// Into the underlying reader.
+	#[inline(always)]
+	pub fn into_inner(self) -> R
+	{
+		self.reader
+	}
+
+	#[inline(always)]
+	fn read_event(&mut self, flags: u8) -> Result<AllocationEvent, AllocationTraceFormatError>
+	{
+		use self::AllocationTraceFormatError::CorruptRecord;
+
+		let kind = flags & 0b11;
+		let tier = (flags >> 2) & 0b111;
+		if tier > CurrentAllocatorInUse::encode(Some(CurrentAllocatorInUse::Stacked)) as u8
+		{
+			return Err(CorruptRecord("tier"))
+		}
+		if flags >> 6 != 0
+		{
+			return Err(CorruptRecord("flags"))
+		}
+		let succeeded = (flags >> 5) & 1 == 1;
+
+		let zig_zag_timestamp_difference = self.read_unsigned()?;
+		let timestamp_difference = ((zig_zag_timestamp_difference >> 1) as i64) ^ -((zig_zag_timestamp_difference & 1) as i64);
+		let timestamp = self.previous_timestamp.wrapping_add(timestamp_difference as u64);
+		self.previous_timestamp = timestamp;
+
+		let size = self.read_usize()?;
+		if size == 0
+		{
+			return Err(CorruptRecord("size"))
+		}
+
+		let alignment =
+		{
+			let mut logarithm_base2_of_alignment = [0u8; 1];
+			self.reader.read_exact(&mut logarithm_base2_of_alignment)?;
+			let logarithm_base2_of_alignment = logarithm_base2_of_alignment[0] as usize;
+			if logarithm_base2_of_alignment >= size_of::<usize>() * 8
+			{
+				return Err(CorruptRecord("alignment"))
+			}
+			1 << logarithm_base2_of_alignment
+		};
+
+		Ok
+		(
+			AllocationEvent
+			{
+				timestamp,
+				size,
+				alignment,
+				current_size: self.read_usize()?,
+				current_address: self.read_usize()?,
+				address: self.read_usize()?,
+				actual_size: self.read_usize()?,
+				kind,
+				tier,
+				succeeded,
+				_padding: [0; 5],
+			}
+		)
+	}
+
+	#[inline(always)]
+	fn read_usize(&mut self) -> Result<usize, AllocationTraceFormatError>
+	{
+		let value = self.read_unsigned()?;
+		if value > (usize::MAX as u64)
+		{
+			return Err(AllocationTraceFormatError::CorruptRecord("value too large"))
+		}
+		Ok(value as usize)
+	}
+
+	#[inline(always)]
+	fn read_unsigned(&mut self) -> Result<u64, AllocationTraceFormatError>
+	{
+		const MaximumShift: u32 = 63;
+
+		let mut value = 0u64;
+		let mut shift = 0;
+		loop
+		{
+			let mut byte = [0u8; 1];
+			self.reader.read_exact(&mut byte)?;
+			let byte = byte[0];
+
+			value |= ((byte & 0x7F) as u64) << shift;
+			if byte & 0x80 == 0
+			{
+				if shift == MaximumShift && byte > 1
+				{
+					return Err(AllocationTraceFormatError::CorruptRecord("integer too large"))
+				}
+				return Ok(value)
+			}
+
+			shift += 7;
+			if shift > MaximumShift
+			{
+				return Err(AllocationTraceFormatError::CorruptRecord("integer too long"))
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod AllocationTraceReaderTests
+{
+	use super::*;
+	use std::io::Cursor;
+
+	#[test]
+	pub fn events_round_trip()
+	{
+		let events =
+		[
+			event(AllocationEventKind::Allocate, Some(CurrentAllocatorInUse::CoroutineLocal), true, 1_000, 1, 1, 0, 0, 0x1000, 8),
+			event(AllocationEventKind::Deallocate, None, true, 999, 1, 1, 1, 0x1000, 0, 0),
+			event(AllocationEventKind::GrowingReallocate, Some(CurrentAllocatorInUse::Stacked), false, 0, usize::MAX, 1 << (size_of::<usize>() * 8 - 1), usize::MAX, usize::MAX, 0, 0),
+			event(AllocationEventKind::ShrinkingReallocate, Some(CurrentAllocatorInUse::Global), true, u64::MAX, 127, 4096, 128, 0x7F, 0x80, 16383),
+			event(AllocationEventKind::Allocate, Some(CurrentAllocatorInUse::ThreadLocal), true, 0, 16384, 8, 0, 0, usize::MAX - 1, 16384),
+			event(AllocationEventKind::Allocate, Some(CurrentAllocatorInUse::ThreadLocal), true, 1 << 63, 8, 8, 0, 0, 0x2000, 8),
+		];
+
+		let reader = AllocationTraceReader::new(Cursor::new(write(&events))).expect("Could not read header");
+		let read = reader.collect::<Result<Vec<_>, _>>().expect("Could not read events");
+		assert_eq!(&read[..], &events[..], "Events did not round trip");
+	}
+
+	#[test]
+	pub fn empty_trace_has_no_events()
+	{
+		let mut reader = AllocationTraceReader::new(Cursor::new(write(&[]))).expect("Could not read header");
+		assert!(reader.next().is_none(), "Empty trace had events");
+	}
+
+	#[test]
+	pub fn wrong_magic_is_rejected()
+	{
+		let mut trace = write(&[]);
+		trace[0] ^= 0xFF;
+		assert!(matches!(AllocationTraceReader::new(Cursor::new(trace)), Err(AllocationTraceFormatError::NotAnAllocationTrace)), "Wrong magic was accepted");
+	}
+
+	#[test]
+	pub fn unsupported_version_is_rejected()
+	{
+		let mut trace = write(&[]);
+		trace[8 .. 12].copy_from_slice(&2u32.to_le_bytes());
+		assert!(matches!(AllocationTraceReader::new(Cursor::new(trace)), Err(AllocationTraceFormatError::UnsupportedVersion(2))), "Unsupported version was accepted");
+	}
+
+	#[test]
+	pub fn truncated_header_is_rejected()
+	{
+		let trace = write(&[]);
+		assert!(matches!(AllocationTraceReader::new(Cursor::new(&trace[.. 10])), Err(AllocationTraceFormatError::InputOutput(_))), "Truncated header was accepted");
+	}
+
+	#[test]
+	pub fn truncated_record_is_rejected()
+	{
+		let trace = write(&[event(AllocationEventKind::Allocate, None, true, 1, 300, 8, 0, 0, 0x1000, 304)]);
+		for length in (HeaderLength + 1) .. trace.len()
+		{
+			assert!(matches!(read_first(trace[.. length].to_vec()), Some(Err(AllocationTraceFormatError::InputOutput(_)))), "Record truncated to {} bytes was accepted", length);
+		}
+	}
+
+	#[test]
+	pub fn corrupt_flags_are_rejected()
+	{
+		assert_corrupt(&[0b0001_1100, 0, 1, 0, 0, 0, 0, 0], "tier");
+		assert_corrupt(&[0b0100_0000, 0, 1, 0, 0, 0, 0, 0], "flags");
+	}
+
+	#[test]
+	pub fn zero_size_is_rejected()
+	{
+		assert_corrupt(&[0, 0, 0, 0, 0, 0, 0, 0], "size");
+	}
+
+	#[test]
+	pub fn alignment_too_large_is_rejected()
+	{
+		assert_corrupt(&[0, 0, 1, (size_of::<usize>() * 8) as u8, 0, 0, 0, 0], "alignment");
+	}
+
+	#[test]
+	pub fn integer_too_long_is_rejected()
+	{
+		let mut record = vec![0, 0];
+		record.extend_from_slice(&[0x80; 10]);
+		record.push(0);
+		assert_corrupt(&record, "integer too long");
+	}
+
+	#[test]
+	pub fn integer_too_large_is_rejected()
+	{
+		let mut record = vec![0, 0];
+		record.extend_from_slice(&[0xFF; 9]);
+		record.push(0x02);
+		assert_corrupt(&record, "integer too large");
+	}
+
+	const HeaderLength: usize = 12;
+
+	fn event(kind: AllocationEventKind, tier: Option<CurrentAllocatorInUse>, succeeded: bool, timestamp: u64, size: usize, alignment: usize, current_size: usize, current_address: usize, address: usize, actual_size: usize) -> AllocationEvent
+	{
+		AllocationEvent
+		{
+			timestamp,
+			size,
+			alignment,
+			current_size,
+			current_address,
+			address,
+			actual_size,
+			kind: kind as u8,
+			tier: CurrentAllocatorInUse::encode(tier) as u8,
+			succeeded,
+			_padding: [0; 5],
+		}
+	}
+
+	fn write(events: &[AllocationEvent]) -> Vec<u8>
+	{
+		let mut writer = AllocationTraceWriter::new(Vec::new()).expect("Could not write header");
+		writer.write_events(events).expect("Could not write events");
+		writer.into_inner()
+	}
+
+	fn read_first(trace: Vec<u8>) -> Option<Result<AllocationEvent, AllocationTraceFormatError>>
+	{
+		AllocationTraceReader::new(Cursor::new(trace)).expect("Could not read header").next()
+	}
+
+	fn assert_corrupt(record: &[u8], expected: &'static str)
+	{
+		let mut trace = write(&[]);
+		trace.extend_from_slice(record);
+		match read_first(trace)
+		{
+			Some(Err(AllocationTraceFormatError::CorruptRecord(reason))) => assert_eq!(reason, expected, "Wrong reason"),
+			other => panic!("Corrupt record {:?} was not rejected: {:?}", record, other),
+		}
+	}
+}
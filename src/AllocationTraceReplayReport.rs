@@ -0,0 +1,75 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// The result of replaying an allocation trace with `AllocationTraceReplayer`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AllocationTraceReplayReport
+{
+	/// Time taken by the allocator, excluding the book-keeping of the replay.
+	pub elapsed: Duration,
+
+	/// Number of allocator operations performed.
+	pub operations: u64,
+
+	/// Number of allocations and reallocations which failed when replayed.
+	pub failures: u64,
+
+	/// Number of events skipped as they failed when recorded.
+	pub skipped: u64,
+
+	/// Number of deallocations and reallocations of memory not allocated in the trace.
+	pub unmatched: u64,
+
+	/// Memory usage in actually allocated bytes.
+	pub usage: u64,
+
+	/// Peak memory usage in actually allocated bytes.
+	pub peak_usage: u64,
+
+	/// Memory usage at the end of the replay, before outstanding memory was deallocated.
+	pub outstanding_at_end: u64,
+
+	/// Fragmentation ratio at the end of the replay, if measured.
+	pub fragmentation_ratio: Option<f64>,
+}
+
+impl AllocationTraceReplayReport
+{
+	/// Mean time per allocator operation.
+	#[inline(always)]
+	pub fn mean_time_per_operation(&self) -> Duration
+	{
+		if self.operations == 0
+		{
+			Duration::default()
+		}
+		else
+		{
+			Duration::from_nanos((self.elapsed.as_nanos() / (self.operations as u128)) as u64)
+		}
+	}
+
+	#[inline(always)]
+	fn time<R>(&mut self, operation: impl FnOnce() -> R) -> R
+	{
+		let started = Instant::now();
+		let result = operation();
+		self.elapsed += started.elapsed();
+		self.operations += 1;
+		result
+	}
+
+	#[inline(always)]
+	fn allocated(&mut self, actual_size: usize)
+	{
+		self.usage += actual_size as u64;
+		self.peak_usage = max(self.peak_usage, self.usage);
+	}
+
+	#[inline(always)]
+	fn deallocated(&mut self, actual_size: usize)
+	{
+		self.usage -= actual_size as u64;
+	}
+}
@@ -0,0 +1,206 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Re-executes a recorded allocation trace against any `Allocator`, eg `BumpAllocator`, `BitSetAllocator`, `MultipleBinarySearchTreeAllocator`, `ContextAllocator` or `System` through `GlobalAllocToAllocatorAdaptor`.
+///
+/// Recorded addresses are mapped to the addresses returned by the allocator being replayed against, so later deallocations and reallocations of the same memory are replayed against the right memory.
+///
+/// * Allocations and reallocations which failed when recorded are skipped;
+/// * Deallocations and reallocations of memory not allocated in the trace (eg allocated before recording started), or whose allocation failed when replayed, are unmatched; unmatched reallocations are replayed as allocations;
+/// * Replayed memory is never read or written, except by the allocator itself (eg when a reallocation moves memory).
+///
+/// The book-keeping of a replay allocates from the global allocator; replaying against the global allocator will skew results.
+#[derive(Debug)]
+pub struct AllocationTraceReplayer<'a, A: Allocator>
+{
+	allocator: &'a A,
+
+	live: HashMap<usize, (MemoryAddress, NonZeroUsize, NonZeroUsize, usize)>,
+
+	report: AllocationTraceReplayReport,
+}
+
+impl<'a, A: Allocator> AllocationTraceReplayer<'a, A>
+{
+	/// Replays all `events` then finishes.
+	///
+	/// `fragmentation_ratio`, if any, is measured after replaying and before finishing; use, for example, `|allocator| allocator.free_space_report().fragmentation_ratio()` for a `MultipleBinarySearchTreeAllocator` or `|allocator| allocator.occupancy().fragmentation_ratio()` for a `BitSetAllocator`.
+	#[inline(always)]
+	pub fn replay(allocator: &'a A, events: impl IntoIterator<Item=AllocationEvent>, fragmentation_ratio: Option<fn(&A) -> f64>) -> AllocationTraceReplayReport
+	{
+		let mut replayer = Self::new(allocator);
+		for event in events
+		{
+			replayer.replay_event(&event)
+		}
+		replayer.finish(fragmentation_ratio)
+	}
+
+	/// Creates a new instance.
+	#[inline(always)]
+	pub fn new(allocator: &'a A) -> Self
+	{
+		Self
+		{
+			allocator,
+			live: HashMap::new(),
+			report: AllocationTraceReplayReport::default(),
+		}
+	}
+
+	/// Report so far.
+	#[inline(always)]
+	pub fn report(&self) -> &AllocationTraceReplayReport
+	{
+		&self.report
+	}
+
+	/// Replays an event.
+	#[inline(always)]
+	pub fn replay_event(&mut self, event: &AllocationEvent)
+	{
+		use self::AllocationEventKind::*;
+
+		let kind = event.kind();
+		if kind != Deallocate && !event.succeeded()
+		{
+			self.report.skipped += 1;
+			return
+		}
+
+		let non_zero_size = event.size.non_zero();
+		let non_zero_power_of_two_alignment = event.alignment.non_zero();
+
+		match kind
+		{
+			Allocate => self.allocate(event.address, non_zero_size, non_zero_power_of_two_alignment),
+
+			Deallocate => match self.live.remove(&event.current_address)
+			{
+				None => self.report.unmatched += 1,
+
+				Some((current_memory, non_zero_current_size, non_zero_power_of_two_current_alignment, current_actual_size)) =>
+				{
+					let allocator = self.allocator;
+					self.report.time(|| allocator.deallocate(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory));
+					self.report.deallocated(current_actual_size)
+				}
+			},
+
+			GrowingReallocate | ShrinkingReallocate => match self.live.remove(&event.current_address)
+			{
+				None =>
+				{
+					self.report.unmatched += 1;
+					self.allocate(event.address, non_zero_size, non_zero_power_of_two_alignment)
+				}
+
+				Some((current_memory, non_zero_current_size, non_zero_power_of_two_current_alignment, current_actual_size)) =>
+				{
+					let allocator = self.allocator;
+					let is_growing = non_zero_size > non_zero_current_size;
+					let result = self.report.time(||
+					{
+						if is_growing
+						{
+							allocator.growing_reallocate(non_zero_size, non_zero_power_of_two_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, false)
+						}
+						else if non_zero_size < non_zero_current_size
+						{
+							allocator.shrinking_reallocate(non_zero_size, non_zero_power_of_two_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, false)
+						}
+						else
+						{
+							Ok((current_memory, current_actual_size))
+						}
+					});
+
+					match result
+					{
+						Ok((new_memory, actual_size)) =>
+						{
+							self.report.deallocated(current_actual_size);
+							self.report.allocated(actual_size);
+							self.live.insert(event.address, (new_memory, non_zero_size, non_zero_power_of_two_alignment, actual_size));
+						}
+
+						Err(AllocError) =>
+						{
+							self.report.failures += 1;
+							self.live.insert(event.address, (current_memory, non_zero_current_size, non_zero_power_of_two_current_alignment, current_actual_size));
+						}
+					}
+				}
+			},
+		}
+	}
+
+	/// Measures fragmentation, if requested, then deallocates all memory still allocated by the replay.
+	#[inline(always)]
+	pub fn finish(mut self, fragmentation_ratio: Option<fn(&A) -> f64>) -> AllocationTraceReplayReport
+	{
+		self.report.outstanding_at_end = self.report.usage;
+		self.report.fragmentation_ratio = fragmentation_ratio.map(|fragmentation_ratio| fragmentation_ratio(self.allocator));
+
+		for (_, (current_memory, non_zero_current_size, non_zero_power_of_two_current_alignment, _)) in self.live.drain()
+		{
+			self.allocator.deallocate(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory)
+		}
+
+		self.report
+	}
+
+	#[inline(always)]
+	fn allocate(&mut self, recorded_address: usize, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize)
+	{
+		let allocator = self.allocator;
+		match self.report.time(|| allocator.allocate(non_zero_size, non_zero_power_of_two_alignment))
+		{
+			Ok((memory_address, actual_size)) =>
+			{
+				self.report.allocated(actual_size);
+				self.live.insert(recorded_address, (memory_address, non_zero_size, non_zero_power_of_two_alignment, actual_size));
+			}
+
+			Err(AllocError) => self.report.failures += 1,
+		}
+	}
+}
+
+#[cfg(test)]
+mod AllocationTraceReplayerTests
+{
+	use super::*;
+
+	#[test]
+	pub fn replay_maps_recorded_addresses_and_reports()
+	{
+		use self::AllocationEventKind::*;
+
+		let events = vec!
+		[
+			AllocationEvent::new(Allocate, None, 64.non_zero(), 8.non_zero(), None, Ok((recorded(0x1000), 64))),
+			AllocationEvent::new(Allocate, None, 32.non_zero(), 8.non_zero(), None, Err(AllocError)),
+			AllocationEvent::new(GrowingReallocate, None, 128.non_zero(), 8.non_zero(), Some((64.non_zero(), recorded(0x1000))), Ok((recorded(0x2000), 128))),
+			AllocationEvent::deallocation(None, 16.non_zero(), 8.non_zero(), recorded(0x9000)),
+			AllocationEvent::new(Allocate, None, 32.non_zero(), 8.non_zero(), None, Ok((recorded(0x3000), 32))),
+			AllocationEvent::deallocation(None, 128.non_zero(), 8.non_zero(), recorded(0x2000)),
+		];
+
+		let report = AllocationTraceReplayer::replay(&GlobalAllocToAllocatorAdaptor(System), events, Some(|_| 0.5));
+
+		assert_eq!(report.operations, 4, "Did not replay two allocations, a reallocation and a deallocation");
+		assert_eq!(report.failures, 0, "Replay failed");
+		assert_eq!(report.skipped, 1, "Did not skip the allocation which failed when recorded");
+		assert_eq!(report.unmatched, 1, "Did not report the deallocation of memory not allocated in the trace");
+		assert_eq!(report.peak_usage, 128 + 32, "Peak usage was wrong");
+		assert_eq!(report.outstanding_at_end, 32, "Outstanding memory was wrong");
+		assert_eq!(report.fragmentation_ratio, Some(0.5), "Fragmentation ratio was not measured");
+	}
+
+	fn recorded(address: usize) -> MemoryAddress
+	{
+		NonNull::new(address as *mut u8).unwrap()
+	}
+}
@@ -0,0 +1,306 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Head of a process-wide, append-only, singly-linked list of allocation trace ring buffers.
+///
+/// Ring buffers are never freed, but retired ring buffers are reused by `register()`.
+static AllocationTraceRingBuffers: AtomicPtr<AllocationTraceRingBuffer> = AtomicPtr::new(null_mut());
+
+/// The allocation trace ring buffer the current thread records into, if any.
+#[thread_local] static CurrentThreadAllocationTraceRingBuffer: Cell<*const AllocationTraceRingBuffer> = Cell::new(null());
+
+/// A ring buffer of `AllocationEvent`s recorded by `TracingAllocator`s on one thread.
+///
+/// Create one per thread with `register()`, then start recording on that thread with `record_on_current_thread()`.
+/// When the thread stops recording, eg before it exits, `retire()` the ring buffer so that `register()` can reuse it; the number of ring buffers is then bounded by the peak number of threads recording at once.
+/// Recording never allocates and never blocks; if the ring buffer is full, events are discarded.
+///
+/// A consumer thread drains all ring buffers with `drain_all()`.
+/// The consumer thread should not itself record events into a ring buffer it is draining.
+pub struct AllocationTraceRingBuffer
+{
+	next_in_registry: *const AllocationTraceRingBuffer,
+
+	retired: AtomicBool,
+
+	magic_ring_buffer: MagicRingBuffer,
+}
+
+impl Debug for AllocationTraceRingBuffer
+{
+	#[inline(always)]
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result
+	{
+		write!(f, "AllocationTraceRingBuffer({:p})", self)
+	}
+}
+
+unsafe impl Send for AllocationTraceRingBuffer
+{
+}
+
+unsafe impl Sync for AllocationTraceRingBuffer
+{
+}
+
+impl AllocationTraceRingBuffer
+{
+	/// Reuses a retired ring buffer or, if there is none, registers a new ring buffer created by `new_magic_ring_buffer` so that it can be drained by `drain_all()`.
+	///
+	/// A reused ring buffer may still hold events recorded before it was retired; they are drained as usual.
+	///
+	/// The ring buffer's size should be a multiple of `size_of::<AllocationEvent>()`; the size of a magic ring buffer is always a multiple of the page size, so this is always true.
+	///
+	/// Fails if out of memory.
+	#[inline(always)]
+	pub fn register(new_magic_ring_buffer: impl FnOnce() -> MagicRingBuffer) -> Result<&'static Self, AllocError>
+	{
+		debug_assert_eq!(size_of::<AllocationEvent>(), 64, "AllocationEvent should be 64 bytes so that page-aligned ring buffers only ever contain whole, aligned events");
+
+		if let Some(retired) = Self::reuse_retired()
+		{
+			return Ok(retired)
+		}
+
+		let magic_ring_buffer = new_magic_ring_buffer();
+		let layout = Layout::new::<Self>();
+		let this = unsafe { System.alloc(layout) } as *mut Self;
+		if unlikely!(this.is_null())
+		{
+			return Err(AllocError)
+		}
+
+		let mut head = AllocationTraceRingBuffers.load(Acquire);
+		unsafe
+		{
+			this.write
+			(
+				Self
+				{
+					next_in_registry: head,
+					retired: AtomicBool::new(false),
+					magic_ring_buffer,
+				}
+			)
+		};
+		loop
+		{
+			match AllocationTraceRingBuffers.compare_exchange_weak(head, this, AcqRel, Acquire)
+			{
+				Ok(_) => return Ok(unsafe { & * this }),
+
+				Err(was) =>
+				{
+					head = was;
+					unsafe { (*this).next_in_registry = head };
+				}
+			}
+		}
+	}
+
+	/// Stops recording into this ring buffer, if the current thread is recording into it, and makes it available for reuse by `register()`.
+	///
+	/// No thread should record into this ring buffer after it is retired.
+	#[inline(always)]
+	pub fn retire(&'static self)
+	{
+		if CurrentThreadAllocationTraceRingBuffer.get() == (self as *const Self)
+		{
+			Self::stop_recording_on_current_thread()
+		}
+		self.retired.store(true, Release)
+	}
+
+	#[inline(always)]
+	fn reuse_retired() -> Option<&'static Self>
+	{
+		let mut allocation_trace_ring_buffer = AllocationTraceRingBuffers.load(Acquire) as *const Self;
+		while allocation_trace_ring_buffer.is_not_null()
+		{
+			let this = allocation_trace_ring_buffer.reference();
+			if this.retired.load(Relaxed) && this.retired.compare_exchange(true, false, Acquire, Relaxed).is_ok()
+			{
+				return Some(this)
+			}
+			allocation_trace_ring_buffer = this.next_in_registry;
+		}
+		None
+	}
+
+	/// Starts recording events of `TracingAllocator`s on the current thread into this ring buffer.
+	///
+	/// A ring buffer should only be recorded into by one thread at a time.
+	#[inline(always)]
+	pub fn record_on_current_thread(&'static self)
+	{
+		CurrentThreadAllocationTraceRingBuffer.set(self)
+	}
+
+	/// Stops recording events on the current thread.
+	#[inline(always)]
+	pub fn stop_recording_on_current_thread()
+	{
+		CurrentThreadAllocationTraceRingBuffer.set(null())
+	}
+
+	/// Drains the events of all registered ring buffers.
+	///
+	/// `consumer` may be called more than once for each ring buffer; it must not record into the ring buffer it is passed.
+	///
+	/// Returns the number of events drained.
+	#[inline(always)]
+	pub fn drain_all(mut consumer: impl FnMut(&'static Self, &[AllocationEvent])) -> usize
+	{
+		let mut drained = 0;
+		let mut allocation_trace_ring_buffer = AllocationTraceRingBuffers.load(Acquire) as *const Self;
+		while allocation_trace_ring_buffer.is_not_null()
+		{
+			let this = allocation_trace_ring_buffer.reference();
+			drained += this.drain(|events| consumer(this, events));
+			allocation_trace_ring_buffer = this.next_in_registry;
+		}
+		drained
+	}
+
+	/// Drains the events of this ring buffer.
+	///
+	/// `consumer` may be called more than once.
+	///
+	/// Returns the number of events drained.
+	#[inline(always)]
+	pub fn drain(&self, mut consumer: impl FnMut(&[AllocationEvent])) -> usize
+	{
+		const EventSize: usize = size_of::<AllocationEvent>();
+
+		let mut drained = 0;
+		loop
+		{
+			let events_drained = self.magic_ring_buffer.read_some_data(|buffer: &mut [u8]|
+			{
+				let number_of_events = buffer.len() / EventSize;
+				if number_of_events != 0
+				{
+					let events = unsafe { from_raw_parts(buffer.as_ptr() as *const AllocationEvent, number_of_events) };
+					consumer(events);
+				}
+				(number_of_events * EventSize, number_of_events)
+			});
+
+			if events_drained == 0
+			{
+				return drained
+			}
+			drained += events_drained
+		}
+	}
+
+	/// Records an event into the current thread's ring buffer, if any.
+	#[inline(always)]
+	pub(crate) fn record(event: impl FnOnce() -> AllocationEvent)
+	{
+		let allocation_trace_ring_buffer = CurrentThreadAllocationTraceRingBuffer.get();
+		if likely!(allocation_trace_ring_buffer.is_null())
+		{
+			return
+		}
+
+		let event = event();
+		let _ = allocation_trace_ring_buffer.reference().magic_ring_buffer.write_some_data(size_of::<AllocationEvent>(), |buffer: &mut [u8]| unsafe { (buffer.as_mut_ptr() as *mut AllocationEvent).write(event) });
+	}
+}
+
+#[cfg(test)]
+mod AllocationTraceRingBufferTests
+{
+	use super::*;
+	use linux_support::memory::huge_pages::DefaultPageSizeAndHugePageSizes;
+	use std::sync::Mutex;
+
+	/// `drain_all()` drains every ring buffer, so tests which drain must not run concurrently.
+	static Serially: Mutex<()> = Mutex::new(());
+
+	const RingBufferSize: usize = 4096;
+
+	const EventsInRingBuffer: usize = RingBufferSize / size_of::<AllocationEvent>();
+
+	#[test]
+	pub fn records_only_while_recording_on_current_thread()
+	{
+		let _serially = Serially.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+		let allocation_trace_ring_buffer = AllocationTraceRingBuffer::register(new_magic_ring_buffer).expect("Did not register");
+
+		record(1);
+		allocation_trace_ring_buffer.record_on_current_thread();
+		record(2);
+		record(3);
+		record(4);
+		AllocationTraceRingBuffer::stop_recording_on_current_thread();
+		record(5);
+
+		assert_eq!(drain_all_of(allocation_trace_ring_buffer), vec![2, 3, 4], "Did not record only while recording");
+		assert_eq!(drain_all_of(allocation_trace_ring_buffer), vec![], "Did not drain all events");
+
+		allocation_trace_ring_buffer.retire();
+	}
+
+	#[test]
+	pub fn events_are_discarded_when_full()
+	{
+		let _serially = Serially.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+		let allocation_trace_ring_buffer = AllocationTraceRingBuffer::register(new_magic_ring_buffer).expect("Did not register");
+
+		allocation_trace_ring_buffer.record_on_current_thread();
+		for size in 1 ..= EventsInRingBuffer * 4
+		{
+			record(size);
+		}
+
+		let sizes = drain_all_of(allocation_trace_ring_buffer);
+		assert!(!sizes.is_empty() && sizes.len() <= EventsInRingBuffer, "Recorded `{}` events into a ring buffer which can hold `{}`", sizes.len(), EventsInRingBuffer);
+		assert_eq!(sizes, (1 ..= sizes.len()).collect::<Vec<_>>(), "Did not discard the events recorded when full");
+
+		record(1);
+		assert_eq!(drain_all_of(allocation_trace_ring_buffer), vec![1], "Did not record once drained");
+
+		allocation_trace_ring_buffer.retire();
+	}
+
+	#[test]
+	pub fn retired_ring_buffer_stops_recording_and_is_reused()
+	{
+		let _serially = Serially.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+		let allocation_trace_ring_buffer = AllocationTraceRingBuffer::register(new_magic_ring_buffer).expect("Did not register");
+
+		allocation_trace_ring_buffer.record_on_current_thread();
+		allocation_trace_ring_buffer.retire();
+		record(1);
+		assert_eq!(drain_all_of(allocation_trace_ring_buffer), vec![], "Recorded after being retired");
+
+		let reused = AllocationTraceRingBuffer::register(|| panic!("Did not reuse a retired ring buffer")).expect("Did not register");
+		reused.retire();
+	}
+
+	fn record(size: usize)
+	{
+		AllocationTraceRingBuffer::record(|| AllocationEvent::deallocation(None, size.non_zero(), 8.non_zero(), NonNull::dangling()))
+	}
+
+	fn drain_all_of(allocation_trace_ring_buffer: &'static AllocationTraceRingBuffer) -> Vec<usize>
+	{
+		let mut sizes = Vec::new();
+		AllocationTraceRingBuffer::drain_all(|drained, events| if drained as *const AllocationTraceRingBuffer == allocation_trace_ring_buffer as *const AllocationTraceRingBuffer
+		{
+			sizes.extend(events.iter().map(|event| event.size))
+		});
+		sizes
+	}
+
+	fn new_magic_ring_buffer() -> MagicRingBuffer
+	{
+		MagicRingBuffer::allocate(&DefaultPageSizeAndHugePageSizes::default(), unsafe { NonZeroU64::new_unchecked(RingBufferSize as u64) }, 0).expect("Could not allocate a magic ring buffer")
+	}
+}
@@ -0,0 +1,114 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Writes `AllocationEvent`s in a compact, versioned binary format; read them back with `AllocationTraceReader`.
+///
+/// The format is a header of `Magic` followed by `Version` as a little-endian `u32`, then one record per event.
+/// Each record is a byte holding the kind (bits 0 - 1), tier (bits 2 - 4) and whether it succeeded (bit 5), then the timestamp as a zig-zag encoded difference from the previous record's timestamp, the size, the logarithm base two of the alignment as a byte, the current size, the current address, the address and the actual size, all as unsigned LEB128 integers.
+///
+/// Use buffered output, eg `BufWriter`; typically events are written from the consumer of `AllocationTraceRingBuffer::drain_all()`.
+#[derive(Debug)]
+pub struct AllocationTraceWriter<W: Write>
+{
+	writer: W,
+
+	previous_timestamp: u64,
+}
+
+impl<W: Write> AllocationTraceWriter<W>
+{
+	/// Start of every trace.
+	pub const Magic: [u8; 8] = *b"CTXALLOC";
+
+	/// Version of the format written.
+	pub const Version: u32 = 1;
+
+	/// Creates a new instance and writes the header.
+	#[inline(always)]
+	pub fn new(mut writer: W) -> io::Result<Self>
+	{
+		writer.write_all(&Self::Magic)?;
+		writer.write_all(&Self::Version.to_le_bytes())?;
+
+		Ok
+		(
+			Self
+			{
+				writer,
+				previous_timestamp: 0,
+			}
+		)
+	}
+
+	/// Writes events.
+	#[inline(always)]
+	pub fn write_events(&mut self, events: &[AllocationEvent]) -> io::Result<()>
+	{
+		for event in events
+		{
+			self.write_event(event)?
+		}
+		Ok(())
+	}
+
+	/// Writes an event.
+	#[inline(always)]
+	pub fn write_event(&mut self, event: &AllocationEvent) -> io::Result<()>
+	{
+		let flags = event.kind | (event.tier << 2) | ((event.succeeded as u8) << 5);
+		self.writer.write_all(&[flags])?;
+
+		let timestamp_difference = event.timestamp.wrapping_sub(self.previous_timestamp) as i64;
+		self.previous_timestamp = event.timestamp;
+		self.write_unsigned(((timestamp_difference << 1) ^ (timestamp_difference >> 63)) as u64)?;
+
+		self.write_unsigned(event.size as u64)?;
+		self.writer.write_all(&[event.alignment.trailing_zeros() as u8])?;
+		self.write_unsigned(event.current_size as u64)?;
+		self.write_unsigned(event.current_address as u64)?;
+		self.write_unsigned(event.address as u64)?;
+		self.write_unsigned(event.actual_size as u64)
+	}
+
+	/// Flushes the underlying writer.
+	#[inline(always)]
+	pub fn flush(&mut self) -> io::Result<()>
+	{
+		self.writer.flush()
+	}
+
+	/// Into the underlying writer.
+	#[inline(always)]
+	pub fn into_inner(self) -> W
+	{
+		self.writer
+	}
+
+	#[inline(always)]
+	fn write_unsigned(&mut self, mut value: u64) -> io::Result<()>
+	{
+		const MaximumBytes: usize = 10;
+
+		let mut bytes = [0u8; MaximumBytes];
+		let mut length = 0;
+		while
+		{
+			let byte = (value & 0x7F) as u8;
+			value >>= 7;
+			bytes[length] = if value == 0
+			{
+				byte
+			}
+			else
+			{
+				byte | 0x80
+			};
+			length += 1;
+			value != 0
+		}
+		{
+		}
+		self.writer.write_all(&bytes[.. length])
+	}
+}
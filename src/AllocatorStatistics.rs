@@ -0,0 +1,281 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Statistics of one tier of one thread, suitable for export in Prometheus text or JSON formats.
+///
+/// Obtain using `GlobalThreadAndCoroutineSwitchableAllocator::allocator_statistics()`, which returns the statistics of the current thread only.
+/// Statistics of several threads can be concatenated before being written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocatorStatistics
+{
+	/// Thread name, or identifier if unnamed.
+	pub thread: String,
+
+	/// Tier.
+	pub tier: CurrentAllocatorInUse,
+
+	/// Type name of the allocator.
+	pub allocator_kind: &'static str,
+
+	/// Memory usage.
+	pub memory_usage: LocalAllocatorMemoryUsageSnapshot,
+}
+
+impl AllocatorStatistics
+{
+	const MetricPrefix: &'static str = "context_allocator_";
+
+	const Metrics: [(&'static str, &'static str, &'static str, fn(&LocalAllocatorMemoryUsageSnapshot) -> i64); 12] =
+	[
+		("allocated_bytes_total", "counter", "Requested bytes allocated.", |memory_usage| memory_usage.allocated as i64),
+		("deallocated_bytes_total", "counter", "Requested bytes deallocated.", |memory_usage| memory_usage.deallocated as i64),
+		("growing_reallocated_bytes_total", "counter", "Requested bytes added by growing reallocations.", |memory_usage| memory_usage.growing_reallocated as i64),
+		("shrinking_reallocated_bytes_total", "counter", "Requested bytes removed by shrinking reallocations.", |memory_usage| memory_usage.shrinking_reallocated as i64),
+		("allocations_total", "counter", "Allocations.", |memory_usage| memory_usage.allocations as i64),
+		("deallocations_total", "counter", "Deallocations.", |memory_usage| memory_usage.deallocations as i64),
+		("growing_reallocations_total", "counter", "Growing reallocations.", |memory_usage| memory_usage.growing_reallocations as i64),
+		("shrinking_reallocations_total", "counter", "Shrinking reallocations.", |memory_usage| memory_usage.shrinking_reallocations as i64),
+		("failed_allocations_total", "counter", "Failed allocations and reallocations.", |memory_usage| memory_usage.failed_allocations as i64),
+		("usage_bytes", "gauge", "Requested bytes in use.", LocalAllocatorMemoryUsageSnapshot::usage),
+		("peak_usage_bytes", "gauge", "Peak requested bytes in use.", |memory_usage| memory_usage.peak_usage as i64),
+		("live_allocations", "gauge", "Allocations not yet deallocated.", LocalAllocatorMemoryUsageSnapshot::live_allocations),
+	];
+
+	/// Create a new instance.
+	#[inline(always)]
+	pub fn new(thread: &str, tier: CurrentAllocatorInUse, allocator_kind: &'static str, memory_usage: LocalAllocatorMemoryUsageSnapshot) -> Self
+	{
+		Self
+		{
+			thread: thread.to_string(),
+			tier,
+			allocator_kind,
+			memory_usage,
+		}
+	}
+
+	/// Label value of the tier; stable across releases.
+	#[inline(always)]
+	pub fn tier_label(&self) -> &'static str
+	{
+		use self::CurrentAllocatorInUse::*;
+
+		match self.tier
+		{
+			CoroutineLocal => "coroutine_local",
+			ThreadLocal => "thread_local",
+			Global => "global",
+			Stacked => "stacked",
+		}
+	}
+
+	/// Writes statistics in the Prometheus text exposition format.
+	///
+	/// Metric names are prefixed `context_allocator_` and labelled with `tier`, `thread` and `allocator`.
+	///
+	/// Writing may allocate, so the global allocator is made the current allocator in use whilst writing.
+	pub fn write_prometheus<CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>>(global_allocator: &GTACSA, statistics: &[Self], writer: &mut impl Write) -> io::Result<()>
+	{
+		let _guard = global_allocator.with_global();
+
+		for &(name, metric_type, help, value) in Self::Metrics.iter()
+		{
+			writeln!(writer, "# HELP {}{} {}", Self::MetricPrefix, name, help)?;
+			writeln!(writer, "# TYPE {}{} {}", Self::MetricPrefix, name, metric_type)?;
+			for statistic in statistics
+			{
+				write!(writer, "{}{}{{tier=\"{}\",thread=\"", Self::MetricPrefix, name, statistic.tier_label())?;
+				Self::write_escaped_prometheus_label_value(writer, &statistic.thread)?;
+				writer.write_all(b"\",allocator=\"")?;
+				Self::write_escaped_prometheus_label_value(writer, statistic.allocator_kind)?;
+				writeln!(writer, "\"}} {}", value(&statistic.memory_usage))?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Writes statistics as a JSON object of the form `{"statistics":[...]}`.
+	///
+	/// Field names match the metric names of `write_prometheus()` without the `context_allocator_` prefix.
+	///
+	/// Writing may allocate, so the global allocator is made the current allocator in use whilst writing.
+	pub fn write_json<CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>>(global_allocator: &GTACSA, statistics: &[Self], writer: &mut impl Write) -> io::Result<()>
+	{
+		let _guard = global_allocator.with_global();
+
+		writer.write_all(b"{\"statistics\":[")?;
+		for (index, statistic) in statistics.iter().enumerate()
+		{
+			if index != 0
+			{
+				writer.write_all(b",")?;
+			}
+
+			write!(writer, "{{\"tier\":\"{}\",\"thread\":\"", statistic.tier_label())?;
+			Self::write_escaped_json_string(writer, &statistic.thread)?;
+			writer.write_all(b"\",\"allocator\":\"")?;
+			Self::write_escaped_json_string(writer, statistic.allocator_kind)?;
+			writer.write_all(b"\"")?;
+			for &(name, _, _, value) in Self::Metrics.iter()
+			{
+				write!(writer, ",\"{}\":{}", name, value(&statistic.memory_usage))?;
+			}
+			writer.write_all(b"}")?;
+		}
+		writer.write_all(b"]}")
+	}
+
+	#[inline(always)]
+	fn write_escaped_prometheus_label_value(writer: &mut impl Write, value: &str) -> io::Result<()>
+	{
+		for character in value.chars()
+		{
+			match character
+			{
+				'\\' => writer.write_all(b"\\\\")?,
+				'"' => writer.write_all(b"\\\"")?,
+				'\n' => writer.write_all(b"\\n")?,
+				_ => write!(writer, "{}", character)?,
+			}
+		}
+		Ok(())
+	}
+
+	#[inline(always)]
+	fn write_escaped_json_string(writer: &mut impl Write, value: &str) -> io::Result<()>
+	{
+		for character in value.chars()
+		{
+			match character
+			{
+				'\\' => writer.write_all(b"\\\\")?,
+				'"' => writer.write_all(b"\\\"")?,
+				'\n' => writer.write_all(b"\\n")?,
+				'\r' => writer.write_all(b"\\r")?,
+				'\t' => writer.write_all(b"\\t")?,
+				'\u{0}' ..= '\u{1F}' => write!(writer, "\\u{:04x}", character as u32)?,
+				_ => write!(writer, "{}", character)?,
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod AllocatorStatisticsTests
+{
+	use super::*;
+
+	#[test]
+	pub fn prometheus_text_exposition_format()
+	{
+		let expected = concat!
+		(
+			"# HELP context_allocator_allocated_bytes_total Requested bytes allocated.\n",
+			"# TYPE context_allocator_allocated_bytes_total counter\n",
+			"context_allocator_allocated_bytes_total{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 100\n",
+			"# HELP context_allocator_deallocated_bytes_total Requested bytes deallocated.\n",
+			"# TYPE context_allocator_deallocated_bytes_total counter\n",
+			"context_allocator_deallocated_bytes_total{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 40\n",
+			"# HELP context_allocator_growing_reallocated_bytes_total Requested bytes added by growing reallocations.\n",
+			"# TYPE context_allocator_growing_reallocated_bytes_total counter\n",
+			"context_allocator_growing_reallocated_bytes_total{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 10\n",
+			"# HELP context_allocator_shrinking_reallocated_bytes_total Requested bytes removed by shrinking reallocations.\n",
+			"# TYPE context_allocator_shrinking_reallocated_bytes_total counter\n",
+			"context_allocator_shrinking_reallocated_bytes_total{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 5\n",
+			"# HELP context_allocator_allocations_total Allocations.\n",
+			"# TYPE context_allocator_allocations_total counter\n",
+			"context_allocator_allocations_total{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 3\n",
+			"# HELP context_allocator_deallocations_total Deallocations.\n",
+			"# TYPE context_allocator_deallocations_total counter\n",
+			"context_allocator_deallocations_total{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 1\n",
+			"# HELP context_allocator_growing_reallocations_total Growing reallocations.\n",
+			"# TYPE context_allocator_growing_reallocations_total counter\n",
+			"context_allocator_growing_reallocations_total{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 1\n",
+			"# HELP context_allocator_shrinking_reallocations_total Shrinking reallocations.\n",
+			"# TYPE context_allocator_shrinking_reallocations_total counter\n",
+			"context_allocator_shrinking_reallocations_total{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 1\n",
+			"# HELP context_allocator_failed_allocations_total Failed allocations and reallocations.\n",
+			"# TYPE context_allocator_failed_allocations_total counter\n",
+			"context_allocator_failed_allocations_total{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 2\n",
+			"# HELP context_allocator_usage_bytes Requested bytes in use.\n",
+			"# TYPE context_allocator_usage_bytes gauge\n",
+			"context_allocator_usage_bytes{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 65\n",
+			"# HELP context_allocator_peak_usage_bytes Peak requested bytes in use.\n",
+			"# TYPE context_allocator_peak_usage_bytes gauge\n",
+			"context_allocator_peak_usage_bytes{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 80\n",
+			"# HELP context_allocator_live_allocations Allocations not yet deallocated.\n",
+			"# TYPE context_allocator_live_allocations gauge\n",
+			"context_allocator_live_allocations{tier=\"thread_local\",thread=\"main\",allocator=\"MyAllocator\"} 2\n",
+		);
+
+		assert_eq!(write_prometheus(&[statistic("main", CurrentAllocatorInUse::ThreadLocal, memory_usage())]), expected, "Prometheus text exposition format was wrong");
+	}
+
+	#[test]
+	pub fn prometheus_label_values_are_escaped()
+	{
+		let written = write_prometheus(&[statistic("back\\slash \"quoted\"\nnew line", CurrentAllocatorInUse::Global, LocalAllocatorMemoryUsageSnapshot::default())]);
+
+		let sample = written.lines().nth(2).expect("No sample");
+		assert_eq!(sample, "context_allocator_allocated_bytes_total{tier=\"global\",thread=\"back\\\\slash \\\"quoted\\\"\\nnew line\",allocator=\"MyAllocator\"} 0", "Label values were not escaped");
+		assert_eq!(written.lines().count(), 12 * 3, "An escaped label value was not on one line");
+	}
+
+	#[test]
+	pub fn json_snapshot()
+	{
+		let expected = concat!
+		(
+			"{\"statistics\":[",
+			"{\"tier\":\"thread_local\",\"thread\":\"main\",\"allocator\":\"MyAllocator\",\"allocated_bytes_total\":100,\"deallocated_bytes_total\":40,\"growing_reallocated_bytes_total\":10,\"shrinking_reallocated_bytes_total\":5,\"allocations_total\":3,\"deallocations_total\":1,\"growing_reallocations_total\":1,\"shrinking_reallocations_total\":1,\"failed_allocations_total\":2,\"usage_bytes\":65,\"peak_usage_bytes\":80,\"live_allocations\":2},",
+			"{\"tier\":\"global\",\"thread\":\"tab\\there \\\"quoted\\\" \\\\ \\u0001\",\"allocator\":\"MyAllocator\",\"allocated_bytes_total\":0,\"deallocated_bytes_total\":0,\"growing_reallocated_bytes_total\":0,\"shrinking_reallocated_bytes_total\":0,\"allocations_total\":0,\"deallocations_total\":0,\"growing_reallocations_total\":0,\"shrinking_reallocations_total\":0,\"failed_allocations_total\":0,\"usage_bytes\":0,\"peak_usage_bytes\":0,\"live_allocations\":0}",
+			"]}",
+		);
+
+		let statistics =
+		[
+			statistic("main", CurrentAllocatorInUse::ThreadLocal, memory_usage()),
+			statistic("tab\there \"quoted\" \\ \u{1}", CurrentAllocatorInUse::Global, LocalAllocatorMemoryUsageSnapshot::default()),
+		];
+		assert_eq!(write_json(&statistics), expected, "JSON snapshot was wrong");
+		assert_eq!(write_json(&[]), "{\"statistics\":[]}", "JSON snapshot of no statistics was wrong");
+	}
+
+	fn write_prometheus(statistics: &[AllocatorStatistics]) -> String
+	{
+		let mut written = Vec::new();
+		AllocatorStatistics::write_prometheus::<MemorySize64Kb, _>(&MyGlobalAllocator, statistics, &mut written).expect("Could not write");
+		String::from_utf8(written).expect("Not UTF-8")
+	}
+
+	fn write_json(statistics: &[AllocatorStatistics]) -> String
+	{
+		let mut written = Vec::new();
+		AllocatorStatistics::write_json::<MemorySize64Kb, _>(&MyGlobalAllocator, statistics, &mut written).expect("Could not write");
+		String::from_utf8(written).expect("Not UTF-8")
+	}
+
+	fn statistic(thread: &str, tier: CurrentAllocatorInUse, memory_usage: LocalAllocatorMemoryUsageSnapshot) -> AllocatorStatistics
+	{
+		AllocatorStatistics::new(thread, tier, "MyAllocator", memory_usage)
+	}
+
+	fn memory_usage() -> LocalAllocatorMemoryUsageSnapshot
+	{
+		LocalAllocatorMemoryUsageSnapshot
+		{
+			allocated: 100,
+			deallocated: 40,
+			growing_reallocated: 10,
+			shrinking_reallocated: 5,
+			allocations: 3,
+			deallocations: 1,
+			growing_reallocations: 1,
+			shrinking_reallocations: 1,
+			failed_allocations: 2,
+			peak_usage: 80,
+		}
+	}
+}
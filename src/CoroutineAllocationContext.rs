@@ -0,0 +1,190 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Bundles the allocation state of a coroutine: its coroutine local allocator, its memory quota (if any), its saved current allocator in use and, optionally, its stack memory.
+///
+/// Use `resume_on()` when switching into the coroutine and `suspend()` when switching out of it; these swap and restore all state in the correct order.
+///
+/// In debug builds, resuming whilst resumed, suspending whilst suspended or resuming whilst another coroutine is resumed on the same thread panics; in release builds, suspending whilst suspended does nothing.
+///
+/// If dropped whilst resumed, it is suspended first.
+pub struct CoroutineAllocationContext<CoroutineHeapSize: MemorySize, StackSize: MemorySize, GTACSA: 'static + GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>>
+{
+	coroutine_local_allocator: Option<MemoryUsageTrackingAllocator<GTACSA::CoroutineLocalAllocator>>,
+
+	coroutine_memory_quota: Option<NonNull<CoroutineMemoryQuota>>,
+
+	current_allocator_in_use: CurrentAllocatorInUse,
+
+	stack_memory: Option<Box<CoroutineStackMemory<StackSize>>>,
+
+	resumed_on: Option<(&'static GTACSA, CurrentAllocatorInUse)>,
+}
+
+impl<CoroutineHeapSize: MemorySize, StackSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>> Drop for CoroutineAllocationContext<CoroutineHeapSize, StackSize, GTACSA>
+{
+	#[inline(always)]
+	fn drop(&mut self)
+	{
+		if self.is_resumed()
+		{
+			self.suspend()
+		}
+	}
+}
+
+impl<CoroutineHeapSize: MemorySize, StackSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>> Debug for CoroutineAllocationContext<CoroutineHeapSize, StackSize, GTACSA>
+{
+	#[inline(always)]
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result
+	{
+		write!(f, "CoroutineAllocationContext(coroutine_local_allocator: {:?}, current_allocator_in_use: {:?}, stack_memory: {:?}, is_resumed: {:?})", self.coroutine_local_allocator, self.current_allocator_in_use, self.stack_memory, self.is_resumed())
+	}
+}
+
+impl<CoroutineHeapSize: MemorySize, StackSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>> CoroutineAllocationContext<CoroutineHeapSize, StackSize, GTACSA>
+{
+	/// Creates a new instance, which is suspended.
+	///
+	/// When first resumed, the current allocator in use will be `CurrentAllocatorInUse::CoroutineLocal`.
+	///
+	/// The stack memory, if any, is owned and freed when this instance is dropped; it is boxed so that it does not move when this instance does.
+	///
+	/// # Safety
+	///
+	/// The memory quota, if any, is not owned; it must remain valid, and must not be moved, for as long as this instance lives.
+	/// This instance must not be forgotten (eg with `mem::forget()`) whilst resumed, as the memory quota would then remain attached.
+	#[inline(always)]
+	pub const unsafe fn new(coroutine_local_allocator: GTACSA::CoroutineLocalAllocator, coroutine_memory_quota: Option<NonNull<CoroutineMemoryQuota>>, stack_memory: Option<Box<CoroutineStackMemory<StackSize>>>) -> Self
+	{
+		Self
+		{
+			coroutine_local_allocator: Some(MemoryUsageTrackingAllocator::new(coroutine_local_allocator)),
+			coroutine_memory_quota,
+			current_allocator_in_use: CurrentAllocatorInUse::CoroutineLocal,
+			stack_memory,
+			resumed_on: None,
+		}
+	}
+
+	/// Is resumed?
+	#[inline(always)]
+	pub fn is_resumed(&self) -> bool
+	{
+		self.resumed_on.is_some()
+	}
+
+	/// The coroutine local allocator, with its memory usage, if suspended.
+	#[inline(always)]
+	pub fn coroutine_local_allocator(&self) -> Option<&MemoryUsageTrackingAllocator<GTACSA::CoroutineLocalAllocator>>
+	{
+		self.coroutine_local_allocator.as_ref()
+	}
+
+	/// The current allocator in use that will be restored when next resumed, if suspended.
+	#[inline(always)]
+	pub fn current_allocator_in_use(&self) -> CurrentAllocatorInUse
+	{
+		self.current_allocator_in_use
+	}
+
+	/// Stack memory, if any.
+	#[inline(always)]
+	pub fn stack_memory(&mut self) -> Option<&mut CoroutineStackMemory<StackSize>>
+	{
+		self.stack_memory.as_deref_mut()
+	}
+
+	/// Swaps in the coroutine local allocator and memory quota, then restores the coroutine's current allocator in use.
+	///
+	/// Call immediately before switching into the coroutine.
+	#[inline(always)]
+	pub fn resume_on(&mut self, global_allocator: &'static GTACSA)
+	{
+		debug_assert!(!self.is_resumed(), "Already resumed");
+		debug_assert!(global_allocator.coroutine_local_allocator().is_none(), "Another coroutine is already resumed on this thread; coroutines can not be nested");
+
+		// Safety: `new()` requires the memory quota to live for as long as this instance, and this instance is suspended before it is dropped.
+		let (was_coroutine_local_allocator, was_coroutine_memory_quota) = unsafe { global_allocator.swap_coroutine_local_allocator_with_memory_quota(self.coroutine_local_allocator.take(), self.coroutine_memory_quota.take()) };
+		debug_assert!(was_coroutine_local_allocator.is_none() && was_coroutine_memory_quota.is_none(), "Another coroutine was already resumed on this thread");
+
+		let restore_to = global_allocator.replace_current_allocator_in_use(self.current_allocator_in_use);
+		self.resumed_on = Some((global_allocator, restore_to))
+	}
+
+	/// Saves the coroutine's current allocator in use and restores the thread's, then swaps out the coroutine local allocator and memory quota.
+	///
+	/// Call immediately after switching out of the coroutine.
+	#[inline(always)]
+	pub fn suspend(&mut self)
+	{
+		debug_assert!(self.is_resumed(), "Not resumed");
+		let (global_allocator, restore_to) = match self.resumed_on.take()
+		{
+			Some(resumed_on) => resumed_on,
+			
+			None => return,
+		};
+
+		self.current_allocator_in_use = global_allocator.replace_current_allocator_in_use(restore_to);
+
+		// Safety: no memory quota is attached.
+		let (coroutine_local_allocator, coroutine_memory_quota) = unsafe { global_allocator.swap_coroutine_local_allocator_with_memory_quota(None, None) };
+		debug_assert!(coroutine_local_allocator.is_some(), "Coroutine local allocator was swapped out whilst resumed");
+		self.coroutine_local_allocator = coroutine_local_allocator;
+		self.coroutine_memory_quota = coroutine_memory_quota;
+	}
+}
+
+#[cfg(test)]
+mod CoroutineAllocationContextTests
+{
+	use super::*;
+
+	#[test]
+	pub fn resume_and_suspend_swap_allocation_state()
+	{
+		use self::CurrentAllocatorInUse::*;
+
+		let heap_memory_layout = Layout::new::<CoroutineHeapMemory<MemorySize64Kb>>();
+		let heap_memory = unsafe { System.alloc_zeroed(heap_memory_layout) } as *const CoroutineHeapMemory<MemorySize64Kb>;
+		assert!(heap_memory.is_not_null(), "Could not allocate heap memory");
+
+		let memory_quota = CoroutineMemoryQuota::new(4096, CoroutineMemoryQuotaPolicy::Fail, None);
+		let mut context = unsafe { CoroutineAllocationContext::<MemorySize64Kb, MemorySize64Kb, MyGlobalAllocator>::new(BumpAllocator::new(heap_memory.reference().into_memory_source()), Some(NonNull::from(&memory_quota)), None) };
+		assert!(!context.is_resumed());
+
+		context.resume_on(&MyGlobalAllocator);
+		let resumed_allocator_in_use = MyGlobalAllocator.save_current_allocator_in_use();
+		let (allocation, _) = MyGlobalAllocator.allocate(64.non_zero(), 8.non_zero()).expect("Did not allocate");
+		let coroutine_local_usage = MyGlobalAllocator.coroutine_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage);
+		let memory_quota_was_attached = MyGlobalAllocator.coroutine_memory_quota().is_some();
+		MyGlobalAllocator.restore_current_allocator_in_use(ThreadLocal);
+		context.suspend();
+
+		assert_eq!(resumed_allocator_in_use, CoroutineLocal, "Did not switch to the coroutine local allocator when first resumed");
+		assert_eq!(coroutine_local_usage, 64, "Did not allocate using the coroutine local allocator");
+		assert!(memory_quota_was_attached, "Did not attach memory quota");
+		assert_eq!(memory_quota.usage(), 64);
+		assert!(!context.is_resumed());
+		assert_eq!(context.current_allocator_in_use(), ThreadLocal, "Did not save the coroutine's allocator in use");
+		assert_eq!(MyGlobalAllocator.save_current_allocator_in_use(), Global, "Did not restore the thread's allocator in use");
+		assert!(MyGlobalAllocator.coroutine_local_allocator().is_none(), "Did not swap out the coroutine local allocator");
+		assert!(MyGlobalAllocator.coroutine_memory_quota().is_none(), "Did not detach memory quota");
+		assert_eq!(context.coroutine_local_allocator().expect("Did not keep the coroutine local allocator").memory_usage().usage(), 64, "Did not keep memory usage whilst suspended");
+
+		context.resume_on(&MyGlobalAllocator);
+		let resumed_allocator_in_use = MyGlobalAllocator.save_current_allocator_in_use();
+		MyGlobalAllocator.deallocate(64.non_zero(), 8.non_zero(), allocation);
+		drop(context);
+
+		assert_eq!(resumed_allocator_in_use, ThreadLocal, "Did not restore the coroutine's allocator in use when resumed");
+		assert_eq!(memory_quota.usage(), 0);
+		assert_eq!(MyGlobalAllocator.save_current_allocator_in_use(), Global, "Drop did not suspend");
+		assert!(MyGlobalAllocator.coroutine_local_allocator().is_none(), "Drop did not swap out the coroutine local allocator");
+		assert!(MyGlobalAllocator.coroutine_memory_quota().is_none(), "Drop did not detach memory quota");
+
+		unsafe { System.dealloc(heap_memory as *mut u8, heap_memory_layout) }
+	}
+}
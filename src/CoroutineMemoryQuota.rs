@@ -0,0 +1,180 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A memory quota for a coroutine.
+///
+/// Attached using `GlobalThreadAndCoroutineSwitchableAllocator::swap_coroutine_local_allocator_with_memory_quota()`.
+/// Whilst attached, bytes allocated from all tiers (coroutine-local, thread-local, stacked and global) are counted against it.
+///
+/// Usage is based on requested memory sizes, not actually allocated sizes.
+/// Memory allocated before the quota was attached should not be deallocated whilst it is attached; if it is, usage is reduced, but never below zero, and debug builds panic if it would have gone below zero.
+#[derive(Debug)]
+pub struct CoroutineMemoryQuota
+{
+	maximum_usage: u64,
+
+	policy: CoroutineMemoryQuotaPolicy,
+
+	peak_usage_reporter: Option<fn(u64)>,
+
+	usage: Cell<u64>,
+
+	peak_usage: Cell<u64>,
+}
+
+impl CoroutineMemoryQuota
+{
+	/// New instance.
+	///
+	/// `peak_usage_reporter`, if any, is called with the peak usage whenever the coroutine is swapped out.
+	#[inline(always)]
+	pub const fn new(maximum_usage: u64, policy: CoroutineMemoryQuotaPolicy, peak_usage_reporter: Option<fn(u64)>) -> Self
+	{
+		Self
+		{
+			maximum_usage,
+			policy,
+			peak_usage_reporter,
+			usage: Cell::new(0),
+			peak_usage: Cell::new(0),
+		}
+	}
+
+	/// Maximum usage.
+	#[inline(always)]
+	pub fn maximum_usage(&self) -> u64
+	{
+		self.maximum_usage
+	}
+
+	/// Current usage.
+	#[inline(always)]
+	pub fn usage(&self) -> u64
+	{
+		self.usage.get()
+	}
+
+	/// Peak usage.
+	#[inline(always)]
+	pub fn peak_usage(&self) -> u64
+	{
+		self.peak_usage.get()
+	}
+
+	#[inline(always)]
+	fn charge(&self, requested: NonZeroUsize) -> Result<(), AllocError>
+	{
+		let usage = self.usage() + (requested.get() as u64);
+
+		if unlikely!(usage > self.maximum_usage)
+		{
+			let permitted = match self.policy
+			{
+				CoroutineMemoryQuotaPolicy::Fail => false,
+
+				CoroutineMemoryQuotaPolicy::Callback(over_quota) => over_quota(self, requested),
+			};
+
+			if !permitted
+			{
+				return Err(AllocError)
+			}
+		}
+
+		self.usage.set(usage);
+		self.peak_usage.set(max(self.peak_usage(), usage));
+		Ok(())
+	}
+
+	#[inline(always)]
+	fn refund(&self, released: NonZeroUsize)
+	{
+		self.usage.set(self.usage().saturating_sub(released.get() as u64))
+	}
+
+	#[inline(always)]
+	fn swapped_out(&self)
+	{
+		if let Some(peak_usage_reporter) = self.peak_usage_reporter
+		{
+			peak_usage_reporter(self.peak_usage())
+		}
+	}
+}
+
+#[cfg(test)]
+mod CoroutineMemoryQuotaTests
+{
+	use super::*;
+
+	#[test]
+	pub fn charges_and_refunds_track_usage_and_peak_usage()
+	{
+		let memory_quota = CoroutineMemoryQuota::new(1024, CoroutineMemoryQuotaPolicy::Fail, None);
+
+		memory_quota.charge(256.non_zero()).expect("Did not charge within quota");
+		memory_quota.charge(768.non_zero()).expect("Did not charge up to quota");
+		assert_eq!(memory_quota.usage(), 1024);
+
+		memory_quota.refund(512.non_zero());
+		assert_eq!(memory_quota.usage(), 512);
+		assert_eq!(memory_quota.peak_usage(), 1024, "Refund reduced peak usage");
+
+		memory_quota.refund(1024.non_zero());
+		assert_eq!(memory_quota.usage(), 0, "Refund did not saturate at zero")
+	}
+
+	#[test]
+	pub fn fail_policy_refuses_charges_over_quota()
+	{
+		let memory_quota = CoroutineMemoryQuota::new(1024, CoroutineMemoryQuotaPolicy::Fail, None);
+
+		memory_quota.charge(1000.non_zero()).expect("Did not charge within quota");
+		assert!(memory_quota.charge(25.non_zero()).is_err(), "Charged over quota");
+		assert_eq!(memory_quota.usage(), 1000, "Refused charge changed usage");
+		assert_eq!(memory_quota.peak_usage(), 1000, "Refused charge changed peak usage")
+	}
+
+	#[test]
+	pub fn callback_policy_decides_charges_over_quota()
+	{
+		fn permit_up_to_double(memory_quota: &CoroutineMemoryQuota, requested: NonZeroUsize) -> bool
+		{
+			memory_quota.usage() + (requested.get() as u64) <= 2 * memory_quota.maximum_usage()
+		}
+
+		let memory_quota = CoroutineMemoryQuota::new(1024, CoroutineMemoryQuotaPolicy::Callback(permit_up_to_double), None);
+
+		memory_quota.charge(1536.non_zero()).expect("Callback did not permit charge");
+		assert_eq!(memory_quota.usage(), 1536);
+		assert!(memory_quota.charge(1024.non_zero()).is_err(), "Callback did not refuse charge");
+		assert_eq!(memory_quota.usage(), 1536, "Refused charge changed usage")
+	}
+
+	#[test]
+	pub fn peak_usage_is_reported_when_swapped_out()
+	{
+		static ReportedPeakUsage: AtomicU64 = AtomicU64::new(0);
+
+		fn record_peak_usage(peak_usage: u64)
+		{
+			ReportedPeakUsage.store(peak_usage, Relaxed)
+		}
+
+		let memory_quota = CoroutineMemoryQuota::new(1024, CoroutineMemoryQuotaPolicy::Fail, Some(record_peak_usage));
+		let memory_quota_pointer = NonNull::from(&memory_quota);
+
+		unsafe { MyGlobalAllocator.swap_coroutine_local_allocator_with_memory_quota(None, Some(memory_quota_pointer)) };
+		let (allocation, _) = MyGlobalAllocator.allocate(256.non_zero(), 8.non_zero()).expect("Did not allocate");
+		let refused = MyGlobalAllocator.allocate(1024.non_zero(), 8.non_zero());
+		MyGlobalAllocator.deallocate(256.non_zero(), 8.non_zero(), allocation);
+		let (_, detached_memory_quota) = unsafe { MyGlobalAllocator.swap_coroutine_local_allocator_with_memory_quota(None, None) };
+
+		assert!(refused.is_err(), "Allocated over quota");
+		assert_eq!(detached_memory_quota, Some(memory_quota_pointer), "Did not detach memory quota");
+		assert_eq!(memory_quota.usage(), 0);
+		assert_eq!(memory_quota.peak_usage(), 256);
+		assert_eq!(ReportedPeakUsage.load(Relaxed), 256, "Did not report peak usage when swapped out")
+	}
+}
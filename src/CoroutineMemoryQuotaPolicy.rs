@@ -0,0 +1,16 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// What to do when an allocation would exceed a `CoroutineMemoryQuota`.
+#[derive(Debug, Copy, Clone)]
+pub enum CoroutineMemoryQuotaPolicy
+{
+	/// Fail the allocation with `AllocError`.
+	Fail,
+
+	/// Call back with the quota and the requested size; return `true` to permit the allocation anyway.
+	///
+	/// The callback must not allocate.
+	Callback(fn(&CoroutineMemoryQuota, NonZeroUsize) -> bool),
+}
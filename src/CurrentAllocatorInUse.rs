@@ -16,4 +16,38 @@ pub enum CurrentAllocatorInUse
 
 	/// A global allocator.
 	Global,
+
+	/// The local allocator at the top of the local allocator stack.
+	Stacked,
+}
+
+impl CurrentAllocatorInUse
+{
+	/// Encodes an optional tier as a small integer, with `0` for `None`; suitable for atomics and compact records.
+	#[inline(always)]
+	pub(crate) const fn encode(tier: Option<Self>) -> usize
+	{
+		match tier
+		{
+			None => 0,
+			Some(tier) => (tier as usize) + 1,
+		}
+	}
+
+	/// Decodes a tier encoded with `encode()`.
+	#[inline(always)]
+	pub(crate) fn decode(encoded: usize) -> Option<Self>
+	{
+		use self::CurrentAllocatorInUse::*;
+
+		match encoded
+		{
+			0 => None,
+			1 => Some(CoroutineLocal),
+			2 => Some(ThreadLocal),
+			3 => Some(Global),
+			4 => Some(Stacked),
+			_ => unreachable!("Invalid tier"),
+		}
+	}
 }
@@ -0,0 +1,104 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Restores the previous `CurrentAllocatorInUse` when dropped, including when dropped during unwinding.
+///
+/// Unlike the `callback_with_*` methods of `GlobalThreadAndCoroutineSwitchableAllocator`, there is no requirement for code to be `UnwindSafe`.
+///
+/// Guards nest; they should be dropped in the reverse order to that in which they were created, which is what happens naturally with lexical scopes.
+///
+/// Created using `GlobalThreadAndCoroutineSwitchableAllocator::with_coroutine_local()`, `with_thread_local()` or `with_global()`.
+///
+/// Not `Send`, as the current allocator in use is per-thread state.
+#[must_use = "The previous allocator in use is restored as soon as the guard is dropped"]
+pub struct CurrentAllocatorInUseGuard<'a, CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>>
+{
+	global_allocator: &'a GTACSA,
+	restore_to: CurrentAllocatorInUse,
+	marker: PhantomData<(CoroutineHeapSize, *const ())>,
+}
+
+impl<'a, CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>> Drop for CurrentAllocatorInUseGuard<'a, CoroutineHeapSize, GTACSA>
+{
+	#[inline(always)]
+	fn drop(&mut self)
+	{
+		self.global_allocator.restore_current_allocator_in_use(self.restore_to)
+	}
+}
+
+impl<'a, CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>> Debug for CurrentAllocatorInUseGuard<'a, CoroutineHeapSize, GTACSA>
+{
+	#[inline(always)]
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result
+	{
+		write!(f, "CurrentAllocatorInUseGuard(restore_to: {:?})", self.restore_to)
+	}
+}
+
+impl<'a, CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>> CurrentAllocatorInUseGuard<'a, CoroutineHeapSize, GTACSA>
+{
+	#[inline(always)]
+	fn new(global_allocator: &'a GTACSA, different: CurrentAllocatorInUse) -> Self
+	{
+		Self
+		{
+			global_allocator,
+			restore_to: global_allocator.replace_current_allocator_in_use(different),
+			marker: PhantomData,
+		}
+	}
+
+	/// The allocator in use that will be restored when this guard is dropped.
+	#[inline(always)]
+	pub fn restore_to(&self) -> CurrentAllocatorInUse
+	{
+		self.restore_to
+	}
+}
+
+#[cfg(test)]
+mod CurrentAllocatorInUseGuardTests
+{
+	use super::*;
+
+	#[test]
+	pub fn nested_guards_restore_the_previous_allocator_in_use()
+	{
+		use self::CurrentAllocatorInUse::*;
+
+		assert_eq!(MyGlobalAllocator.save_current_allocator_in_use(), Global);
+		{
+			let thread_local = MyGlobalAllocator.with_thread_local();
+			assert_eq!(thread_local.restore_to(), Global);
+			assert_eq!(MyGlobalAllocator.save_current_allocator_in_use(), ThreadLocal);
+			{
+				let coroutine_local = MyGlobalAllocator.with_coroutine_local();
+				assert_eq!(coroutine_local.restore_to(), ThreadLocal);
+				assert_eq!(MyGlobalAllocator.save_current_allocator_in_use(), CoroutineLocal);
+			}
+			assert_eq!(MyGlobalAllocator.save_current_allocator_in_use(), ThreadLocal);
+		}
+		assert_eq!(MyGlobalAllocator.save_current_allocator_in_use(), Global)
+	}
+
+	#[test]
+	pub fn guard_restores_the_previous_allocator_in_use_when_unwinding()
+	{
+		use self::CurrentAllocatorInUse::*;
+
+		let thread_local = MyGlobalAllocator.with_thread_local();
+		let result = catch_unwind(||
+		{
+			let _global = MyGlobalAllocator.with_global();
+			resume_unwind(Box::new(()))
+		});
+		let restored = MyGlobalAllocator.save_current_allocator_in_use();
+		drop(thread_local);
+
+		assert!(result.is_err(), "Did not unwind");
+		assert_eq!(restored, ThreadLocal, "Did not restore the allocator in use when unwinding");
+		assert_eq!(MyGlobalAllocator.save_current_allocator_in_use(), Global)
+	}
+}
@@ -0,0 +1,28 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// What a `GlobalThreadAndCoroutineSwitchableAllocatorInstance` does when the allocator for the current allocator in use is exhausted.
+///
+/// Spills go from coroutine-local (or stacked) to thread-local to global; each spill is counted in `ExhaustionSpills`.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum ExhaustionPolicy
+{
+	/// Fail with `AllocError`.
+	Strict,
+
+	/// Spill coroutine-local and stacked allocations to the thread-local allocator, but no further.
+	SpillToThreadLocal,
+
+	/// Spill coroutine-local and stacked allocations to the thread-local allocator, and then thread-local allocations to the global allocator.
+	SpillToGlobal,
+}
+
+impl Default for ExhaustionPolicy
+{
+	#[inline(always)]
+	fn default() -> Self
+	{
+		ExhaustionPolicy::Strict
+	}
+}
@@ -0,0 +1,14 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Counts of allocations which spilled to another tier because of an `ExhaustionPolicy`, across all threads.
+#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ExhaustionSpills
+{
+	/// Allocations which spilled to the thread-local allocator.
+	pub to_thread_local: u64,
+
+	/// Allocations which spilled to the global allocator.
+	pub to_global: u64,
+}
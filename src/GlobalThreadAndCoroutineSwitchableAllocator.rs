@@ -16,6 +16,15 @@ pub trait GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize: MemoryS
 	/// Type of the global allocator.
 	type GlobalAllocator: Allocator;
 	
+	/// Coroutine local allocator memory usage.
+	///
+	/// Panics if there is no coroutine local allocator.
+	#[inline(always)]
+	fn coroutine_local_allocator_memory_usage<R, LAMUU: FnOnce(&LocalAllocatorMemoryUsage) -> R>(&self, local_allocator_message_usage_user: LAMUU) -> R
+	{
+		local_allocator_message_usage_user(self.coroutine_local_allocator().expect("Coroutine local allocator needs to have been swapped in").memory_usage())
+	}
+	
 	/// Thread-local allocator memory usage.
 	///
 	/// Panics if the thread-local allocator has not been initialized.
@@ -25,66 +34,280 @@ pub trait GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize: MemoryS
 		local_allocator_message_usage_user(self.thread_local_allocator().expect("Thread local allocator needs to have been initialized").memory_usage())
 	}
 	
+	/// Global allocator memory usage by the current thread.
+	///
+	/// Memory allocated from the global allocator by one thread may be deallocated by another; usage is only approximate if this happens.
+	#[inline(always)]
+	fn global_allocator_memory_usage<R, LAMUU: FnOnce(&LocalAllocatorMemoryUsage) -> R>(&self, local_allocator_message_usage_user: LAMUU) -> R
+	{
+		local_allocator_message_usage_user(self.global_allocator_memory_usage_of_current_thread())
+	}
+	
+	/// Memory usage of all local allocators pushed by the current thread.
+	#[inline(always)]
+	fn stacked_allocator_memory_usage<R, LAMUU: FnOnce(&LocalAllocatorMemoryUsage) -> R>(&self, local_allocator_message_usage_user: LAMUU) -> R
+	{
+		local_allocator_message_usage_user(self.stacked_allocator_memory_usage_of_current_thread())
+	}
+	
+	/// Statistics of each tier in use by the current thread, labelled with the current thread's name (or identifier if unnamed).
+	///
+	/// Only the current thread's statistics are returned; to export statistics of several threads, collect them on each thread and concatenate them.
+	/// Local allocators pushed by the current thread are reported together as the `Stacked` tier, if any have been used.
+	///
+	/// Allocates using the global allocator.
+	#[inline(always)]
+	fn allocator_statistics(&self) -> Vec<AllocatorStatistics> where Self: Sized
+	{
+		use self::CurrentAllocatorInUse::*;
+		
+		let _guard = self.with_global();
+		
+		let thread = current();
+		let thread = match thread.name()
+		{
+			Some(name) => name.to_string(),
+			None => format!("{:?}", thread.id()),
+		};
+		
+		let mut allocator_statistics = Vec::with_capacity(4);
+		if let Some(coroutine_local_allocator) = self.coroutine_local_allocator()
+		{
+			allocator_statistics.push(AllocatorStatistics::new(&thread, CoroutineLocal, type_name::<Self::CoroutineLocalAllocator>(), coroutine_local_allocator.memory_usage().snapshot()))
+		}
+		if let Some(thread_local_allocator) = self.thread_local_allocator()
+		{
+			allocator_statistics.push(AllocatorStatistics::new(&thread, ThreadLocal, type_name::<Self::ThreadLocalAllocator>(), thread_local_allocator.memory_usage().snapshot()))
+		}
+		allocator_statistics.push(AllocatorStatistics::new(&thread, Global, type_name::<Self::GlobalAllocator>(), self.global_allocator_memory_usage_of_current_thread().snapshot()));
+		let stacked_allocator_memory_usage = self.stacked_allocator_memory_usage_of_current_thread().snapshot();
+		if !self.local_allocator_stack().is_empty() || stacked_allocator_memory_usage.allocations != 0
+		{
+			allocator_statistics.push(AllocatorStatistics::new(&thread, Stacked, type_name::<StackedLocalAllocator>(), stacked_allocator_memory_usage))
+		}
+		allocator_statistics
+	}
+	
+	/// Writes the statistics of each tier in use by the current thread in the Prometheus text exposition format.
+	///
+	/// Allocates using the global allocator.
+	#[inline(always)]
+	fn write_allocator_statistics_in_prometheus_format(&self, writer: &mut impl Write) -> io::Result<()> where Self: Sized
+	{
+		AllocatorStatistics::write_prometheus::<CoroutineHeapSize, Self>(self, &self.allocator_statistics(), writer)
+	}
+	
+	/// Writes the statistics of each tier in use by the current thread as JSON.
+	///
+	/// Allocates using the global allocator.
+	#[inline(always)]
+	fn write_allocator_statistics_in_json_format(&self, writer: &mut impl Write) -> io::Result<()> where Self: Sized
+	{
+		AllocatorStatistics::write_json::<CoroutineHeapSize, Self>(self, &self.allocator_statistics(), writer)
+	}
+	
 	#[doc(hidden)]
 	#[inline(always)]
-	fn coroutine_local_allocator(&self) -> Option<&Self::CoroutineLocalAllocator>
+	fn coroutine_local_allocator(&self) -> Option<&MemoryUsageTrackingAllocator<Self::CoroutineLocalAllocator>>
 	{
 		self.use_per_thread_state(|per_thread_state| match &per_thread_state.coroutine_local_allocator
 		{
-			&Some(ref x) => Some(unsafe { & * (x as *const Self::CoroutineLocalAllocator) }),
+			&Some(ref x) => Some(unsafe { & * (x as *const MemoryUsageTrackingAllocator<Self::CoroutineLocalAllocator>) }),
 			&None => None,
 		})
 	}
 	
 	#[doc(hidden)]
 	#[inline(always)]
-	fn thread_local_allocator(&self) -> Option<&MemoryUsageTrackingThreadLocalAllocator<Self::ThreadLocalAllocator>>
+	fn thread_local_allocator(&self) -> Option<&MemoryUsageTrackingAllocator<Self::ThreadLocalAllocator>>
 	{
 		self.use_per_thread_state(|per_thread_state| match &per_thread_state.thread_local_allocator
 		{
-			&Some(ref x) => Some(unsafe { & * (x as *const MemoryUsageTrackingThreadLocalAllocator<Self::ThreadLocalAllocator>) }),
+			&Some(ref x) => Some(unsafe { & * (x as *const MemoryUsageTrackingAllocator<Self::ThreadLocalAllocator>) }),
 			&None => None,
 		})
 	}
 	
+	#[doc(hidden)]
+	#[inline(always)]
+	fn global_allocator_memory_usage_of_current_thread(&self) -> &LocalAllocatorMemoryUsage
+	{
+		self.use_per_thread_state(|per_thread_state| unsafe { & * (&per_thread_state.global_allocator_memory_usage as *const LocalAllocatorMemoryUsage) })
+	}
+	
+	#[doc(hidden)]
+	#[inline(always)]
+	fn stacked_allocator_memory_usage_of_current_thread(&self) -> &LocalAllocatorMemoryUsage
+	{
+		self.use_per_thread_state(|per_thread_state| unsafe { & * (&per_thread_state.stacked_allocator_memory_usage as *const LocalAllocatorMemoryUsage) })
+	}
+	
+	#[doc(hidden)]
+	#[inline(always)]
+	fn local_allocator_stack(&self) -> &LocalAllocatorStack
+	{
+		self.use_per_thread_state(|per_thread_state| unsafe { & * (&per_thread_state.local_allocator_stack as *const LocalAllocatorStack) })
+	}
+	
 	#[doc(hidden)]
 	fn global_allocator(&self) -> &Self::GlobalAllocator;
 	
+	/// Counts of allocations which spilled to another tier because of the exhaustion policy, across all threads.
+	///
+	/// Implementations which do not spill need not override this; it returns no spills.
+	#[inline(always)]
+	fn exhaustion_spills(&self) -> ExhaustionSpills
+	{
+		ExhaustionSpills::default()
+	}
+	
 	/// Swaps the coroutine local allocator.
 	///
 	/// Used before calling a coroutine.
 	///
 	/// Used after calling a coroutine.
+	///
+	/// The coroutine local allocator's memory usage is not kept when it is swapped out; use `swap_coroutine_local_allocator_tracking_memory_usage()` to keep it.
+	///
+	/// Detaches any coroutine memory quota.
 	#[inline(always)]
 	fn swap_coroutine_local_allocator(&self, replacement: Option<Self::CoroutineLocalAllocator>) -> Option<Self::CoroutineLocalAllocator>
 	{
-		self.use_per_thread_state(|per_thread_state| replace(&mut per_thread_state.coroutine_local_allocator, replacement))
+		self.swap_coroutine_local_allocator_tracking_memory_usage(replacement.map(MemoryUsageTrackingAllocator::new)).map(MemoryUsageTrackingAllocator::into_allocator)
+	}
+	
+	/// Swaps the coroutine local allocator together with its memory usage, which is tracked whilst it is swapped in.
+	///
+	/// Detaches any coroutine memory quota.
+	#[inline(always)]
+	fn swap_coroutine_local_allocator_tracking_memory_usage(&self, replacement: Option<MemoryUsageTrackingAllocator<Self::CoroutineLocalAllocator>>) -> Option<MemoryUsageTrackingAllocator<Self::CoroutineLocalAllocator>>
+	{
+		// Safety: no memory quota is attached.
+		unsafe { self.swap_coroutine_local_allocator_with_memory_quota(replacement, None).0 }
+	}
+	
+	/// Swaps the coroutine local allocator and the coroutine memory quota.
+	///
+	/// Whilst attached, the memory quota counts bytes allocated from all tiers.
+	/// When a memory quota is detached, it reports its peak usage.
+	///
+	/// Memory of a coroutine local allocator deallocated whilst it is swapped out, by any thread, is queued and deallocated when it is next swapped in.
+	///
+	/// # Safety
+	///
+	/// `replacement_memory_quota`, if any, is dereferenced by every allocation, deallocation and reallocation on the current thread until it is detached by a subsequent swap, so it must remain valid, and must not be moved, until then.
+	/// It must not be attached on more than one thread at once, as it is not thread-safe.
+	#[inline(always)]
+	unsafe fn swap_coroutine_local_allocator_with_memory_quota(&self, replacement: Option<MemoryUsageTrackingAllocator<Self::CoroutineLocalAllocator>>, replacement_memory_quota: Option<NonNull<CoroutineMemoryQuota>>) -> (Option<MemoryUsageTrackingAllocator<Self::CoroutineLocalAllocator>>, Option<NonNull<CoroutineMemoryQuota>>)
+	{
+		if let Some(ref coroutine_local_allocator) = replacement
+		{
+			let memory_range = coroutine_local_allocator.memory_range();
+			let (memory_range_owner, _registered) = MemoryRangeRegistry::owner_of_registering_if_necessary(memory_range);
+			let remote_free_queue = match memory_range_owner.remote_free_queue()
+			{
+				Some(remote_free_queue) => remote_free_queue,
+				
+				None => RemoteFreeQueue::register(memory_range),
+			};
+			memory_range_owner.assign(CurrentAllocatorInUse::CoroutineLocal, Some(remote_free_queue));
+			remote_free_queue.drain_into(coroutine_local_allocator);
+		}
+		
+		let (coroutine_local_allocator, coroutine_memory_quota) = self.use_per_thread_state(|per_thread_state| (replace(&mut per_thread_state.coroutine_local_allocator, replacement), replace(&mut per_thread_state.coroutine_memory_quota, replacement_memory_quota)));
+		if let Some(coroutine_memory_quota) = coroutine_memory_quota
+		{
+			coroutine_memory_quota.reference().swapped_out()
+		}
+		(coroutine_local_allocator, coroutine_memory_quota)
+	}
+	
+	#[doc(hidden)]
+	#[inline(always)]
+	fn coroutine_memory_quota(&self) -> Option<&CoroutineMemoryQuota>
+	{
+		self.use_per_thread_state(|per_thread_state| per_thread_state.coroutine_memory_quota.map(|coroutine_memory_quota| coroutine_memory_quota.reference()))
+	}
+
+	/// Pushes a local allocator onto the local allocator stack and switches the current allocator in use to `CurrentAllocatorInUse::Stacked`, until the returned guard is dropped.
+	///
+	/// Whilst pushed, deallocations and reallocations of memory owned by `local_allocator` are routed to it, even if it is no longer at the top of the stack.
+	///
+	/// Panics if the local allocator stack is full (see `LocalAllocatorStack::MaximumDepth`).
+	/// Aborts if guards are dropped in a different order to the reverse of that in which they were created.
+	///
+	/// # Safety
+	///
+	/// The local allocator stack refers to `local_allocator` until the returned guard is dropped, so the guard must be dropped, and not forgotten (eg with `mem::forget()`), before `local_allocator` is moved or dropped.
+	/// Guards must be dropped on the thread which created them, in the reverse order to that in which they were created.
+	/// Memory allocated from `local_allocator` must not be deallocated or reallocated after the guard is dropped.
+	#[inline(always)]
+	unsafe fn push_local_allocator<'a, MS: MemorySource, LA: LocalAllocator<MS>>(&'a self, local_allocator: &'a LA) -> LocalAllocatorStackGuard<'a, CoroutineHeapSize, Self> where Self: Sized
+	{
+		LocalAllocatorStackGuard::new::<MS, LA>(self, local_allocator)
 	}
 
 	/// Initializes the thread-local allocator.
+	///
+	/// Its memory range is registered so that other threads can queue deallocations of its memory to it; these are drained on this thread's next allocation or by `drain_remote_frees()`.
 	#[inline(always)]
 	fn initialize_thread_local_allocator(&self, thread_local_allocator: Self::ThreadLocalAllocator)
 	{
+		let memory_range = thread_local_allocator.memory_range();
+		let (memory_range_owner, registered) = MemoryRangeRegistry::owner_of_registering_if_necessary(memory_range);
+		let remote_free_queue = RemoteFreeQueue::register(memory_range);
+		memory_range_owner.assign(CurrentAllocatorInUse::ThreadLocal, Some(remote_free_queue));
 		self.use_per_thread_state(|per_thread_state|
 		{
 			debug_assert!(per_thread_state.thread_local_allocator.is_none(), "Already initialized thread-local allocator");
 			
-			per_thread_state.thread_local_allocator = Some(MemoryUsageTrackingThreadLocalAllocator::new(thread_local_allocator))
+			per_thread_state.thread_local_allocator = Some(MemoryUsageTrackingAllocator::new(thread_local_allocator));
+			per_thread_state.remote_free_queue = Some(remote_free_queue);
+			per_thread_state.registered_thread_local_memory_range_owner = if registered
+			{
+				Some(memory_range_owner)
+			}
+			else
+			{
+				None
+			}
 		})
 	}
 
 	/// Drops the thread-local allocator.
 	///
+	/// Any deallocations queued by other threads are drained first.
+	/// Other threads must not deallocate or reallocate its memory afterwards.
+	///
 	/// Panics in debug if no thread-local allocator has been initialized with `initialize_thread_local_allocator()`.
 	#[inline(always)]
 	fn drop_thread_local_allocator(&self)
 	{
-		self.use_per_thread_state(|per_thread_state|
-		{
-			debug_assert!(per_thread_state.thread_local_allocator.is_some(), "Already deinitialized thread-local allocator");
-			
-			per_thread_state.thread_local_allocator = None
-		})
+		self.use_per_thread_state(|per_thread_state| per_thread_state.drop_thread_local_allocator())
+	}
+	
+	/// Drops the thread-local allocator automatically when the current thread exits, an opt-in alternative to calling `drop_thread_local_allocator()`.
+	///
+	/// If memory is still allocated from it, `outstanding_memory_reporter` is called with the number of bytes, as reported by `LocalAllocatorMemoryUsage::usage()`, before it is dropped; that memory must not be used, deallocated or reallocated afterwards.
+	/// Thread-locals are destroyed in the reverse order to that in which they were first used, so call this early to drop the thread-local allocator after the destructors of other thread-locals have run.
+	/// The current allocator in use is switched to global, and no thread-local allocator is created by a `ThreadLocalAllocatorFactory` thereafter.
+	///
+	/// Call after `initialize_thread_local_allocator()`; it is harmless to also call `drop_thread_local_allocator()` before the thread exits.
+	#[inline(always)]
+	fn drop_thread_local_allocator_on_thread_exit(&self, outstanding_memory_reporter: fn(u64)) where Self: Sized
+	{
+		ThreadExitTeardown::register::<CoroutineHeapSize, Self>(self, outstanding_memory_reporter)
+	}
+	
+	/// Deallocates any memory of the thread-local allocator that other threads have queued for deallocation.
+	///
+	/// This happens automatically on the next allocation made by this thread; call it to reclaim memory sooner, eg when a thread is idle.
+	///
+	/// Returns the number of deallocations drained.
+	#[inline(always)]
+	fn drain_remote_frees(&self) -> usize
+	{
+		self.use_per_thread_state(|per_thread_state| per_thread_state.drain_remote_frees())
 	}
 	
 	/// Replace the current allocator in use.
@@ -121,26 +344,49 @@ pub trait GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize: MemoryS
 		self.use_per_thread_state(|per_thread_state| per_thread_state.current_allocator_in_use = restore_to)
 	}
 	
+	/// Callback with the coroutine local allocator, detailing changes in memory usage.
+	///
+	/// Panics if there is no coroutine local allocator.
+	#[inline(always)]
+	fn callback_with_coroutine_local_allocator_detailing_memory_usage<F: FnOnce() -> R + UnwindSafe, R>(&self, our_usage: &Cell<u64>, callback: F) -> R
+	{
+		self.callback_with_different_current_allocator_detailing_memory_usage(CurrentAllocatorInUse::CoroutineLocal, |global_allocator| global_allocator.coroutine_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), our_usage, callback)
+	}
+	
 	/// Callback with the thread-local allocator, detailing changes in memory usage.
 	/// 
 	/// Panics if the thread-local allocator has not been initialized.
 	#[inline(always)]
 	fn callback_with_thread_local_allocator_detailing_memory_usage<F: FnOnce() -> R + UnwindSafe, R>(&self, our_usage: &Cell<u64>, callback: F) -> R
 	{
-		let thread_local_allocator_memory_usage_before = self.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage);
-		let result = self.callback_with_thread_local_allocator(callback);
-		let thread_local_allocator_memory_usage_after = self.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage);
+		self.callback_with_different_current_allocator_detailing_memory_usage(CurrentAllocatorInUse::ThreadLocal, |global_allocator| global_allocator.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), our_usage, callback)
+	}
+	
+	/// Callback with the global allocator, detailing changes in memory usage by the current thread.
+	#[inline(always)]
+	fn callback_with_global_allocator_detailing_memory_usage<F: FnOnce() -> R + UnwindSafe, R>(&self, our_usage: &Cell<u64>, callback: F) -> R
+	{
+		self.callback_with_different_current_allocator_detailing_memory_usage(CurrentAllocatorInUse::Global, |global_allocator| global_allocator.global_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), our_usage, callback)
+	}
+	
+	#[doc(hidden)]
+	#[inline(always)]
+	fn callback_with_different_current_allocator_detailing_memory_usage<F: FnOnce() -> R + UnwindSafe, R>(&self, different: CurrentAllocatorInUse, memory_usage: impl Fn(&Self) -> u64, our_usage: &Cell<u64>, callback: F) -> R
+	{
+		let memory_usage_before = memory_usage(self);
+		let result = self.callback_with_different_current_allocator(different, callback);
+		let memory_usage_after = memory_usage(self);
 		
 		let was_our_usage = our_usage.get();
 		our_usage.set
 		(
-			if thread_local_allocator_memory_usage_after >= thread_local_allocator_memory_usage_before
+			if memory_usage_after >= memory_usage_before
 			{
-				was_our_usage + (thread_local_allocator_memory_usage_after - thread_local_allocator_memory_usage_before)
+				was_our_usage + (memory_usage_after - memory_usage_before)
 			}
 			else
 			{
-				was_our_usage - (thread_local_allocator_memory_usage_before - thread_local_allocator_memory_usage_after)
+				was_our_usage.saturating_sub(memory_usage_before - memory_usage_after)
 			}
 		);
 		
@@ -161,6 +407,27 @@ pub trait GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize: MemoryS
 		self.callback_with_different_current_allocator(CurrentAllocatorInUse::Global, callback)
 	}
 	
+	/// Switch the current allocator in use to coroutine local until the returned guard is dropped.
+	#[inline(always)]
+	fn with_coroutine_local(&self) -> CurrentAllocatorInUseGuard<'_, CoroutineHeapSize, Self> where Self: Sized
+	{
+		CurrentAllocatorInUseGuard::new(self, CurrentAllocatorInUse::CoroutineLocal)
+	}
+
+	/// Switch the current allocator in use to thread-local until the returned guard is dropped.
+	#[inline(always)]
+	fn with_thread_local(&self) -> CurrentAllocatorInUseGuard<'_, CoroutineHeapSize, Self> where Self: Sized
+	{
+		CurrentAllocatorInUseGuard::new(self, CurrentAllocatorInUse::ThreadLocal)
+	}
+
+	/// Switch the current allocator in use to global until the returned guard is dropped.
+	#[inline(always)]
+	fn with_global(&self) -> CurrentAllocatorInUseGuard<'_, CoroutineHeapSize, Self> where Self: Sized
+	{
+		CurrentAllocatorInUseGuard::new(self, CurrentAllocatorInUse::Global)
+	}
+
 	#[doc(hidden)]
 	#[inline(always)]
 	fn callback_with_different_current_allocator<F: FnOnce() -> R + UnwindSafe, R>(&self, different: CurrentAllocatorInUse, callback: F) -> R
@@ -186,3 +453,22 @@ pub trait GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize: MemoryS
 	#[doc(hidden)]
 	fn per_thread_state(&self) -> fn() -> NonNull<PerThreadState<CoroutineHeapSize, Self::CoroutineLocalAllocator, Self::ThreadLocalAllocator>>;
 }
+
+#[cfg(test)]
+mod GlobalThreadAndCoroutineSwitchableAllocatorTests
+{
+	use super::*;
+
+	#[test]
+	pub fn detailed_memory_usage_never_goes_below_zero()
+	{
+		let our_usage = Cell::new(0);
+
+		let (allocation, _) = MyGlobalAllocator.callback_with_global_allocator_detailing_memory_usage(&our_usage, || MyGlobalAllocator.allocate(64.non_zero(), 8.non_zero())).expect("Did not allocate");
+		assert_eq!(our_usage.get(), 64);
+
+		our_usage.set(0);
+		MyGlobalAllocator.callback_with_global_allocator_detailing_memory_usage(&our_usage, || MyGlobalAllocator.deallocate(64.non_zero(), 8.non_zero(), allocation));
+		assert_eq!(our_usage.get(), 0, "Deallocating memory allocated before the callback did not saturate")
+	}
+}
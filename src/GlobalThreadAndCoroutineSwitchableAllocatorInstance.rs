@@ -10,6 +10,16 @@ pub struct GlobalThreadAndCoroutineSwitchableAllocatorInstance<CoroutineHeapSize
 {
 	global_allocator: GlobalAllocator,
 	
+	thread_local_allocator_factory: ThreadLocalAllocatorFactory<ThreadLocalAllocator>,
+	
+	lazily_created_thread_local_allocator_outstanding_memory_reporter: fn(u64),
+	
+	exhaustion_policy: ExhaustionPolicy,
+	
+	spills_to_thread_local: AtomicU64,
+	
+	spills_to_global: AtomicU64,
+	
 	per_thread_state: fn() -> NonNull<PerThreadState<CoroutineHeapSize, CoroutineLocalAllocator, ThreadLocalAllocator>>,
 	
 	marker: PhantomData<(CoroutineHeapSize, CoroutineLocalAllocator, ThreadLocalAllocator)>,
@@ -39,26 +49,56 @@ unsafe impl<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocat
 
 macro_rules! choose_allocator
 {
-	($self: ident, $current_memory: ident, $callback: ident, $($argument: ident),*) =>
+	($self: ident, $current_memory: ident, $callback: ident, $owned_by_allocator_not_in_use_callback: ident, $($argument: ident),*) =>
 	{
 		{
-			if let Some(coroutine_local_allocator) = $self.coroutine_local_allocator()
+			use self::CurrentAllocatorInUse::*;
+
+			let memory_range_owner = match MemoryRangeRegistry::owner_of($current_memory)
 			{
-				if likely!(coroutine_local_allocator.contains($current_memory))
-				{
-					return coroutine_local_allocator.$callback($($argument, )*)
-				}
-			}
+				Some(memory_range_owner) => memory_range_owner,
+
+				None => return $self.global_allocator_memory_usage_of_current_thread().$callback($self.global_allocator(), $($argument, )*),
+			};
 
-			if let Some(thread_local_allocator) = $self.thread_local_allocator()
+			let tier = match memory_range_owner.tier()
 			{
-				if likely!(thread_local_allocator.contains($current_memory))
+				Some(tier) => tier,
+
+				// Registered, but never put into use by a local allocator, eg the memory of a global allocator which uses a `MemoryMapSource`.
+				None => return $self.global_allocator_memory_usage_of_current_thread().$callback($self.global_allocator(), $($argument, )*),
+			};
+
+			if likely!(memory_range_owner.is_owned_by_current_thread())
+			{
+				match tier
 				{
-					return thread_local_allocator.$callback($($argument, )*)
+					CoroutineLocal => if let Some(coroutine_local_allocator) = $self.coroutine_local_allocator()
+					{
+						if likely!(coroutine_local_allocator.contains($current_memory))
+						{
+							return coroutine_local_allocator.$callback($($argument, )*)
+						}
+					},
+
+					ThreadLocal => if let Some(thread_local_allocator) = $self.thread_local_allocator()
+					{
+						if likely!(thread_local_allocator.contains($current_memory))
+						{
+							return thread_local_allocator.$callback($($argument, )*)
+						}
+					},
+
+					Stacked => if let Some(stacked_local_allocator) = $self.local_allocator_stack().owner_of($current_memory)
+					{
+						return $self.stacked_allocator_memory_usage_of_current_thread().$callback(stacked_local_allocator, $($argument, )*)
+					},
+
+					Global => (),
 				}
 			}
 
-			$self.global_allocator().$callback($($argument, )*)
+			$self.$owned_by_allocator_not_in_use_callback(memory_range_owner, $($argument, )*)
 		}
 	}
 }
@@ -68,34 +108,80 @@ impl<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocator<Coro
 	#[inline(always)]
 	fn allocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
 	{
-		use self::CurrentAllocatorInUse::*;
-
-		match self.save_current_allocator_in_use()
+		let coroutine_memory_quota = self.coroutine_memory_quota();
+		if let Some(coroutine_memory_quota) = coroutine_memory_quota
 		{
-			CoroutineLocal => self.coroutine_local_allocator().expect("Should have assigned a coroutine local allocator").allocate(non_zero_size, non_zero_power_of_two_alignment),
+			coroutine_memory_quota.charge(non_zero_size)?
+		}
 
-			ThreadLocal => self.thread_local_allocator().expect("Should have assigned a thread local allocator").allocate(non_zero_size, non_zero_power_of_two_alignment),
-			
-			Global => self.global_allocator().allocate(non_zero_size, non_zero_power_of_two_alignment),
+		let result = self.allocate_from_current_allocator_in_use(non_zero_size, non_zero_power_of_two_alignment);
+
+		if unlikely!(result.is_err())
+		{
+			if let Some(coroutine_memory_quota) = coroutine_memory_quota
+			{
+				coroutine_memory_quota.refund(non_zero_size)
+			}
 		}
+		result
 	}
 
 	#[inline(always)]
 	fn deallocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
 	{
-		choose_allocator!(self, current_memory, deallocate, non_zero_size, non_zero_power_of_two_alignment, current_memory)
+		if let Some(coroutine_memory_quota) = self.coroutine_memory_quota()
+		{
+			debug_assert!(coroutine_memory_quota.usage() >= (non_zero_size.get() as u64), "Deallocated more memory than is charged to the coroutine memory quota; was it allocated before the quota was attached?");
+			coroutine_memory_quota.refund(non_zero_size)
+		}
+
+		self.deallocate_choosing_allocator(non_zero_size, non_zero_power_of_two_alignment, current_memory)
 	}
 
 	#[inline(always)]
 	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
 	{
-		choose_allocator!(self, current_memory, growing_reallocate, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+		let coroutine_memory_quota_and_increase = match (self.coroutine_memory_quota(), NonZeroUsize::new(non_zero_new_size.get() - non_zero_current_size.get()))
+		{
+			(Some(coroutine_memory_quota), Some(increase)) =>
+			{
+				coroutine_memory_quota.charge(increase)?;
+				Some((coroutine_memory_quota, increase))
+			}
+
+			_ => None,
+		};
+
+		let result = match self.growing_reallocate_choosing_allocator(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+		{
+			Err(AllocError) => self.spill_growing_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved),
+			
+			result @ Ok(_) => result,
+		};
+
+		if unlikely!(result.is_err())
+		{
+			if let Some((coroutine_memory_quota, increase)) = coroutine_memory_quota_and_increase
+			{
+				coroutine_memory_quota.refund(increase)
+			}
+		}
+		result
 	}
 
 	#[inline(always)]
 	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
 	{
-		choose_allocator!(self, current_memory, growing_reallocate, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+		let result = self.shrinking_reallocate_choosing_allocator(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved);
+
+		if likely!(result.is_ok())
+		{
+			if let (Some(coroutine_memory_quota), Some(decrease)) = (self.coroutine_memory_quota(), NonZeroUsize::new(non_zero_current_size.get() - non_zero_new_size.get()))
+			{
+				coroutine_memory_quota.refund(decrease)
+			}
+		}
+		result
 	}
 }
 
@@ -118,6 +204,16 @@ impl<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocator<Coro
 	{
 		&self.global_allocator
 	}
+	
+	#[inline(always)]
+	fn exhaustion_spills(&self) -> ExhaustionSpills
+	{
+		ExhaustionSpills
+		{
+			to_thread_local: self.spills_to_thread_local.load(Relaxed),
+			to_global: self.spills_to_global.load(Relaxed),
+		}
+	}
 }
 
 impl<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocator<CoroutineHeapMemorySource<CoroutineHeapSize>>, ThreadLocalAllocator: LocalAllocator<MemoryMapSource>, GlobalAllocator: Allocator> GlobalThreadAndCoroutineSwitchableAllocatorInstance<CoroutineHeapSize, CoroutineLocalAllocator, ThreadLocalAllocator, GlobalAllocator>
@@ -127,14 +223,292 @@ impl<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocator<Coro
 	/// Prefer `system()` to this.
 	#[inline(always)]
 	pub const fn new(global_allocator: GlobalAllocator, per_thread_state: fn() -> NonNull<PerThreadState<CoroutineHeapSize, CoroutineLocalAllocator, ThreadLocalAllocator>>) -> Self
+	{
+		Self::new_with_thread_local_allocator_factory(global_allocator, ThreadLocalAllocatorFactory::Never, per_thread_state)
+	}
+	
+	/// New instance, intended to only be used once to construct a static global allocator field.
+	///
+	/// `thread_local_allocator_factory` is used to create a thread-local allocator the first time one is needed on a thread which has not called `initialize_thread_local_allocator()`.
+	/// Thread-local allocators so created are dropped when their thread exits, as if by `drop_thread_local_allocator_on_thread_exit()`.
+	#[inline(always)]
+	pub const fn new_with_thread_local_allocator_factory(global_allocator: GlobalAllocator, thread_local_allocator_factory: ThreadLocalAllocatorFactory<ThreadLocalAllocator>, per_thread_state: fn() -> NonNull<PerThreadState<CoroutineHeapSize, CoroutineLocalAllocator, ThreadLocalAllocator>>) -> Self
 	{
 		Self
 		{
 			global_allocator,
+			thread_local_allocator_factory,
+			lazily_created_thread_local_allocator_outstanding_memory_reporter: Self::ignore_outstanding_memory,
+			exhaustion_policy: ExhaustionPolicy::Strict,
+			spills_to_thread_local: AtomicU64::new(0),
+			spills_to_global: AtomicU64::new(0),
 			per_thread_state,
 			marker: PhantomData
 		}
 	}
+	
+	/// Changes what happens when the allocator for the current allocator in use is exhausted; the default is `ExhaustionPolicy::Strict`.
+	#[inline(always)]
+	pub const fn with_exhaustion_policy(self, exhaustion_policy: ExhaustionPolicy) -> Self
+	{
+		Self
+		{
+			exhaustion_policy,
+			..self
+		}
+	}
+	
+	/// Changes the reporter of memory still allocated when a thread exits from a thread-local allocator created by the `ThreadLocalAllocatorFactory`; the default ignores it.
+	#[inline(always)]
+	pub const fn with_lazily_created_thread_local_allocator_outstanding_memory_reporter(self, outstanding_memory_reporter: fn(u64)) -> Self
+	{
+		Self
+		{
+			lazily_created_thread_local_allocator_outstanding_memory_reporter: outstanding_memory_reporter,
+			..self
+		}
+	}
+	
+	#[doc(hidden)]
+	#[inline(always)]
+	pub fn ignore_outstanding_memory(_outstanding_bytes: u64)
+	{
+	}
+}
+
+impl<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocator<CoroutineHeapMemorySource<CoroutineHeapSize>>, ThreadLocalAllocator: LocalAllocator<MemoryMapSource>, GlobalAllocator: Allocator> GlobalThreadAndCoroutineSwitchableAllocatorInstance<CoroutineHeapSize, CoroutineLocalAllocator, ThreadLocalAllocator, GlobalAllocator>
+{
+	#[inline(always)]
+	fn allocate_from_current_allocator_in_use(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		use self::CurrentAllocatorInUse::*;
+
+		self.drain_remote_frees_if_any();
+
+		let current_allocator_in_use = self.save_current_allocator_in_use();
+		let result = match current_allocator_in_use
+		{
+			CoroutineLocal => self.coroutine_local_allocator().expect("Should have assigned a coroutine local allocator").allocate(non_zero_size, non_zero_power_of_two_alignment),
+
+			ThreadLocal => match self.thread_local_allocator_or_lazily_created()
+			{
+				Some(thread_local_allocator) => thread_local_allocator.allocate(non_zero_size, non_zero_power_of_two_alignment),
+				
+				None => self.allocate_from_global_allocator(non_zero_size, non_zero_power_of_two_alignment),
+			},
+			
+			Global => self.allocate_from_global_allocator(non_zero_size, non_zero_power_of_two_alignment),
+			
+			Stacked => self.stacked_allocator_memory_usage_of_current_thread().allocate(self.local_allocator_stack().top().expect("Should have pushed a local allocator"), non_zero_size, non_zero_power_of_two_alignment),
+		};
+		
+		if likely!(result.is_ok())
+		{
+			return result
+		}
+		self.spill(current_allocator_in_use, non_zero_size, non_zero_power_of_two_alignment)
+	}
+	
+	/// Memory usage of the global allocator is tracked per thread.
+	#[inline(always)]
+	fn allocate_from_global_allocator(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		self.global_allocator_memory_usage_of_current_thread().allocate(self.global_allocator(), non_zero_size, non_zero_power_of_two_alignment)
+	}
+	
+	/// Allocates from the tiers after `exhausted`, as permitted by the exhaustion policy.
+	#[cold]
+	#[inline(never)]
+	fn spill(&self, exhausted: CurrentAllocatorInUse, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		use self::CurrentAllocatorInUse::*;
+		use self::ExhaustionPolicy::*;
+		
+		let exhaustion_policy = self.exhaustion_policy;
+		if exhaustion_policy == Strict
+		{
+			return Err(AllocError)
+		}
+		
+		let spill_to_thread_local = match exhausted
+		{
+			CoroutineLocal | Stacked => true,
+			
+			ThreadLocal => false,
+			
+			Global => return Err(AllocError),
+		};
+		
+		if spill_to_thread_local
+		{
+			if let Some(thread_local_allocator) = self.thread_local_allocator_or_lazily_created()
+			{
+				if let Ok(allocated) = thread_local_allocator.allocate(non_zero_size, non_zero_power_of_two_alignment)
+				{
+					self.spills_to_thread_local.fetch_add(1, Relaxed);
+					return Ok(allocated)
+				}
+			}
+		}
+		
+		if exhaustion_policy == SpillToGlobal
+		{
+			let allocated = self.allocate_from_global_allocator(non_zero_size, non_zero_power_of_two_alignment)?;
+			self.spills_to_global.fetch_add(1, Relaxed);
+			Ok(allocated)
+		}
+		else
+		{
+			Err(AllocError)
+		}
+	}
+	
+	/// Growing reallocations which fail in place are spilled as an allocation, copy and deallocation.
+	#[cold]
+	#[inline(never)]
+	fn spill_growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		if unlikely!(current_memory_can_not_be_moved)
+		{
+			return Err(AllocError)
+		}
+		
+		let (new_memory, actual_size) = self.spill(self.save_current_allocator_in_use(), non_zero_new_size, non_zero_power_of_two_new_alignment)?;
+		unsafe { new_memory.as_ptr().copy_from_nonoverlapping(current_memory.as_ptr(), non_zero_current_size.get()) };
+		self.deallocate_choosing_allocator(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory);
+		Ok((new_memory, actual_size))
+	}
+
+	#[inline(always)]
+	fn deallocate_choosing_allocator(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		choose_allocator!(self, current_memory, deallocate, deallocate_owned_by_allocator_not_in_use, non_zero_size, non_zero_power_of_two_alignment, current_memory)
+	}
+
+	#[inline(always)]
+	fn growing_reallocate_choosing_allocator(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		choose_allocator!(self, current_memory, growing_reallocate, growing_reallocate_owned_by_allocator_not_in_use, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+	}
+
+	#[inline(always)]
+	fn shrinking_reallocate_choosing_allocator(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		choose_allocator!(self, current_memory, shrinking_reallocate, shrinking_reallocate_owned_by_allocator_not_in_use, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+	}
+
+	#[inline(always)]
+	fn thread_local_allocator_or_lazily_created(&self) -> Option<&MemoryUsageTrackingAllocator<ThreadLocalAllocator>>
+	{
+		let thread_local_allocator = self.thread_local_allocator();
+		if likely!(thread_local_allocator.is_some())
+		{
+			return thread_local_allocator
+		}
+		self.lazily_create_thread_local_allocator()
+	}
+	
+	#[cold]
+	#[inline(never)]
+	fn lazily_create_thread_local_allocator(&self) -> Option<&MemoryUsageTrackingAllocator<ThreadLocalAllocator>>
+	{
+		if unlikely!(self.thread_local_allocator_factory.is_never())
+		{
+			return None
+		}
+		
+		if self.use_per_thread_state(|per_thread_state| per_thread_state.thread_local_allocator_factory_disabled)
+		{
+			return None
+		}
+		
+		let restore_to = self.replace_current_allocator_in_use(CurrentAllocatorInUse::Global);
+		let created = self.thread_local_allocator_factory.create();
+		self.restore_current_allocator_in_use(restore_to);
+		
+		match created
+		{
+			Some(thread_local_allocator) =>
+			{
+				self.initialize_thread_local_allocator(thread_local_allocator);
+				self.drop_thread_local_allocator_on_thread_exit(self.lazily_created_thread_local_allocator_outstanding_memory_reporter);
+				self.thread_local_allocator()
+			}
+			
+			None =>
+			{
+				self.use_per_thread_state(|per_thread_state| per_thread_state.thread_local_allocator_factory_disabled = true);
+				None
+			}
+		}
+	}
+	
+	#[inline(always)]
+	fn drain_remote_frees_if_any(&self)
+	{
+		if let Some(remote_free_queue) = self.use_per_thread_state(|per_thread_state| per_thread_state.remote_free_queue)
+		{
+			if unlikely!(remote_free_queue.has_remote_frees())
+			{
+				self.drain_remote_frees();
+			}
+		}
+	}
+
+	/// Memory owned by a thread-local allocator of another thread, or by a coroutine local allocator which is swapped out, is queued to its owner.
+	#[inline(always)]
+	fn deallocate_owned_by_allocator_not_in_use(&self, memory_range_owner: &MemoryRangeOwner, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		let remote_free_queue = Self::live_remote_free_queue(memory_range_owner, current_memory);
+		if unlikely!(!remote_free_queue.push(non_zero_size, non_zero_power_of_two_alignment, current_memory))
+		{
+			MemoryRangeRegistry::memory_belongs_to_no_known_allocator(current_memory)
+		}
+	}
+
+	#[inline(always)]
+	fn growing_reallocate_owned_by_allocator_not_in_use(&self, memory_range_owner: &MemoryRangeOwner, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		self.reallocate_owned_by_allocator_not_in_use(memory_range_owner, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved, non_zero_current_size)
+	}
+
+	#[inline(always)]
+	fn shrinking_reallocate_owned_by_allocator_not_in_use(&self, memory_range_owner: &MemoryRangeOwner, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		self.reallocate_owned_by_allocator_not_in_use(memory_range_owner, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved, non_zero_new_size)
+	}
+
+	/// Memory owned by an allocator not in use can not be reallocated in place; it is copied into memory from the current allocator in use and the original is queued to its owner.
+	#[inline(always)]
+	fn reallocate_owned_by_allocator_not_in_use(&self, memory_range_owner: &MemoryRangeOwner, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool, amount_to_copy: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let remote_free_queue = Self::live_remote_free_queue(memory_range_owner, current_memory);
+
+		if unlikely!(current_memory_can_not_be_moved)
+		{
+			return Err(AllocError)
+		}
+
+		let (new_memory, actual_size) = self.allocate_from_current_allocator_in_use(non_zero_new_size, non_zero_power_of_two_new_alignment)?;
+		unsafe { new_memory.as_ptr().copy_from_nonoverlapping(current_memory.as_ptr(), amount_to_copy.get()) };
+		if unlikely!(!remote_free_queue.push(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory))
+		{
+			MemoryRangeRegistry::memory_belongs_to_no_known_allocator(current_memory)
+		}
+		Ok((new_memory, actual_size))
+	}
+
+	/// Aborts if the owner has no live queue, eg as its local allocator has been dropped or popped.
+	#[inline(always)]
+	fn live_remote_free_queue(memory_range_owner: &MemoryRangeOwner, current_memory: NonNull<u8>) -> &'static RemoteFreeQueue
+	{
+		match memory_range_owner.remote_free_queue()
+		{
+			Some(remote_free_queue) if likely!(remote_free_queue.is_live()) => remote_free_queue,
+
+			_ => MemoryRangeRegistry::memory_belongs_to_no_known_allocator(current_memory),
+		}
+	}
 }
 
 impl<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocator<CoroutineHeapMemorySource<CoroutineHeapSize>>, ThreadLocalAllocator: LocalAllocator<MemoryMapSource>> GlobalThreadAndCoroutineSwitchableAllocatorInstance<CoroutineHeapSize, CoroutineLocalAllocator, ThreadLocalAllocator, GlobalAllocToAllocatorAdaptor<System>>
@@ -146,12 +520,13 @@ impl<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocator<Coro
 	/// ```
 	/// use context_allocator::allocators::global::PerThreadState;
 	/// use std::ptr::NonNull;
+	/// use std::ptr::addr_of_mut;
 	///
 	/// #[inline(always)]
 	/// fn per_thread_state() -> NonNull<PerThreadState<HeapSize, CoroutineLocalAllocator, ThreadLocalAllocator>>
 	/// {
 	/// 	#[thread_local] static mut per_thread_state: PerThreadState<HeapSize, CoroutineLocalAllocator, ThreadLocalAllocator> = PerThreadState::empty();
-	/// 	unsafe { NonNull::new_unchecked(&mut per_thread_state) }
+	/// 	unsafe { NonNull::new_unchecked(addr_of_mut!(per_thread_state)) }
 	/// }
 	/// ```
 	///
@@ -170,3 +545,298 @@ impl<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocator<Coro
 		Self::new(GlobalAllocToAllocatorAdaptor::System, per_thread_state)
 	}
 }
+
+#[cfg(test)]
+mod GlobalThreadAndCoroutineSwitchableAllocatorInstanceTests
+{
+	use super::*;
+	use std::ptr::addr_of_mut;
+	use std::thread::spawn;
+
+	#[test]
+	pub fn shrinking_reallocation_is_routed_to_shrinking_reallocate()
+	{
+		Instance.initialize_thread_local_allocator(FixedBufferLocalAllocator);
+
+		let (allocation, _) = Instance.callback_with_thread_local_allocator(|| Instance.allocate(32.non_zero(), 8.non_zero())).expect("Did not allocate");
+		assert_eq!(Instance.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), 32);
+
+		let (reallocation, _) = Instance.shrinking_reallocate(16.non_zero(), 8.non_zero(), 32.non_zero(), 8.non_zero(), allocation, false).expect("Did not reallocate");
+		assert_eq!(allocation, reallocation, "Did not shrink allocation in place");
+		assert_eq!(Instance.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), 16, "Did not shrink allocation using the thread-local allocator");
+
+		Instance.deallocate(16.non_zero(), 8.non_zero(), reallocation);
+		Instance.drop_thread_local_allocator()
+	}
+
+	#[test]
+	pub fn popping_a_pushed_thread_local_allocator_restores_its_registration()
+	{
+		use self::CurrentAllocatorInUse::*;
+
+		Instance.initialize_thread_local_allocator(FixedBufferLocalAllocator);
+
+		let (allocation, _) = Instance.callback_with_thread_local_allocator(|| Instance.allocate(32.non_zero(), 8.non_zero())).expect("Did not allocate");
+		let memory_range_owner = MemoryRangeRegistry::owner_of(allocation).expect("Thread-local allocator is not registered");
+
+		let local_allocator = FixedBufferLocalAllocator;
+		{
+			let _guard = unsafe { Instance.push_local_allocator::<MemoryMapSource, FixedBufferLocalAllocator>(&local_allocator) };
+			assert_eq!(memory_range_owner.tier(), Some(Stacked));
+			assert!(memory_range_owner.remote_free_queue().is_some(), "Did not keep the queue of the thread-local allocator whilst pushed");
+		}
+		assert_eq!(memory_range_owner.tier(), Some(ThreadLocal), "Did not restore the tier when popped");
+		assert!(memory_range_owner.is_owned_by_current_thread());
+
+		Instance.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		assert_eq!(Instance.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), 0, "Did not deallocate using the thread-local allocator");
+
+		let (allocation, _) = Instance.callback_with_thread_local_allocator(|| Instance.allocate(32.non_zero(), 8.non_zero())).expect("Did not allocate");
+		let address = allocation.as_ptr() as usize;
+		spawn(move || Instance.deallocate(32.non_zero(), 8.non_zero(), unsafe { NonNull::new_unchecked(address as *mut u8) })).join().expect("Other thread panicked");
+		assert_eq!(Instance.drain_remote_frees(), 1, "Did not queue the deallocation by another thread");
+		assert_eq!(Instance.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), 0);
+
+		Instance.drop_thread_local_allocator()
+	}
+
+	#[test]
+	pub fn outstanding_memory_of_thread_local_allocator_is_reported_when_its_thread_exits()
+	{
+		static OutstandingBytes: AtomicU64 = AtomicU64::new(0);
+
+		fn record_outstanding_bytes(outstanding_bytes: u64)
+		{
+			OutstandingBytes.fetch_add(outstanding_bytes, Relaxed);
+		}
+
+		spawn(||
+		{
+			Instance.initialize_thread_local_allocator(FixedBufferLocalAllocator);
+			Instance.drop_thread_local_allocator_on_thread_exit(record_outstanding_bytes);
+
+			Instance.callback_with_thread_local_allocator(|| Instance.allocate(32.non_zero(), 8.non_zero())).expect("Did not allocate");
+		}).join().expect("Other thread panicked");
+
+		assert_eq!(OutstandingBytes.load(Relaxed), 32, "Did not report outstanding memory")
+	}
+
+	#[test]
+	pub fn thread_local_allocations_fall_back_to_global_if_never_created()
+	{
+		let (allocation, _) = Instance.callback_with_thread_local_allocator(|| Instance.allocate(32.non_zero(), 8.non_zero())).expect("Did not allocate");
+		assert!(Instance.thread_local_allocator().is_none(), "Created a thread-local allocator");
+		assert_eq!(Instance.global_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), 32, "Did not fall back to the global allocator");
+
+		Instance.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		assert_eq!(Instance.global_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), 0)
+	}
+
+	#[test]
+	pub fn thread_local_allocator_is_created_by_function()
+	{
+		let (allocation, _) = FunctionFactoryInstance.callback_with_thread_local_allocator(|| FunctionFactoryInstance.allocate(32.non_zero(), 8.non_zero())).expect("Did not allocate");
+		assert!(FunctionFactoryInstance.thread_local_allocator().is_some(), "Did not create a thread-local allocator");
+		assert_eq!(FunctionFactoryInstance.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), 32, "Did not allocate using the created thread-local allocator");
+		assert_eq!(MemoryRangeRegistry::owner_of(allocation).expect("Created thread-local allocator is not registered").tier(), Some(CurrentAllocatorInUse::ThreadLocal));
+
+		FunctionFactoryInstance.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		FunctionFactoryInstance.drop_thread_local_allocator()
+	}
+
+	#[test]
+	pub fn thread_local_allocator_is_created_from_a_memory_map()
+	{
+		let (allocation, _) = MemoryMapFactoryInstance.callback_with_thread_local_allocator(|| MemoryMapFactoryInstance.allocate(64.non_zero(), 8.non_zero())).expect("Did not allocate");
+		assert!(MemoryMapFactoryInstance.thread_local_allocator().is_some(), "Did not create a thread-local allocator");
+		assert_eq!(MemoryMapFactoryInstance.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), 64, "Did not allocate using the created thread-local allocator");
+		assert_eq!(MemoryRangeRegistry::owner_of(allocation).expect("Created thread-local allocator is not registered").tier(), Some(CurrentAllocatorInUse::ThreadLocal));
+
+		MemoryMapFactoryInstance.deallocate(64.non_zero(), 8.non_zero(), allocation);
+		MemoryMapFactoryInstance.drop_thread_local_allocator()
+	}
+
+	#[test]
+	pub fn exhausted_stacked_allocation_spills_to_a_lazily_created_thread_local_allocator()
+	{
+		let local_allocator = ExhaustedLocalAllocator;
+		let (allocation, _) =
+		{
+			let _guard = unsafe { SpillingInstance.push_local_allocator::<MemoryMapSource, ExhaustedLocalAllocator>(&local_allocator) };
+			SpillingInstance.allocate(32.non_zero(), 8.non_zero()).expect("Did not spill")
+		};
+
+		assert!(SpillingInstance.thread_local_allocator().is_some(), "Did not lazily create a thread-local allocator to spill to");
+		assert_eq!(SpillingInstance.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage), 32, "Did not spill to the thread-local allocator");
+		assert_eq!(SpillingInstance.exhaustion_spills().to_thread_local, 1);
+		assert_eq!(SpillingInstance.exhaustion_spills().to_global, 0);
+
+		SpillingInstance.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		SpillingInstance.drop_thread_local_allocator()
+	}
+
+	#[test]
+	pub fn exhausted_stacked_allocation_fails_if_strict()
+	{
+		let local_allocator = ExhaustedLocalAllocator;
+		let _guard = unsafe { FunctionFactoryInstance.push_local_allocator::<MemoryMapSource, ExhaustedLocalAllocator>(&local_allocator) };
+		assert!(FunctionFactoryInstance.allocate(32.non_zero(), 8.non_zero()).is_err(), "Spilled although strict");
+		assert!(FunctionFactoryInstance.thread_local_allocator().is_none(), "Created a thread-local allocator although strict")
+	}
+
+	static Instance: GlobalThreadAndCoroutineSwitchableAllocatorInstance<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, FixedBufferLocalAllocator, GlobalAllocToAllocatorAdaptor<System>> = GlobalThreadAndCoroutineSwitchableAllocatorInstance::system(per_thread_state);
+
+	static FunctionFactoryInstance: GlobalThreadAndCoroutineSwitchableAllocatorInstance<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, FixedBufferLocalAllocator, GlobalAllocToAllocatorAdaptor<System>> = GlobalThreadAndCoroutineSwitchableAllocatorInstance::new_with_thread_local_allocator_factory(GlobalAllocToAllocatorAdaptor::System, ThreadLocalAllocatorFactory::Function(new_fixed_buffer_local_allocator), function_factory_per_thread_state);
+
+	static MemoryMapFactoryInstance: GlobalThreadAndCoroutineSwitchableAllocatorInstance<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, MultipleBinarySearchTreeAllocator<MemoryMapSource>, GlobalAllocToAllocatorAdaptor<System>> = GlobalThreadAndCoroutineSwitchableAllocatorInstance::new_with_thread_local_allocator_factory
+	(
+		GlobalAllocToAllocatorAdaptor::System,
+		ThreadLocalAllocatorFactory::MemoryMap
+		{
+			size: unsafe { NonZeroU64::new_unchecked(1 << 20) },
+			settings: MappedMemorySettings::default,
+			lifetime_hint: LifetimeHint::LongLived,
+			block_size_hint: new_non_zero_usize(64),
+		},
+		memory_map_factory_per_thread_state,
+	);
+
+	static SpillingInstance: GlobalThreadAndCoroutineSwitchableAllocatorInstance<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, FixedBufferLocalAllocator, GlobalAllocToAllocatorAdaptor<System>> = GlobalThreadAndCoroutineSwitchableAllocatorInstance::new_with_thread_local_allocator_factory(GlobalAllocToAllocatorAdaptor::System, ThreadLocalAllocatorFactory::Function(new_fixed_buffer_local_allocator), spilling_per_thread_state).with_exhaustion_policy(ExhaustionPolicy::SpillToThreadLocal);
+
+	#[inline(always)]
+	fn new_fixed_buffer_local_allocator() -> Result<FixedBufferLocalAllocator, AllocError>
+	{
+		Ok(FixedBufferLocalAllocator)
+	}
+
+	#[inline(always)]
+	fn function_factory_per_thread_state() -> NonNull<PerThreadState<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, FixedBufferLocalAllocator>>
+	{
+		#[thread_local] static mut per_thread_state: PerThreadState<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, FixedBufferLocalAllocator> = PerThreadState::empty();
+		unsafe { NonNull::new_unchecked(addr_of_mut!(per_thread_state)) }
+	}
+
+	#[inline(always)]
+	fn spilling_per_thread_state() -> NonNull<PerThreadState<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, FixedBufferLocalAllocator>>
+	{
+		#[thread_local] static mut per_thread_state: PerThreadState<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, FixedBufferLocalAllocator> = PerThreadState::empty();
+		unsafe { NonNull::new_unchecked(addr_of_mut!(per_thread_state)) }
+	}
+
+	#[inline(always)]
+	fn memory_map_factory_per_thread_state() -> NonNull<PerThreadState<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, MultipleBinarySearchTreeAllocator<MemoryMapSource>>>
+	{
+		#[thread_local] static mut per_thread_state: PerThreadState<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, MultipleBinarySearchTreeAllocator<MemoryMapSource>> = PerThreadState::empty();
+		unsafe { NonNull::new_unchecked(addr_of_mut!(per_thread_state)) }
+	}
+
+	#[inline(always)]
+	fn per_thread_state() -> NonNull<PerThreadState<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, FixedBufferLocalAllocator>>
+	{
+		#[thread_local] static mut per_thread_state: PerThreadState<MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, FixedBufferLocalAllocator> = PerThreadState::empty();
+		unsafe { NonNull::new_unchecked(addr_of_mut!(per_thread_state)) }
+	}
+
+	/// Allocates the whole of a per-thread buffer, and reallocates in place.
+	#[derive(Debug)]
+	struct FixedBufferLocalAllocator;
+
+	impl Allocator for FixedBufferLocalAllocator
+	{
+		#[inline(always)]
+		fn allocate(&self, non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			Ok((Self::buffer().from, non_zero_size.get()))
+		}
+
+		#[inline(always)]
+		fn deallocate(&self, _non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize, _current_memory: NonNull<u8>)
+		{
+		}
+
+		#[inline(always)]
+		fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, _non_zero_power_of_two_new_alignment: NonZeroUsize, _non_zero_current_size: NonZeroUsize, _non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, _current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			Ok((current_memory, non_zero_new_size.get()))
+		}
+
+		#[inline(always)]
+		fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, _non_zero_power_of_two_new_alignment: NonZeroUsize, _non_zero_current_size: NonZeroUsize, _non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, _current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			Ok((current_memory, non_zero_new_size.get()))
+		}
+	}
+
+	impl LocalAllocator<MemoryMapSource> for FixedBufferLocalAllocator
+	{
+		#[inline(always)]
+		fn new_local_allocator(_memory_source: MemoryMapSource, _lifetime_hint: LifetimeHint, _block_size_hint: NonZeroUsize) -> Self
+		{
+			Self
+		}
+
+		#[inline(always)]
+		fn memory_range(&self) -> MemoryRange
+		{
+			Self::buffer()
+		}
+	}
+
+	impl FixedBufferLocalAllocator
+	{
+		#[inline(always)]
+		fn buffer() -> MemoryRange
+		{
+			#[thread_local] static mut Buffer: [u64; 8] = [0; 8];
+			let from = unsafe { NonNull::new_unchecked(addr_of_mut!(Buffer)) }.cast::<u8>();
+			MemoryRange::new(from, from.add(size_of::<[u64; 8]>()))
+		}
+	}
+
+	/// Fails every allocation, as if exhausted.
+	#[derive(Debug)]
+	struct ExhaustedLocalAllocator;
+
+	impl Allocator for ExhaustedLocalAllocator
+	{
+		#[inline(always)]
+		fn allocate(&self, _non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			Err(AllocError)
+		}
+
+		#[inline(always)]
+		fn deallocate(&self, _non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize, _current_memory: NonNull<u8>)
+		{
+		}
+
+		#[inline(always)]
+		fn growing_reallocate(&self, _non_zero_new_size: NonZeroUsize, _non_zero_power_of_two_new_alignment: NonZeroUsize, _non_zero_current_size: NonZeroUsize, _non_zero_power_of_two_current_alignment: NonZeroUsize, _current_memory: NonNull<u8>, _current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			Err(AllocError)
+		}
+
+		#[inline(always)]
+		fn shrinking_reallocate(&self, _non_zero_new_size: NonZeroUsize, _non_zero_power_of_two_new_alignment: NonZeroUsize, _non_zero_current_size: NonZeroUsize, _non_zero_power_of_two_current_alignment: NonZeroUsize, _current_memory: NonNull<u8>, _current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			Err(AllocError)
+		}
+	}
+
+	impl LocalAllocator<MemoryMapSource> for ExhaustedLocalAllocator
+	{
+		#[inline(always)]
+		fn new_local_allocator(_memory_source: MemoryMapSource, _lifetime_hint: LifetimeHint, _block_size_hint: NonZeroUsize) -> Self
+		{
+			Self
+		}
+
+		#[inline(always)]
+		fn memory_range(&self) -> MemoryRange
+		{
+			#[thread_local] static mut Buffer: [u64; 1] = [0; 1];
+			let from = unsafe { NonNull::new_unchecked(addr_of_mut!(Buffer)) }.cast::<u8>();
+			MemoryRange::new(from, from.add(size_of::<[u64; 1]>()))
+		}
+	}
+}
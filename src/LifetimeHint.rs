@@ -3,7 +3,7 @@
 
 
 /// A hint for choosing a memory allocator.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub enum LifetimeHint
 {
 	/// Use this variant for contexts with short-lived lifetimes.
@@ -15,6 +15,18 @@ pub struct LocalAllocatorMemoryUsage
 	growing_reallocated: Cell<u64>,
 	
 	shrinking_reallocated: Cell<u64>,
+	
+	allocations: Cell<u64>,
+	
+	deallocations: Cell<u64>,
+	
+	growing_reallocations: Cell<u64>,
+	
+	shrinking_reallocations: Cell<u64>,
+	
+	failed_allocations: Cell<u64>,
+	
+	peak_usage: Cell<u64>,
 }
 
 impl Default for LocalAllocatorMemoryUsage
@@ -30,15 +42,24 @@ impl Sub for LocalAllocatorMemoryUsage
 {
 	type Output = Self;
 	
+	/// The peak usage of the result is that of `self`, as a peak is not a counter.
+	///
+	/// Counters saturate at zero, so subtracting later memory usage from earlier memory usage does not panic.
 	#[inline(always)]
 	fn sub(self, rhs: Self) -> Self::Output
 	{
 		Self
 		{
-			allocated: Cell::new(self.get_allocated() - rhs.get_allocated()),
-			deallocated: Cell::new(self.get_deallocated() - rhs.get_deallocated()),
-			growing_reallocated: Cell::new(self.get_growing_reallocated() - rhs.get_growing_reallocated()),
-			shrinking_reallocated: Cell::new(self.get_shrinking_reallocated() - rhs.get_shrinking_reallocated())
+			allocated: Cell::new(self.get_allocated().saturating_sub(rhs.get_allocated())),
+			deallocated: Cell::new(self.get_deallocated().saturating_sub(rhs.get_deallocated())),
+			growing_reallocated: Cell::new(self.get_growing_reallocated().saturating_sub(rhs.get_growing_reallocated())),
+			shrinking_reallocated: Cell::new(self.get_shrinking_reallocated().saturating_sub(rhs.get_shrinking_reallocated())),
+			allocations: Cell::new(self.allocations.get().saturating_sub(rhs.allocations.get())),
+			deallocations: Cell::new(self.deallocations.get().saturating_sub(rhs.deallocations.get())),
+			growing_reallocations: Cell::new(self.growing_reallocations.get().saturating_sub(rhs.growing_reallocations.get())),
+			shrinking_reallocations: Cell::new(self.shrinking_reallocations.get().saturating_sub(rhs.shrinking_reallocations.get())),
+			failed_allocations: Cell::new(self.failed_allocations.get().saturating_sub(rhs.failed_allocations.get())),
+			peak_usage: Cell::new(self.peak_usage.get()),
 		}
 	}
 }
@@ -57,38 +78,152 @@ impl LocalAllocatorMemoryUsage
 			growing_reallocated: Cell::new(0),
 			
 			shrinking_reallocated: Cell::new(0),
+			
+			allocations: Cell::new(0),
+			
+			deallocations: Cell::new(0),
+			
+			growing_reallocations: Cell::new(0),
+			
+			shrinking_reallocations: Cell::new(0),
+			
+			failed_allocations: Cell::new(0),
+			
+			peak_usage: Cell::new(0),
 		}
 	}
 	
 	/// Memory usage.
+	///
+	/// Never less than zero, even if memory allocated elsewhere, eg by another thread, has been deallocated.
 	#[inline(always)]
 	pub fn usage(&self) -> u64
 	{
-		(self.get_allocated() + self.get_growing_reallocated()) - (self.get_deallocated() + self.get_shrinking_reallocated())
+		(self.get_allocated() + self.get_growing_reallocated()).saturating_sub(self.get_deallocated() + self.get_shrinking_reallocated())
+	}
+	
+	/// Peak memory usage since creation or the last call to `reset_peak_usage()`.
+	#[inline(always)]
+	pub fn peak_usage(&self) -> u64
+	{
+		self.peak_usage.get()
+	}
+	
+	/// Resets peak memory usage to current memory usage.
+	#[inline(always)]
+	pub fn reset_peak_usage(&self)
+	{
+		self.peak_usage.set(self.usage())
+	}
+	
+	/// Number of allocations not yet deallocated.
+	#[inline(always)]
+	pub fn live_allocations(&self) -> u64
+	{
+		self.allocations.get().saturating_sub(self.deallocations.get())
+	}
+	
+	/// An immutable snapshot.
+	#[inline(always)]
+	pub fn snapshot(&self) -> LocalAllocatorMemoryUsageSnapshot
+	{
+		LocalAllocatorMemoryUsageSnapshot
+		{
+			allocated: self.get_allocated(),
+			deallocated: self.get_deallocated(),
+			growing_reallocated: self.get_growing_reallocated(),
+			shrinking_reallocated: self.get_shrinking_reallocated(),
+			allocations: self.allocations.get(),
+			deallocations: self.deallocations.get(),
+			growing_reallocations: self.growing_reallocations.get(),
+			shrinking_reallocations: self.shrinking_reallocations.get(),
+			failed_allocations: self.failed_allocations.get(),
+			peak_usage: self.peak_usage(),
+		}
+	}
+	
+	#[inline(always)]
+	pub(crate) fn allocate(&self, allocator: &impl Allocator, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let result = allocator.allocate(non_zero_size, non_zero_power_of_two_alignment);
+		match result
+		{
+			Ok((_, actual_size)) => self.allocated(actual_size),
+			
+			Err(AllocError) => self.allocation_failed(),
+		}
+		result
+	}
+	
+	#[inline(always)]
+	pub(crate) fn deallocate(&self, allocator: &impl Allocator, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		allocator.deallocate(non_zero_size, non_zero_power_of_two_alignment, current_memory);
+		self.deallocated(non_zero_size);
+	}
+	
+	#[inline(always)]
+	pub(crate) fn growing_reallocate(&self, allocator: &impl Allocator, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let result = allocator.growing_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved);
+		match result
+		{
+			Ok((_, actual_size)) => self.growing_reallocated(non_zero_current_size, actual_size),
+			
+			Err(AllocError) => self.allocation_failed(),
+		}
+		result
+	}
+	
+	#[inline(always)]
+	pub(crate) fn shrinking_reallocate(&self, allocator: &impl Allocator, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let result = allocator.shrinking_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)?;
+		self.shrinking_reallocated(non_zero_current_size, result.1);
+		Ok(result)
 	}
 	
 	#[inline(always)]
 	fn allocated(&self, size: usize)
 	{
 		self.allocated.set(self.get_allocated() + size as u64);
+		self.allocations.set(self.allocations.get() + 1);
+		self.update_peak_usage();
 	}
 	
 	#[inline(always)]
 	fn deallocated(&self, size: NonZeroUsize)
 	{
 		self.deallocated.set(self.get_deallocated() + size.get() as u64);
+		self.deallocations.set(self.deallocations.get() + 1);
 	}
 	
 	#[inline(always)]
 	fn growing_reallocated(&self, non_zero_current_size: NonZeroUsize, size: usize)
 	{
 		self.growing_reallocated.set(self.get_growing_reallocated() + ((size - non_zero_current_size.get()) as u64));
+		self.growing_reallocations.set(self.growing_reallocations.get() + 1);
+		self.update_peak_usage();
 	}
 	
 	#[inline(always)]
 	fn shrinking_reallocated(&self, non_zero_current_size: NonZeroUsize, size: usize)
 	{
 		self.shrinking_reallocated.set(self.get_shrinking_reallocated() + ((non_zero_current_size.get() - size) as u64));
+		self.shrinking_reallocations.set(self.shrinking_reallocations.get() + 1);
+	}
+	
+	/// Failed allocations include failed growing reallocations.
+	#[inline(always)]
+	fn allocation_failed(&self)
+	{
+		self.failed_allocations.set(self.failed_allocations.get() + 1);
+	}
+	
+	#[inline(always)]
+	fn update_peak_usage(&self)
+	{
+		self.peak_usage.set(max(self.peak_usage(), self.usage()))
 	}
 	
 	#[inline(always)]
@@ -115,3 +250,59 @@ impl LocalAllocatorMemoryUsage
 		self.shrinking_reallocated.get()
 	}
 }
+
+#[cfg(test)]
+mod LocalAllocatorMemoryUsageTests
+{
+	use super::*;
+
+	#[test]
+	pub fn counters_and_peak_usage_are_recorded()
+	{
+		let allocator = GlobalAllocToAllocatorAdaptor(System);
+		let memory_usage = LocalAllocatorMemoryUsage::new();
+
+		let (first, _) = memory_usage.allocate(&allocator, 64.non_zero(), 8.non_zero()).expect("Did not allocate");
+		let (second, _) = memory_usage.allocate(&allocator, 32.non_zero(), 8.non_zero()).expect("Did not allocate");
+		let (first, _) = memory_usage.growing_reallocate(&allocator, 128.non_zero(), 8.non_zero(), 64.non_zero(), 8.non_zero(), first, false).expect("Did not grow");
+		assert!(memory_usage.growing_reallocate(&allocator, 256.non_zero(), 16.non_zero(), 32.non_zero(), 8.non_zero(), second, false).is_err(), "Grew to a greater alignment");
+		let (first, _) = memory_usage.shrinking_reallocate(&allocator, 16.non_zero(), 8.non_zero(), 128.non_zero(), 8.non_zero(), first, false).expect("Did not shrink");
+		memory_usage.deallocate(&allocator, 32.non_zero(), 8.non_zero(), second);
+
+		let snapshot = memory_usage.snapshot();
+		assert_eq!(snapshot.allocated, 96);
+		assert_eq!(snapshot.deallocated, 32);
+		assert_eq!(snapshot.growing_reallocated, 64);
+		assert_eq!(snapshot.shrinking_reallocated, 112);
+		assert_eq!(snapshot.allocations, 2);
+		assert_eq!(snapshot.deallocations, 1);
+		assert_eq!(snapshot.growing_reallocations, 1);
+		assert_eq!(snapshot.shrinking_reallocations, 1);
+		assert_eq!(snapshot.failed_allocations, 1, "Did not count the failed growing reallocation");
+		assert_eq!(snapshot.reallocations(), 2);
+		assert_eq!(snapshot.usage(), 16);
+		assert_eq!(memory_usage.usage(), 16);
+		assert_eq!(memory_usage.live_allocations(), 1);
+		assert_eq!(memory_usage.peak_usage(), 160);
+
+		memory_usage.reset_peak_usage();
+		assert_eq!(memory_usage.peak_usage(), 16, "Did not reset peak usage to current usage");
+
+		memory_usage.deallocate(&allocator, 16.non_zero(), 8.non_zero(), first);
+		assert_eq!(memory_usage.usage(), 0);
+		assert_eq!(memory_usage.live_allocations(), 0);
+		assert_eq!(memory_usage.peak_usage(), 16, "Deallocation changed peak usage")
+	}
+
+	#[test]
+	pub fn usage_never_goes_below_zero()
+	{
+		let allocator = GlobalAllocToAllocatorAdaptor(System);
+		let memory_usage = LocalAllocatorMemoryUsage::new();
+		let (allocation, _) = allocator.allocate(64.non_zero(), 8.non_zero()).expect("Did not allocate");
+
+		memory_usage.deallocate(&allocator, 64.non_zero(), 8.non_zero(), allocation);
+		assert_eq!(memory_usage.usage(), 0);
+		assert_eq!(memory_usage.live_allocations(), 0)
+	}
+}
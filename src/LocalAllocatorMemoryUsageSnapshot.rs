@@ -0,0 +1,129 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// An immutable snapshot of `LocalAllocatorMemoryUsage`.
+///
+/// Subtract an earlier snapshot from a later one to find the change between them.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LocalAllocatorMemoryUsageSnapshot
+{
+	/// Bytes allocated.
+	pub allocated: u64,
+
+	/// Bytes deallocated.
+	pub deallocated: u64,
+
+	/// Bytes added by growing reallocations.
+	pub growing_reallocated: u64,
+
+	/// Bytes removed by shrinking reallocations.
+	pub shrinking_reallocated: u64,
+
+	/// Number of allocations.
+	pub allocations: u64,
+
+	/// Number of deallocations.
+	pub deallocations: u64,
+
+	/// Number of growing reallocations.
+	pub growing_reallocations: u64,
+
+	/// Number of shrinking reallocations.
+	pub shrinking_reallocations: u64,
+
+	/// Number of failed allocations, including failed growing reallocations.
+	pub failed_allocations: u64,
+
+	/// Peak memory usage.
+	pub peak_usage: u64,
+}
+
+impl Sub for LocalAllocatorMemoryUsageSnapshot
+{
+	type Output = Self;
+
+	/// The peak usage of the result is that of `self`, as a peak is not a counter.
+	///
+	/// Counters saturate at zero, so subtracting a later snapshot from an earlier one does not panic.
+	#[inline(always)]
+	fn sub(self, rhs: Self) -> Self::Output
+	{
+		Self
+		{
+			allocated: self.allocated.saturating_sub(rhs.allocated),
+			deallocated: self.deallocated.saturating_sub(rhs.deallocated),
+			growing_reallocated: self.growing_reallocated.saturating_sub(rhs.growing_reallocated),
+			shrinking_reallocated: self.shrinking_reallocated.saturating_sub(rhs.shrinking_reallocated),
+			allocations: self.allocations.saturating_sub(rhs.allocations),
+			deallocations: self.deallocations.saturating_sub(rhs.deallocations),
+			growing_reallocations: self.growing_reallocations.saturating_sub(rhs.growing_reallocations),
+			shrinking_reallocations: self.shrinking_reallocations.saturating_sub(rhs.shrinking_reallocations),
+			failed_allocations: self.failed_allocations.saturating_sub(rhs.failed_allocations),
+			peak_usage: self.peak_usage,
+		}
+	}
+}
+
+impl LocalAllocatorMemoryUsageSnapshot
+{
+	/// Memory usage.
+	///
+	/// For a difference of snapshots, this is the change in memory usage, which may be negative.
+	#[inline(always)]
+	pub fn usage(&self) -> i64
+	{
+		((self.allocated + self.growing_reallocated) as i64) - ((self.deallocated + self.shrinking_reallocated) as i64)
+	}
+
+	/// Number of allocations not yet deallocated.
+	///
+	/// For a difference of snapshots, this is the change in live allocations, which may be negative.
+	#[inline(always)]
+	pub fn live_allocations(&self) -> i64
+	{
+		(self.allocations as i64) - (self.deallocations as i64)
+	}
+
+	/// Total number of reallocations.
+	#[inline(always)]
+	pub fn reallocations(&self) -> u64
+	{
+		self.growing_reallocations + self.shrinking_reallocations
+	}
+}
+
+#[cfg(test)]
+mod LocalAllocatorMemoryUsageSnapshotTests
+{
+	use super::*;
+
+	#[test]
+	pub fn difference_of_snapshots_is_the_change_between_them()
+	{
+		let allocator = GlobalAllocToAllocatorAdaptor(System);
+		let memory_usage = LocalAllocatorMemoryUsage::new();
+		let (retained, _) = memory_usage.allocate(&allocator, 32.non_zero(), 8.non_zero()).expect("Did not allocate");
+
+		let before = memory_usage.snapshot();
+		let (allocation, _) = memory_usage.allocate(&allocator, 64.non_zero(), 8.non_zero()).expect("Did not allocate");
+		memory_usage.deallocate(&allocator, 64.non_zero(), 8.non_zero(), allocation);
+		let after = memory_usage.snapshot();
+
+		let difference = after - before;
+		assert_eq!(difference.allocated, 64);
+		assert_eq!(difference.deallocated, 64);
+		assert_eq!(difference.allocations, 1);
+		assert_eq!(difference.deallocations, 1);
+		assert_eq!(difference.usage(), 0);
+		assert_eq!(difference.live_allocations(), 0);
+		assert_eq!(difference.peak_usage, 96, "Peak usage is not that of the later snapshot");
+
+		let reversed = before - after;
+		assert_eq!(reversed.allocated, 0, "Did not saturate");
+		assert_eq!(reversed.allocations, 0, "Did not saturate");
+		assert_eq!(reversed.peak_usage, 32);
+
+		memory_usage.deallocate(&allocator, 32.non_zero(), 8.non_zero(), retained)
+	}
+}
@@ -0,0 +1,104 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A bounded stack of registered local allocators, eg for request-scoped, coroutine-scoped and connection-scoped lifetimes.
+///
+/// Allocations are made from the top of the stack when the current allocator in use is `CurrentAllocatorInUse::Stacked`.
+/// Deallocations and reallocations are routed to whichever level on the stack owns the memory.
+///
+/// The stack never allocates; it is a fixed size array.
+#[derive(Debug)]
+pub struct LocalAllocatorStack
+{
+	depth: usize,
+
+	levels: [Option<StackedLocalAllocator>; Self::MaximumDepth],
+}
+
+impl LocalAllocatorStack
+{
+	/// Maximum number of local allocators that can be registered at once.
+	pub const MaximumDepth: usize = 16;
+
+	#[inline(always)]
+	const fn empty() -> Self
+	{
+		Self
+		{
+			depth: 0,
+			levels: [None; Self::MaximumDepth],
+		}
+	}
+
+	/// Number of local allocators currently registered.
+	#[inline(always)]
+	pub fn depth(&self) -> usize
+	{
+		self.depth
+	}
+
+	/// Is empty?
+	#[inline(always)]
+	pub fn is_empty(&self) -> bool
+	{
+		self.depth == 0
+	}
+
+	/// The local allocator at the top of the stack, if any.
+	#[inline(always)]
+	pub fn top(&self) -> Option<&StackedLocalAllocator>
+	{
+		if unlikely!(self.is_empty())
+		{
+			None
+		}
+		else
+		{
+			self.levels.get_unchecked_safe(self.depth - 1).as_ref()
+		}
+	}
+
+	/// Finds the level which owns `current_memory`, searching from the top of the stack to the bottom.
+	#[inline(always)]
+	pub fn owner_of(&self, current_memory: MemoryAddress) -> Option<&StackedLocalAllocator>
+	{
+		let mut level = self.depth;
+		while level != 0
+		{
+			level -= 1;
+			if let Some(ref stacked_local_allocator) = self.levels.get_unchecked_safe(level)
+			{
+				if stacked_local_allocator.contains(current_memory)
+				{
+					return Some(stacked_local_allocator)
+				}
+			}
+		}
+		None
+	}
+
+	/// Panics if the stack is full.
+	#[inline(always)]
+	fn push(&mut self, stacked_local_allocator: StackedLocalAllocator) -> usize
+	{
+		assert!(self.depth < Self::MaximumDepth, "Local allocator stack is full; it has a maximum depth of `{}`", Self::MaximumDepth);
+
+		self.levels[self.depth] = Some(stacked_local_allocator);
+		self.depth += 1;
+		self.depth
+	}
+
+	/// Aborts if not popped in the reverse order to which pushed, as a level would be left referring to a local allocator which may no longer exist.
+	#[inline(always)]
+	fn pop(&mut self, expected_depth: usize)
+	{
+		if unlikely!(self.depth != expected_depth || self.depth == 0)
+		{
+			abort_with_message(format_args!("Local allocators must be popped in the reverse order to which they were pushed; popping depth `{}` but the local allocator stack has depth `{}`", expected_depth, self.depth))
+		}
+
+		self.depth -= 1;
+		self.levels[self.depth] = None;
+	}
+}
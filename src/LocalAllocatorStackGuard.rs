@@ -0,0 +1,87 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Pops a local allocator from the `LocalAllocatorStack` and restores the previous `CurrentAllocatorInUse` when dropped, including when dropped during unwinding.
+///
+/// Created using `GlobalThreadAndCoroutineSwitchableAllocator::push_local_allocator()`.
+///
+/// Any memory still allocated from the popped local allocator must not be deallocated or reallocated after this guard is dropped.
+/// It must not be forgotten, eg with `mem::forget()`; see the safety requirements of `push_local_allocator()`.
+///
+/// Not `Send`, as the local allocator stack is per-thread state.
+#[must_use = "The local allocator is popped as soon as the guard is dropped"]
+pub struct LocalAllocatorStackGuard<'a, CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>>
+{
+	global_allocator: &'a GTACSA,
+	depth: usize,
+	restore_to: CurrentAllocatorInUse,
+	memory_range_owner: &'static MemoryRangeOwner,
+	previous_assignment: Option<(usize, usize, *mut RemoteFreeQueue)>,
+	marker: PhantomData<(CoroutineHeapSize, *const ())>,
+}
+
+impl<'a, CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>> Drop for LocalAllocatorStackGuard<'a, CoroutineHeapSize, GTACSA>
+{
+	#[inline(always)]
+	fn drop(&mut self)
+	{
+		let depth = self.depth;
+		self.global_allocator.use_per_thread_state(|per_thread_state| per_thread_state.local_allocator_stack.pop(depth));
+		match self.previous_assignment
+		{
+			None => MemoryRangeRegistry::deregister(self.memory_range_owner),
+			
+			Some(previous_assignment) => self.memory_range_owner.restore_assignment(previous_assignment),
+		}
+		self.global_allocator.restore_current_allocator_in_use(self.restore_to)
+	}
+}
+
+impl<'a, CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>> Debug for LocalAllocatorStackGuard<'a, CoroutineHeapSize, GTACSA>
+{
+	#[inline(always)]
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result
+	{
+		write!(f, "LocalAllocatorStackGuard(depth: {:?}, restore_to: {:?})", self.depth, self.restore_to)
+	}
+}
+
+impl<'a, CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>> LocalAllocatorStackGuard<'a, CoroutineHeapSize, GTACSA>
+{
+	#[inline(always)]
+	fn new<MS: MemorySource, LA: LocalAllocator<MS>>(global_allocator: &'a GTACSA, local_allocator: &'a LA) -> Self
+	{
+		let stacked_local_allocator = StackedLocalAllocator::new::<MS, LA>(local_allocator);
+		
+		// Memory sources other than `MemoryMapSource` do not register themselves, so the memory range is registered for as long as it is pushed.
+		// A memory range which is already registered, eg that of the thread-local allocator, keeps any queue, and has its previous assignment restored when popped.
+		let (memory_range_owner, registered) = MemoryRangeRegistry::owner_of_registering_if_necessary(stacked_local_allocator.memory_range());
+		let previous_assignment = if registered
+		{
+			None
+		}
+		else
+		{
+			Some(memory_range_owner.assignment())
+		};
+		memory_range_owner.assign(CurrentAllocatorInUse::Stacked, memory_range_owner.remote_free_queue());
+		
+		Self
+		{
+			global_allocator,
+			depth: global_allocator.use_per_thread_state(|per_thread_state| per_thread_state.local_allocator_stack.push(stacked_local_allocator)),
+			restore_to: global_allocator.replace_current_allocator_in_use(CurrentAllocatorInUse::Stacked),
+			memory_range_owner,
+			previous_assignment,
+			marker: PhantomData,
+		}
+	}
+
+	/// Depth of the local allocator stack whilst this guard is live.
+	#[inline(always)]
+	pub fn depth(&self) -> usize
+	{
+		self.depth
+	}
+}
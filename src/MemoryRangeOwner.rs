@@ -0,0 +1,215 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Head of a process-wide, append-only, singly-linked list of memory range owners.
+///
+/// Owners are never freed; a deregistered owner is later reused.
+static MemoryRangeOwners: AtomicPtr<MemoryRangeOwner> = AtomicPtr::new(null_mut());
+
+/// Used to identify the current thread by the address of this thread-local.
+#[thread_local] static CurrentThread: u8 = 0;
+
+/// The owner of a memory range registered in the `MemoryRangeRegistry`.
+///
+/// The tier and thread are known once the memory range's local allocator has been put into use by a `GlobalThreadAndCoroutineSwitchableAllocator`.
+#[derive(Debug)]
+pub struct MemoryRangeOwner
+{
+	next_in_registry: *const MemoryRangeOwner,
+
+	is_live: AtomicBool,
+
+	from: AtomicUsize,
+
+	to: AtomicUsize,
+
+	next_on_first_page: AtomicPtr<MemoryRangeOwner>,
+
+	next_on_interior_pages: AtomicPtr<MemoryRangeOwner>,
+
+	next_on_last_page: AtomicPtr<MemoryRangeOwner>,
+
+	tier: AtomicUsize,
+
+	thread: AtomicUsize,
+
+	remote_free_queue: AtomicPtr<RemoteFreeQueue>,
+}
+
+unsafe impl Send for MemoryRangeOwner
+{
+}
+
+unsafe impl Sync for MemoryRangeOwner
+{
+}
+
+impl MemoryRangeOwner
+{
+	const Unassigned: usize = CurrentAllocatorInUse::encode(None);
+
+	/// The registered memory range.
+	#[inline(always)]
+	pub fn memory_range(&self) -> MemoryRange
+	{
+		MemoryRange::new(MemoryAddress::from_usize(self.from.load(Relaxed)), MemoryAddress::from_usize(self.to.load(Relaxed)))
+	}
+
+	/// The tier of the local allocator using this memory range, if it has been put into use.
+	#[inline(always)]
+	pub fn tier(&self) -> Option<CurrentAllocatorInUse>
+	{
+		CurrentAllocatorInUse::decode(self.tier.load(Acquire))
+	}
+
+	/// An opaque identifier of the thread which put the local allocator using this memory range into use, if any.
+	///
+	/// Identifiers are only unique amongst live threads.
+	#[inline(always)]
+	pub fn thread(&self) -> Option<NonZeroUsize>
+	{
+		NonZeroUsize::new(self.thread.load(Acquire))
+	}
+
+	/// Was the local allocator using this memory range put into use by the current thread?
+	#[inline(always)]
+	pub fn is_owned_by_current_thread(&self) -> bool
+	{
+		self.thread() == Some(Self::current_thread())
+	}
+
+	/// The queue to use to deallocate memory if owned by a thread-local or coroutine local allocator which is not in use by the deallocating thread.
+	#[inline(always)]
+	pub fn remote_free_queue(&self) -> Option<&'static RemoteFreeQueue>
+	{
+		let remote_free_queue = self.remote_free_queue.load(Acquire) as *const RemoteFreeQueue;
+		if remote_free_queue.is_null()
+		{
+			None
+		}
+		else
+		{
+			Some(remote_free_queue.reference())
+		}
+	}
+
+	/// Records that the local allocator using this memory range has been put into use on the current thread.
+	#[inline(always)]
+	pub(crate) fn assign(&self, tier: CurrentAllocatorInUse, remote_free_queue: Option<&'static RemoteFreeQueue>)
+	{
+		self.thread.store(Self::current_thread().get(), Relaxed);
+		self.remote_free_queue.store(remote_free_queue.map(|remote_free_queue| remote_free_queue as *const RemoteFreeQueue as *mut RemoteFreeQueue).unwrap_or(null_mut()), Relaxed);
+		self.tier.store(CurrentAllocatorInUse::encode(Some(tier)), Release);
+	}
+
+	/// The tier, thread and queue recorded by `assign()`, so that they can be put back with `restore_assignment()`.
+	#[inline(always)]
+	pub(crate) fn assignment(&self) -> (usize, usize, *mut RemoteFreeQueue)
+	{
+		(self.tier.load(Acquire), self.thread.load(Relaxed), self.remote_free_queue.load(Relaxed))
+	}
+
+	/// Puts back an assignment previously obtained with `assignment()`.
+	#[inline(always)]
+	pub(crate) fn restore_assignment(&self, (tier, thread, remote_free_queue): (usize, usize, *mut RemoteFreeQueue))
+	{
+		self.thread.store(thread, Relaxed);
+		self.remote_free_queue.store(remote_free_queue, Relaxed);
+		self.tier.store(tier, Release);
+	}
+
+	#[inline(always)]
+	fn current_thread() -> NonZeroUsize
+	{
+		new_non_zero_usize(&CurrentThread as *const u8 as usize)
+	}
+
+	/// The next owner in the chain of owners of memory ranges overlapping `page_number`, which must be a page of this owner's memory range.
+	///
+	/// Only the first and last pages of a memory range can be shared with memory ranges which do not enclose it, so pages in between share a link.
+	#[inline(always)]
+	fn next_on_page(&self, page_number: usize) -> &AtomicPtr<Self>
+	{
+		let memory_range = self.memory_range();
+		if page_number == MemoryRangeRegistry::page_number(memory_range.from)
+		{
+			&self.next_on_first_page
+		}
+		else if page_number == MemoryRangeRegistry::page_number(memory_range.to.subtract(1))
+		{
+			&self.next_on_last_page
+		}
+		else
+		{
+			&self.next_on_interior_pages
+		}
+	}
+
+	#[inline(always)]
+	fn release(&self)
+	{
+		self.tier.store(Self::Unassigned, Relaxed);
+		self.thread.store(0, Relaxed);
+		self.remote_free_queue.store(null_mut(), Relaxed);
+		self.is_live.store(false, Release)
+	}
+
+	/// Panics if a new owner is needed and can not be allocated.
+	#[inline(always)]
+	fn claim(memory_range: MemoryRange) -> &'static Self
+	{
+		let this = Self::claim_vacant_or_allocate();
+		this.from.store(memory_range.from.to_usize(), Relaxed);
+		this.to.store(memory_range.to.to_usize(), Relaxed);
+		this
+	}
+
+	#[inline(always)]
+	fn claim_vacant_or_allocate() -> &'static Self
+	{
+		let mut memory_range_owner = MemoryRangeOwners.load(Acquire) as *const Self;
+		while memory_range_owner.is_not_null()
+		{
+			let this = memory_range_owner.reference();
+			if this.is_live.compare_exchange(false, true, AcqRel, Relaxed).is_ok()
+			{
+				return this
+			}
+			memory_range_owner = this.next_in_registry;
+		}
+
+		let this = unsafe { System.alloc(Layout::new::<Self>()) } as *mut Self;
+		assert!(this.is_not_null(), "Could not allocate a memory range owner");
+		let mut head = MemoryRangeOwners.load(Acquire);
+		loop
+		{
+			unsafe
+			{
+				this.write
+				(
+					Self
+					{
+						next_in_registry: head,
+						is_live: AtomicBool::new(true),
+						from: AtomicUsize::new(0),
+						to: AtomicUsize::new(0),
+						next_on_first_page: AtomicPtr::new(null_mut()),
+						next_on_interior_pages: AtomicPtr::new(null_mut()),
+						next_on_last_page: AtomicPtr::new(null_mut()),
+						tier: AtomicUsize::new(Self::Unassigned),
+						thread: AtomicUsize::new(0),
+						remote_free_queue: AtomicPtr::new(null_mut()),
+					}
+				)
+			};
+
+			match MemoryRangeOwners.compare_exchange_weak(head, this, AcqRel, Acquire)
+			{
+				Ok(_) => return this.reference(),
+
+				Err(was) => head = was,
+			}
+		}
+	}
+}
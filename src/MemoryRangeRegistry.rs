@@ -0,0 +1,332 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Root of the radix map; nodes are allocated lazily and never freed.
+static MemoryRangeRegistryRoot: AtomicPtr<MemoryRangeRegistryNode> = AtomicPtr::new(null_mut());
+
+/// Held whilst registering or deregistering, as both relink the chains of pages which may be shared.
+static MemoryRangeRegistryWriter: AtomicBool = AtomicBool::new(false);
+
+/// A process-wide, page-granular radix map from a memory address to the `MemoryRangeOwner` of the registered `MemoryRange` containing it.
+///
+/// A `MemoryMapSource` registers its memory range when created and deregisters it when dropped; the memory ranges of other local allocators are registered when they are first put into use by a `GlobalThreadAndCoroutineSwitchableAllocator`.
+/// Lookups with `owner_of()` are constant time and lock-free.
+///
+/// Ranges need not be page-aligned; each page has a chain of the owners of the ranges overlapping it, most recently registered first, so ranges can share a page.
+/// Ranges may be nested (eg coroutine heap memory inside a memory map), in which case the enclosing range must be registered first; the innermost registration wins until it is deregistered.
+///
+/// Covers a 48-bit virtual address space; addresses above it are never registered.
+#[derive(Debug)]
+pub struct MemoryRangeRegistry;
+
+impl MemoryRangeRegistry
+{
+	const PageShift: usize = 12;
+
+	const BitsPerLevel: usize = 12;
+
+	const EntriesPerNode: usize = 1 << Self::BitsPerLevel;
+
+	const IndexMask: usize = Self::EntriesPerNode - 1;
+
+	const Levels: usize = 3;
+
+	const MaximumPageNumber: usize = 1 << (Self::BitsPerLevel * Self::Levels);
+
+	/// Finds the owner of the registered memory range containing `address`, if any.
+	#[inline(always)]
+	pub fn owner_of(address: MemoryAddress) -> Option<&'static MemoryRangeOwner>
+	{
+		let page_number = Self::page_number(address);
+		if unlikely!(page_number >= Self::MaximumPageNumber)
+		{
+			return None
+		}
+
+		let mut node = MemoryRangeRegistryRoot.load(Acquire);
+		let mut level = Self::Levels - 1;
+		while level != 0
+		{
+			if unlikely!(node.is_null())
+			{
+				return None
+			}
+			node = node.reference().entry(Self::index(page_number, level)).load(Acquire) as *mut MemoryRangeRegistryNode;
+			level -= 1;
+		}
+
+		if unlikely!(node.is_null())
+		{
+			return None
+		}
+		// Owners are recycled; a concurrent deregistration may have reused one in the chain for a different range, so each is checked to contain `address`.
+		let mut memory_range_owner = node.reference().entry(Self::index(page_number, 0)).load(Acquire) as *const MemoryRangeOwner;
+		while memory_range_owner.is_not_null()
+		{
+			let this = memory_range_owner.reference();
+			if likely!(this.memory_range().contains(address))
+			{
+				return Some(this)
+			}
+			memory_range_owner = this.next_on_page(page_number).load(Acquire) as *const MemoryRangeOwner;
+		}
+		None
+	}
+
+	/// Registers `memory_range`.
+	///
+	/// Panics if memory for book-keeping can not be allocated.
+	#[inline(always)]
+	pub(crate) fn register(memory_range: MemoryRange) -> &'static MemoryRangeOwner
+	{
+		let memory_range_owner = MemoryRangeOwner::claim(memory_range);
+		let this = memory_range_owner as *const MemoryRangeOwner as *mut MemoryRangeOwner;
+
+		Self::exclusively(|| Self::for_each_page(memory_range, |page_number|
+		{
+			let entry = Self::leaf_entry_creating_if_necessary(page_number);
+			memory_range_owner.next_on_page(page_number).store(entry.load(Relaxed), Relaxed);
+			entry.store(this, Release);
+		}));
+
+		memory_range_owner
+	}
+
+	/// Deregisters a memory range previously registered with `register()`, unlinking it from the chain of each of its pages.
+	#[inline(always)]
+	pub(crate) fn deregister(memory_range_owner: &'static MemoryRangeOwner)
+	{
+		let this = memory_range_owner as *const MemoryRangeOwner as *mut MemoryRangeOwner;
+
+		Self::exclusively(|| Self::for_each_page(memory_range_owner.memory_range(), |page_number|
+		{
+			let next = memory_range_owner.next_on_page(page_number).load(Relaxed);
+			let mut link = Self::leaf_entry_creating_if_necessary(page_number);
+			loop
+			{
+				let linked = link.load(Relaxed);
+				if unlikely!(linked.is_null())
+				{
+					break
+				}
+				if linked == this
+				{
+					link.store(next, Release);
+					break
+				}
+				link = linked.reference().next_on_page(page_number);
+			}
+		}));
+
+		memory_range_owner.release()
+	}
+
+	/// Finds the owner of exactly `memory_range`, registering it if it is not already registered.
+	///
+	/// Returns `true` if it was registered by this call, in which case the caller is responsible for deregistering it.
+	#[inline(always)]
+	pub(crate) fn owner_of_registering_if_necessary(memory_range: MemoryRange) -> (&'static MemoryRangeOwner, bool)
+	{
+		match Self::owner_of(memory_range.from)
+		{
+			Some(memory_range_owner) if memory_range_owner.memory_range() == memory_range => (memory_range_owner, false),
+
+			_ => (Self::register(memory_range), true),
+		}
+	}
+
+	/// Aborts, as there is nothing safe that can be done with the memory; panicking is not permitted inside a global allocator, and leaking would hide the bug.
+	#[cold]
+	#[inline(never)]
+	pub(crate) fn memory_belongs_to_no_known_allocator(current_memory: MemoryAddress) -> !
+	{
+		abort_with_message(format_args!("Memory `{:?}` belongs to no known allocator that can deallocate it; it is probably owned by a local allocator which has since been dropped or popped", current_memory))
+	}
+
+	#[inline(always)]
+	fn exclusively(writer: impl FnOnce())
+	{
+		while MemoryRangeRegistryWriter.compare_exchange_weak(false, true, Acquire, Relaxed).is_err()
+		{
+			spin_loop()
+		}
+		writer();
+		MemoryRangeRegistryWriter.store(false, Release)
+	}
+
+	#[inline(always)]
+	fn for_each_page(memory_range: MemoryRange, mut page_user: impl FnMut(usize))
+	{
+		let first_page_number = Self::page_number(memory_range.from);
+		let last_page_number = min(Self::page_number(memory_range.to.subtract(1)), Self::MaximumPageNumber - 1);
+
+		let mut page_number = first_page_number;
+		while page_number <= last_page_number
+		{
+			page_user(page_number);
+			page_number += 1;
+		}
+	}
+
+	#[inline(always)]
+	fn leaf_entry_creating_if_necessary(page_number: usize) -> &'static AtomicPtr<MemoryRangeOwner>
+	{
+		let mut node = MemoryRangeRegistryNode::get_or_create(&MemoryRangeRegistryRoot);
+		let mut level = Self::Levels - 1;
+		while level != 0
+		{
+			let entry = node.entry(Self::index(page_number, level));
+			node = MemoryRangeRegistryNode::get_or_create(unsafe { & * (entry as *const AtomicPtr<()> as *const AtomicPtr<MemoryRangeRegistryNode>) });
+			level -= 1;
+		}
+		unsafe { & * (node.entry(Self::index(page_number, 0)) as *const AtomicPtr<()> as *const AtomicPtr<MemoryRangeOwner>) }
+	}
+
+	#[inline(always)]
+	fn page_number(address: MemoryAddress) -> usize
+	{
+		address.to_usize() >> Self::PageShift
+	}
+
+	#[inline(always)]
+	fn index(page_number: usize, level: usize) -> usize
+	{
+		(page_number >> (level * Self::BitsPerLevel)) & Self::IndexMask
+	}
+}
+
+/// A node in the radix map; entries in interior nodes point to nodes, entries in leaf nodes point to `MemoryRangeOwner`s.
+///
+/// Only ever created zeroed, which is equivalent to all entries being null.
+#[repr(transparent)]
+struct MemoryRangeRegistryNode
+{
+	entries: [AtomicPtr<()>; MemoryRangeRegistry::EntriesPerNode],
+}
+
+impl MemoryRangeRegistryNode
+{
+	#[inline(always)]
+	fn entry(&self, index: usize) -> &AtomicPtr<()>
+	{
+		self.entries.get_unchecked_safe(index)
+	}
+
+	#[inline(always)]
+	fn get_or_create(entry: &AtomicPtr<Self>) -> &'static Self
+	{
+		let node = entry.load(Acquire);
+		if likely!(node.is_not_null())
+		{
+			return node.reference()
+		}
+
+		let layout = Layout::new::<Self>();
+		let new_node = unsafe { System.alloc_zeroed(layout) } as *mut Self;
+		assert!(new_node.is_not_null(), "Could not allocate a memory range registry node");
+
+		match entry.compare_exchange(null_mut(), new_node, AcqRel, Acquire)
+		{
+			Ok(_) => new_node.reference(),
+
+			Err(node) =>
+			{
+				unsafe { System.dealloc(new_node as *mut u8, layout) };
+				node.reference()
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod MemoryRangeRegistryTests
+{
+	use super::*;
+
+	#[test]
+	pub fn memory_range_is_registered_only_if_necessary()
+	{
+		let buffer: &'static mut [u64; 8] = Box::leak(Box::new([0; 8]));
+		let from = NonNull::from(buffer).cast::<u8>();
+		let memory_range = MemoryRange::new(from, from.add(size_of::<[u64; 8]>()));
+
+		let (memory_range_owner, registered) = MemoryRangeRegistry::owner_of_registering_if_necessary(memory_range);
+		assert!(registered, "Did not register an unregistered memory range");
+		assert_eq!(memory_range_owner.memory_range(), memory_range);
+		assert!(memory_range_owner.tier().is_none(), "Memory range was in use before being assigned");
+
+		let (registered_memory_range_owner, registered_again) = MemoryRangeRegistry::owner_of_registering_if_necessary(memory_range);
+		assert!(!registered_again, "Registered an already registered memory range");
+		assert_eq!(registered_memory_range_owner as *const MemoryRangeOwner, memory_range_owner as *const MemoryRangeOwner, "Did not find the owner of an already registered memory range");
+
+		let owner_of_last_byte = MemoryRangeRegistry::owner_of(memory_range.to.subtract(1)).expect("Did not find owner of last byte");
+		assert_eq!(owner_of_last_byte as *const MemoryRangeOwner, memory_range_owner as *const MemoryRangeOwner);
+
+		MemoryRangeRegistry::deregister(memory_range_owner);
+		assert!(MemoryRangeRegistry::owner_of(from).is_none(), "Memory range was still registered");
+	}
+
+	#[test]
+	pub fn adjacent_memory_ranges_sharing_a_page_are_both_found()
+	{
+		let from = new_pages(1);
+		let first = MemoryRange::new(from, from.add(100));
+		let second = MemoryRange::new(from.add(100), from.add(200));
+
+		let first_owner = MemoryRangeRegistry::register(first);
+		let second_owner = MemoryRangeRegistry::register(second);
+		assert_owner(first.from, first_owner);
+		assert_owner(second.from, second_owner);
+
+		MemoryRangeRegistry::deregister(first_owner);
+		assert!(MemoryRangeRegistry::owner_of(first.from).is_none(), "First memory range was still registered");
+		assert_owner(second.from, second_owner);
+
+		let first_owner = MemoryRangeRegistry::register(first);
+		MemoryRangeRegistry::deregister(second_owner);
+		assert_owner(first.from, first_owner);
+		assert!(MemoryRangeRegistry::owner_of(second.from).is_none(), "Second memory range was still registered");
+
+		MemoryRangeRegistry::deregister(first_owner);
+		assert!(MemoryRangeRegistry::owner_of(first.from).is_none(), "First memory range was still registered");
+	}
+
+	#[test]
+	pub fn nested_memory_range_is_found_until_deregistered()
+	{
+		let from = new_pages(2);
+		let outer = MemoryRange::new(from, from.add(2 * PageSize));
+		let inner = MemoryRange::new(from.add(PageSize - 96), from.add(PageSize + 96));
+
+		let outer_owner = MemoryRangeRegistry::register(outer);
+		let inner_owner = MemoryRangeRegistry::register(inner);
+		assert_owner(outer.from, outer_owner);
+		assert_owner(inner.from, inner_owner);
+		assert_owner(inner.to.subtract(1), inner_owner);
+		assert_owner(inner.to, outer_owner);
+		assert_owner(outer.to.subtract(1), outer_owner);
+
+		MemoryRangeRegistry::deregister(inner_owner);
+		assert_owner(inner.from, outer_owner);
+		assert_owner(inner.to.subtract(1), outer_owner);
+
+		MemoryRangeRegistry::deregister(outer_owner);
+		assert!(MemoryRangeRegistry::owner_of(inner.from).is_none(), "Outer memory range was still registered");
+	}
+
+	const PageSize: usize = 1 << MemoryRangeRegistry::PageShift;
+
+	/// Whole pages, so that memory ranges registered by other tests can not share them.
+	fn new_pages(number_of_pages: usize) -> MemoryAddress
+	{
+		let pages = unsafe { System.alloc(Layout::from_size_align(number_of_pages * PageSize, PageSize).unwrap()) };
+		NonNull::new(pages).expect("Could not allocate pages")
+	}
+
+	fn assert_owner(address: MemoryAddress, memory_range_owner: &'static MemoryRangeOwner)
+	{
+		let owner_of = MemoryRangeRegistry::owner_of(address).expect("Did not find owner");
+		assert_eq!(owner_of as *const MemoryRangeOwner, memory_range_owner as *const MemoryRangeOwner, "Found the wrong owner of `{:?}`", address)
+	}
+}
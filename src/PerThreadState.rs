@@ -7,9 +7,23 @@ pub struct PerThreadState<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator
 {
 	current_allocator_in_use: CurrentAllocatorInUse,
 
-	coroutine_local_allocator: Option<CoroutineLocalAllocator>,
+	coroutine_local_allocator: Option<MemoryUsageTrackingAllocator<CoroutineLocalAllocator>>,
 
-	thread_local_allocator: Option<MemoryUsageTrackingThreadLocalAllocator<ThreadLocalAllocator>>,
+	coroutine_memory_quota: Option<NonNull<CoroutineMemoryQuota>>,
+
+	thread_local_allocator: Option<MemoryUsageTrackingAllocator<ThreadLocalAllocator>>,
+
+	thread_local_allocator_factory_disabled: bool,
+
+	global_allocator_memory_usage: LocalAllocatorMemoryUsage,
+
+	stacked_allocator_memory_usage: LocalAllocatorMemoryUsage,
+
+	local_allocator_stack: LocalAllocatorStack,
+
+	remote_free_queue: Option<&'static RemoteFreeQueue>,
+
+	registered_thread_local_memory_range_owner: Option<&'static MemoryRangeOwner>,
 
 	marker: PhantomData<CoroutineHeapSize>,
 }
@@ -24,8 +38,52 @@ impl<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocator<Coro
 		{
 			current_allocator_in_use: CurrentAllocatorInUse::Global,
 			coroutine_local_allocator: None,
+			coroutine_memory_quota: None,
 			thread_local_allocator: None,
+			thread_local_allocator_factory_disabled: false,
+			global_allocator_memory_usage: LocalAllocatorMemoryUsage::new(),
+			stacked_allocator_memory_usage: LocalAllocatorMemoryUsage::new(),
+			local_allocator_stack: LocalAllocatorStack::empty(),
+			remote_free_queue: None,
+			registered_thread_local_memory_range_owner: None,
 			marker: PhantomData,
 		}
 	}
+
+	#[inline(always)]
+	fn thread_local_allocator_memory_usage<R, LAMUU: FnOnce(&LocalAllocatorMemoryUsage) -> R>(&self, local_allocator_message_usage_user: LAMUU) -> R
+	{
+		local_allocator_message_usage_user(self.thread_local_allocator.as_ref().expect("Thread local allocator needs to have been initialized").memory_usage())
+	}
+
+	#[inline(always)]
+	fn drain_remote_frees(&self) -> usize
+	{
+		match (&self.thread_local_allocator, self.remote_free_queue)
+		{
+			(&Some(ref thread_local_allocator), Some(remote_free_queue)) => remote_free_queue.drain_into(thread_local_allocator),
+
+			_ => 0,
+		}
+	}
+
+	#[inline(always)]
+	fn drop_thread_local_allocator(&mut self)
+	{
+		if let Some(ref thread_local_allocator) = self.thread_local_allocator
+		{
+			if let Some(remote_free_queue) = self.remote_free_queue.take()
+			{
+				remote_free_queue.retire(thread_local_allocator);
+			}
+		}
+
+		debug_assert!(self.thread_local_allocator.is_some(), "Already deinitialized thread-local allocator");
+
+		self.thread_local_allocator = None;
+		if let Some(registered_thread_local_memory_range_owner) = self.registered_thread_local_memory_range_owner.take()
+		{
+			MemoryRangeRegistry::deregister(registered_thread_local_memory_range_owner)
+		}
+	}
 }
@@ -0,0 +1,388 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Encodes a `SamplingHeapProfileReport` as an uncompressed pprof `profile.proto` message.
+///
+/// Each distinct instruction pointer becomes a `Location` with one `Line` for each of its symbols (so inlined functions are preserved), and each distinct function name, system name and source file a `Function`.
+/// Mappings are not known, so there are none; tools symbolize using the functions.
+struct PprofHeapProfileEncoder
+{
+	strings: HashMap<String, i64>,
+
+	string_table: Vec<String>,
+
+	functions: HashMap<(i64, i64, i64), u64>,
+
+	locations: HashMap<usize, u64>,
+
+	profile: Vec<u8>,
+}
+
+impl PprofHeapProfileEncoder
+{
+	const VarintWireType: u64 = 0;
+
+	const LengthDelimitedWireType: u64 = 2;
+
+	const SampleTypes: [(&'static str, &'static str); 4] =
+	[
+		("alloc_objects", "count"),
+		("alloc_space", "bytes"),
+		("inuse_objects", "count"),
+		("inuse_space", "bytes"),
+	];
+
+	const UnknownFunctionName: &'static str = "<unknown>";
+
+	#[inline(always)]
+	fn encode(report: &SamplingHeapProfileReport) -> Vec<u8>
+	{
+		let mut encoder = Self
+		{
+			strings: HashMap::new(),
+			string_table: Vec::new(),
+			functions: HashMap::new(),
+			locations: HashMap::new(),
+			profile: Vec::new(),
+		};
+		encoder.string("");
+
+		// `Profile.sample_type`.
+		for &(sample_type, unit) in Self::SampleTypes.iter()
+		{
+			let value_type = encoder.value_type(sample_type, unit);
+			Self::write_length_delimited_field(&mut encoder.profile, 1, &value_type);
+		}
+
+		// `Profile.sample`.
+		for call_stack in report.call_stacks.iter()
+		{
+			let mut location_identifiers: Vec<u64> = call_stack.frames.iter().map(|frame| encoder.location(frame)).collect();
+			if location_identifiers.is_empty()
+			{
+				location_identifiers.push(encoder.location(&SamplingHeapProfileFrame { instruction_pointer: 0, symbols: Vec::new() }))
+			}
+
+			let mut sample = Vec::new();
+			Self::write_packed_field(&mut sample, 1, location_identifiers.iter().cloned());
+			Self::write_packed_field(&mut sample, 2, [call_stack.estimated_allocated_objects, call_stack.estimated_allocated_bytes, call_stack.estimated_objects, call_stack.estimated_bytes].iter().cloned());
+			Self::write_length_delimited_field(&mut encoder.profile, 2, &sample);
+		}
+
+		// `Profile.time_nanos`.
+		let time_nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_nanos() as u64).unwrap_or(0);
+		Self::write_varint_field(&mut encoder.profile, 9, time_nanos);
+
+		// `Profile.period_type` and `Profile.period`.
+		let period_type = encoder.value_type("space", "bytes");
+		Self::write_length_delimited_field(&mut encoder.profile, 11, &period_type);
+		Self::write_varint_field(&mut encoder.profile, 12, report.sampling_interval);
+
+		// `Profile.default_sample_type`.
+		let default_sample_type = encoder.string("inuse_space");
+		Self::write_varint_field(&mut encoder.profile, 14, default_sample_type as u64);
+
+		// `Profile.string_table`; must be last as encoding the other fields adds strings.
+		let Self { string_table, mut profile, .. } = encoder;
+		for string in string_table.iter()
+		{
+			Self::write_length_delimited_field(&mut profile, 6, string.as_bytes())
+		}
+
+		profile
+	}
+
+	#[inline(always)]
+	fn location(&mut self, frame: &SamplingHeapProfileFrame) -> u64
+	{
+		let next_identifier = (self.locations.len() as u64) + 1;
+		let location_identifier = *self.locations.entry(frame.instruction_pointer).or_insert(next_identifier);
+		if location_identifier == next_identifier
+		{
+			let mut location = Vec::new();
+			Self::write_varint_field(&mut location, 1, location_identifier);
+			Self::write_varint_field(&mut location, 3, frame.instruction_pointer as u64);
+
+			if frame.symbols.is_empty()
+			{
+				let line_message = self.line(Self::UnknownFunctionName, Self::UnknownFunctionName, "", 0);
+				Self::write_length_delimited_field(&mut location, 4, &line_message);
+			}
+			for symbol in frame.symbols.iter()
+			{
+				let name = symbol.name.as_deref().unwrap_or(Self::UnknownFunctionName);
+				let system_name = symbol.system_name.as_deref().unwrap_or(name);
+				let line_message = self.line(name, system_name, symbol.file_name.as_deref().unwrap_or(""), symbol.line.unwrap_or(0));
+				Self::write_length_delimited_field(&mut location, 4, &line_message);
+			}
+
+			Self::write_length_delimited_field(&mut self.profile, 4, &location);
+		}
+		location_identifier
+	}
+
+	#[inline(always)]
+	fn line(&mut self, name: &str, system_name: &str, file_name: &str, line: u32) -> Vec<u8>
+	{
+		let function_identifier = self.function(name, system_name, file_name);
+
+		let mut line_message = Vec::new();
+		Self::write_varint_field(&mut line_message, 1, function_identifier);
+		Self::write_varint_field(&mut line_message, 2, line as u64);
+		line_message
+	}
+
+	#[inline(always)]
+	fn function(&mut self, name: &str, system_name: &str, file_name: &str) -> u64
+	{
+		let name = self.string(name);
+		let system_name = self.string(system_name);
+		let file_name = self.string(file_name);
+
+		let next_identifier = (self.functions.len() as u64) + 1;
+		let function_identifier = *self.functions.entry((name, system_name, file_name)).or_insert(next_identifier);
+		if function_identifier == next_identifier
+		{
+			let mut function = Vec::new();
+			Self::write_varint_field(&mut function, 1, function_identifier);
+			Self::write_varint_field(&mut function, 2, name as u64);
+			Self::write_varint_field(&mut function, 3, system_name as u64);
+			Self::write_varint_field(&mut function, 4, file_name as u64);
+
+			Self::write_length_delimited_field(&mut self.profile, 5, &function);
+		}
+		function_identifier
+	}
+
+	#[inline(always)]
+	fn value_type(&mut self, value_type: &str, unit: &str) -> Vec<u8>
+	{
+		let value_type = self.string(value_type);
+		let unit = self.string(unit);
+
+		let mut message = Vec::new();
+		Self::write_varint_field(&mut message, 1, value_type as u64);
+		Self::write_varint_field(&mut message, 2, unit as u64);
+		message
+	}
+
+	#[inline(always)]
+	fn string(&mut self, string: &str) -> i64
+	{
+		if let Some(&index) = self.strings.get(string)
+		{
+			return index
+		}
+
+		let index = self.string_table.len() as i64;
+		self.string_table.push(string.to_string());
+		self.strings.insert(string.to_string(), index);
+		index
+	}
+
+	#[inline(always)]
+	fn write_packed_field(buffer: &mut Vec<u8>, field_number: u64, values: impl Iterator<Item=u64>)
+	{
+		let mut packed = Vec::new();
+		for value in values
+		{
+			Self::write_varint(&mut packed, value)
+		}
+		Self::write_length_delimited_field(buffer, field_number, &packed)
+	}
+
+	#[inline(always)]
+	fn write_length_delimited_field(buffer: &mut Vec<u8>, field_number: u64, bytes: &[u8])
+	{
+		Self::write_varint(buffer, (field_number << 3) | Self::LengthDelimitedWireType);
+		Self::write_varint(buffer, bytes.len() as u64);
+		buffer.extend_from_slice(bytes)
+	}
+
+	#[inline(always)]
+	fn write_varint_field(buffer: &mut Vec<u8>, field_number: u64, value: u64)
+	{
+		Self::write_varint(buffer, (field_number << 3) | Self::VarintWireType);
+		Self::write_varint(buffer, value)
+	}
+
+	#[inline(always)]
+	fn write_varint(buffer: &mut Vec<u8>, mut value: u64)
+	{
+		while value >= 0x80
+		{
+			buffer.push((value as u8) | 0x80);
+			value >>= 7;
+		}
+		buffer.push(value as u8)
+	}
+}
+
+#[cfg(test)]
+mod PprofHeapProfileEncoderTests
+{
+	use super::*;
+	use std::str::from_utf8;
+
+	#[test]
+	pub fn encoded_profile_decodes()
+	{
+		let inlined_frame = frame(0x1000, vec![symbol("inner", "_ZN5inner17h0123456789abcdefE", "src/lib.rs", 10), symbol("outer", "_ZN5outer17h0123456789abcdefE", "src/lib.rs", 20)]);
+		let report = SamplingHeapProfileReport
+		{
+			sampling_interval: 4096,
+			call_stacks: vec!
+			[
+				call_stack(vec![inlined_frame.clone(), frame(0x2000, vec![symbol("caller", "_ZN6caller17h0123456789abcdefE", "src/main.rs", 30)])], [1, 4096, 2, 8192]),
+				call_stack(vec![inlined_frame, frame(0x3000, vec![])], [3, 12288, 0, 0]),
+			],
+		};
+
+		let profile = PprofHeapProfileEncoder::encode(&report);
+
+		let strings: Vec<&str> = length_delimited_fields(&profile, 6).into_iter().map(|string| from_utf8(string).expect("Not UTF-8")).collect();
+		assert_eq!(strings[0], "", "String table index 0 was not the empty string");
+
+		let sample_types: Vec<(&str, &str)> = length_delimited_fields(&profile, 1).into_iter().map(|value_type| (strings[varint_field(value_type, 1) as usize], strings[varint_field(value_type, 2) as usize])).collect();
+		assert_eq!(sample_types, PprofHeapProfileEncoder::SampleTypes.to_vec(), "Sample types were wrong");
+
+		let functions: HashMap<u64, (&str, &str, &str)> = length_delimited_fields(&profile, 5).into_iter().map(|function| (varint_field(function, 1), (strings[varint_field(function, 2) as usize], strings[varint_field(function, 3) as usize], strings[varint_field(function, 4) as usize]))).collect();
+		assert_eq!(identifiers(functions.keys()), (1 ..= 4).collect::<Vec<_>>(), "Function identifiers were not 1 to 4");
+		assert!(functions.values().any(|&function| function == ("inner", "_ZN5inner17h0123456789abcdefE", "src/lib.rs")), "System name was not the mangled name");
+		assert!(functions.values().any(|&function| function == (PprofHeapProfileEncoder::UnknownFunctionName, PprofHeapProfileEncoder::UnknownFunctionName, "")), "Unresolved frame had no function");
+
+		let locations: HashMap<u64, (u64, Vec<(&str, u64)>)> = length_delimited_fields(&profile, 4).into_iter().map(|location|
+		{
+			let lines = length_delimited_fields(location, 4).into_iter().map(|line| (functions[&varint_field(line, 1)].0, varint_field(line, 2))).collect();
+			(varint_field(location, 1), (varint_field(location, 3), lines))
+		}).collect();
+		assert_eq!(identifiers(locations.keys()), (1 ..= 3).collect::<Vec<_>>(), "Location identifiers were not 1 to 3");
+
+		let samples: Vec<(Vec<(u64, Vec<(&str, u64)>)>, Vec<u64>)> = length_delimited_fields(&profile, 2).into_iter().map(|sample|
+		{
+			let call_stack = varints(length_delimited_fields(sample, 1)[0]).into_iter().map(|location_identifier| locations[&location_identifier].clone()).collect();
+			(call_stack, varints(length_delimited_fields(sample, 2)[0]))
+		}).collect();
+		let inlined_location = (0x1000, vec![("inner", 10), ("outer", 20)]);
+		assert_eq!
+		(
+			samples,
+			vec!
+			[
+				(vec![inlined_location.clone(), (0x2000, vec![("caller", 30)])], vec![1, 4096, 2, 8192]),
+				(vec![inlined_location, (0x3000, vec![(PprofHeapProfileEncoder::UnknownFunctionName, 0)])], vec![3, 12288, 0, 0]),
+			],
+			"Samples were wrong"
+		);
+
+		assert_eq!(varint_field(&profile, 12), 4096, "Period was not the sampling interval");
+		assert_eq!(strings[varint_field(&profile, 14) as usize], "inuse_space", "Default sample type was wrong");
+	}
+
+	fn frame(instruction_pointer: usize, symbols: Vec<SamplingHeapProfileSymbol>) -> SamplingHeapProfileFrame
+	{
+		SamplingHeapProfileFrame
+		{
+			instruction_pointer,
+			symbols,
+		}
+	}
+
+	fn symbol(name: &str, system_name: &str, file_name: &str, line: u32) -> SamplingHeapProfileSymbol
+	{
+		SamplingHeapProfileSymbol
+		{
+			name: Some(name.to_string()),
+			system_name: Some(system_name.to_string()),
+			file_name: Some(file_name.to_string()),
+			line: Some(line),
+		}
+	}
+
+	fn call_stack(frames: Vec<SamplingHeapProfileFrame>, [estimated_allocated_objects, estimated_allocated_bytes, estimated_objects, estimated_bytes]: [u64; 4]) -> SamplingHeapProfileCallStack
+	{
+		SamplingHeapProfileCallStack
+		{
+			frames,
+			live_samples: 0,
+			sampled_bytes: 0,
+			estimated_bytes,
+			estimated_objects,
+			allocated_samples: 0,
+			allocated_sampled_bytes: 0,
+			estimated_allocated_bytes,
+			estimated_allocated_objects,
+		}
+	}
+
+	fn identifiers<'a>(identifiers: impl Iterator<Item=&'a u64>) -> Vec<u64>
+	{
+		let mut identifiers: Vec<u64> = identifiers.cloned().collect();
+		identifiers.sort();
+		identifiers
+	}
+
+	/// Decodes the fields of a protocol buffers message as `(field number, Left(varint) or Right(length delimited bytes))`.
+	fn fields(mut message: &[u8]) -> Vec<(u64, Either<u64, &[u8]>)>
+	{
+		let mut fields = Vec::new();
+		while !message.is_empty()
+		{
+			let key = varint(&mut message);
+			let field = match key & 0x07
+			{
+				PprofHeapProfileEncoder::VarintWireType => Left(varint(&mut message)),
+
+				PprofHeapProfileEncoder::LengthDelimitedWireType =>
+				{
+					let length = varint(&mut message) as usize;
+					let (bytes, remaining) = message.split_at(length);
+					message = remaining;
+					Right(bytes)
+				}
+
+				wire_type => panic!("Unexpected wire type `{}`", wire_type),
+			};
+			fields.push((key >> 3, field))
+		}
+		fields
+	}
+
+	fn varint_field(message: &[u8], field_number: u64) -> u64
+	{
+		fields(message).into_iter().find_map(|(number, field)| if number == field_number { field.left() } else { None }).expect("Missing varint field")
+	}
+
+	fn length_delimited_fields(message: &[u8], field_number: u64) -> Vec<&[u8]>
+	{
+		fields(message).into_iter().filter_map(|(number, field)| if number == field_number { field.right() } else { None }).collect()
+	}
+
+	fn varints(mut packed: &[u8]) -> Vec<u64>
+	{
+		let mut varints = Vec::new();
+		while !packed.is_empty()
+		{
+			varints.push(varint(&mut packed))
+		}
+		varints
+	}
+
+	fn varint(bytes: &mut &[u8]) -> u64
+	{
+		let mut value = 0;
+		let mut shift = 0;
+		loop
+		{
+			let byte = bytes[0];
+			*bytes = &bytes[1 .. ];
+			value |= ((byte & 0x7F) as u64) << shift;
+			if byte & 0x80 == 0
+			{
+				return value
+			}
+			shift += 7;
+		}
+	}
+}
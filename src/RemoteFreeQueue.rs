@@ -0,0 +1,440 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Head of a process-wide, append-only, singly-linked list of remote free queues.
+///
+/// Queues are allocated from `System`, like the `MemoryRangeRegistry`, and are never freed; a queue whose local allocator has been dropped is retired and later reused.
+static RemoteFreeQueueRegistry: AtomicPtr<RemoteFreeQueue> = AtomicPtr::new(null_mut());
+
+/// A lock-free, multiple-producer, single-consumer queue of deallocations of memory owned by a local allocator which is not in use by the deallocating thread.
+///
+/// For a thread-local allocator, other threads push deallocations; the owning thread drains them on its next allocation or on demand with `GlobalThreadAndCoroutineSwitchableAllocator::drain_remote_frees()`.
+/// For a coroutine local allocator, any thread, including its own whilst it is swapped out, pushes deallocations; they are drained when it is next swapped in.
+#[derive(Debug)]
+pub struct RemoteFreeQueue
+{
+	next_in_registry: *const RemoteFreeQueue,
+
+	state: AtomicUsize,
+
+	from: AtomicUsize,
+
+	to: AtomicUsize,
+
+	head: AtomicPtr<RemoteFree>,
+
+	pushers: AtomicUsize,
+}
+
+unsafe impl Send for RemoteFreeQueue
+{
+}
+
+unsafe impl Sync for RemoteFreeQueue
+{
+}
+
+impl RemoteFreeQueue
+{
+	const Vacant: usize = 0;
+
+	const Claiming: usize = 1;
+
+	const Live: usize = 2;
+
+	const Retired: usize = 3;
+
+	/// Registers the memory range of a thread-local or coroutine local allocator.
+	///
+	/// Panics if a new queue is needed and can not be allocated.
+	#[inline(always)]
+	pub(crate) fn register(memory_range: MemoryRange) -> &'static Self
+	{
+		let this = Self::claim();
+		this.from.store(memory_range.from.to_usize(), Relaxed);
+		this.to.store(memory_range.to.to_usize(), Relaxed);
+		this.drain(|remote_free| Self::not_owned(remote_free.current_memory));
+		this.state.store(Self::Live, Release);
+		this
+	}
+
+	/// Retires this queue then drains any outstanding remote frees.
+	///
+	/// Waits for any pushes which started before this queue was retired to finish, so that no remote free is left on a retired queue.
+	#[inline(always)]
+	pub(crate) fn retire(&self, local_allocator: &impl Allocator) -> usize
+	{
+		self.retire_without_draining();
+		self.drain_into(local_allocator)
+	}
+
+	/// Retires this queue then discards any outstanding remote frees, as the memory of its local allocator is about to be released wholesale.
+	#[inline(always)]
+	pub(crate) fn retire_discarding_remote_frees(&self) -> usize
+	{
+		self.retire_without_draining();
+		self.drain(|_remote_free| {})
+	}
+
+	/// Is the thread-local allocator owning this queue still live?
+	#[inline(always)]
+	pub fn is_live(&self) -> bool
+	{
+		self.state.load(Acquire) == Self::Live
+	}
+
+	/// Are there any remote frees waiting to be drained?
+	#[inline(always)]
+	pub fn has_remote_frees(&self) -> bool
+	{
+		self.head.load(Relaxed).is_not_null()
+	}
+
+	/// Queues a deallocation made by a thread which does not own `current_memory`.
+	///
+	/// Returns `false`, without queueing, if this queue is no longer live, as its thread-local allocator has been or is being dropped.
+	///
+	/// If `current_memory` is too small or misaligned to hold the queue's book-keeping, book-keeping is allocated from `System`; if that fails, `current_memory` is leaked.
+	#[inline(always)]
+	pub(crate) fn push(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: MemoryAddress) -> bool
+	{
+		self.pushers.fetch_add(1, SeqCst);
+		let is_live = self.state.load(SeqCst) == Self::Live;
+		if likely!(is_live)
+		{
+			self.push_whilst_live(non_zero_size, non_zero_power_of_two_alignment, current_memory)
+		}
+		self.pushers.fetch_sub(1, Release);
+		is_live
+	}
+
+	#[inline(always)]
+	fn push_whilst_live(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: MemoryAddress)
+	{
+		let remote_free = if likely!(RemoteFree::fits_in(non_zero_size, current_memory))
+		{
+			current_memory.cast::<RemoteFree>()
+		}
+		else
+		{
+			let book_keeping = unsafe { System.alloc(RemoteFree::layout()) } as *mut RemoteFree;
+			if unlikely!(book_keeping.is_null())
+			{
+				return
+			}
+			book_keeping.non_null()
+		};
+
+		unsafe
+		{
+			remote_free.as_ptr().write
+			(
+				RemoteFree
+				{
+					next: null_mut(),
+					current_memory,
+					non_zero_size,
+					non_zero_power_of_two_alignment,
+				}
+			)
+		};
+
+		let mut head = self.head.load(Relaxed);
+		loop
+		{
+			remote_free.mutable_reference().next = head;
+			match self.head.compare_exchange_weak(head, remote_free.as_ptr(), Release, Relaxed)
+			{
+				Ok(_) => return,
+
+				Err(was) => head = was,
+			}
+		}
+	}
+
+	/// Deallocates all queued remote frees into `local_allocator`.
+	///
+	/// Returns the number of remote frees drained.
+	#[inline(always)]
+	pub(crate) fn drain_into(&self, local_allocator: &impl Allocator) -> usize
+	{
+		let memory_range = MemoryRange::new(MemoryAddress::from_usize(self.from.load(Relaxed)), MemoryAddress::from_usize(self.to.load(Relaxed)));
+
+		self.drain(|remote_free|
+		{
+			let current_memory = remote_free.current_memory;
+			if likely!(memory_range.contains(current_memory))
+			{
+				local_allocator.deallocate(remote_free.non_zero_size, remote_free.non_zero_power_of_two_alignment, current_memory)
+			}
+			else
+			{
+				Self::not_owned(current_memory)
+			}
+		})
+	}
+
+	/// A remote free of memory not owned by this queue's local allocator is a bug in the deallocating code; it aborts in debug builds and is leaked in release builds.
+	#[inline(always)]
+	fn not_owned(current_memory: MemoryAddress)
+	{
+		if cfg!(debug_assertions)
+		{
+			MemoryRangeRegistry::memory_belongs_to_no_known_allocator(current_memory)
+		}
+	}
+
+	#[inline(always)]
+	fn retire_without_draining(&self)
+	{
+		self.state.store(Self::Retired, SeqCst);
+		while self.pushers.load(SeqCst) != 0
+		{
+			spin_loop()
+		}
+	}
+
+	#[inline(always)]
+	fn drain(&self, mut deallocate: impl FnMut(RemoteFree)) -> usize
+	{
+		let mut count = 0;
+		let mut remote_free_pointer = self.head.swap(null_mut(), Acquire);
+		while remote_free_pointer.is_not_null()
+		{
+			let book_keeping = remote_free_pointer.non_null().cast::<u8>();
+			let remote_free = unsafe { remote_free_pointer.read() };
+			remote_free_pointer = remote_free.next;
+
+			let book_keeping_was_allocated = book_keeping != remote_free.current_memory;
+			if unlikely!(book_keeping_was_allocated)
+			{
+				unsafe { System.dealloc(book_keeping.as_ptr(), RemoteFree::layout()) }
+			}
+
+			deallocate(remote_free);
+			count += 1;
+		}
+		count
+	}
+
+	#[inline(always)]
+	fn claim() -> &'static Self
+	{
+		let mut remote_free_queue = RemoteFreeQueueRegistry.load(Acquire) as *const Self;
+		while remote_free_queue.is_not_null()
+		{
+			let this = remote_free_queue.reference();
+			for vacant_or_retired in [Self::Vacant, Self::Retired].iter()
+			{
+				if this.state.compare_exchange(*vacant_or_retired, Self::Claiming, AcqRel, Relaxed).is_ok()
+				{
+					return this
+				}
+			}
+			remote_free_queue = this.next_in_registry;
+		}
+
+		let this = unsafe { System.alloc(Layout::new::<Self>()) } as *mut Self;
+		assert!(this.is_not_null(), "Could not allocate a remote free queue");
+		let this = this.non_null();
+		let mut head = RemoteFreeQueueRegistry.load(Acquire);
+		loop
+		{
+			unsafe
+			{
+				this.as_ptr().write
+				(
+					Self
+					{
+						next_in_registry: head,
+						state: AtomicUsize::new(Self::Claiming),
+						from: AtomicUsize::new(0),
+						to: AtomicUsize::new(0),
+						head: AtomicPtr::new(null_mut()),
+						pushers: AtomicUsize::new(0),
+					}
+				)
+			};
+
+			match RemoteFreeQueueRegistry.compare_exchange_weak(head, this.as_ptr(), AcqRel, Acquire)
+			{
+				Ok(_) => return this.reference(),
+
+				Err(was) => head = was,
+			}
+		}
+	}
+}
+
+#[derive(Debug)]
+struct RemoteFree
+{
+	next: *mut RemoteFree,
+	current_memory: MemoryAddress,
+	non_zero_size: NonZeroUsize,
+	non_zero_power_of_two_alignment: NonZeroUsize,
+}
+
+impl RemoteFree
+{
+	const Size: NonZeroUsize = new_non_zero_usize(size_of::<Self>());
+
+	const Alignment: NonZeroUsize = new_non_zero_usize(align_of::<Self>());
+
+	#[inline(always)]
+	fn layout() -> Layout
+	{
+		Layout::new::<Self>()
+	}
+
+	#[inline(always)]
+	fn fits_in(non_zero_size: NonZeroUsize, current_memory: MemoryAddress) -> bool
+	{
+		non_zero_size >= Self::Size && current_memory.is_aligned_to(Self::Alignment)
+	}
+}
+
+#[cfg(test)]
+mod RemoteFreeQueueTests
+{
+	use super::*;
+	use std::sync::Arc;
+	use std::sync::Barrier;
+	use std::thread::spawn;
+
+	#[test]
+	pub fn remote_frees_are_drained_into_the_owning_local_allocator()
+	{
+		let memory_range = new_memory_range();
+		let remote_free_queue = RemoteFreeQueue::register(memory_range);
+
+		let first = memory_range.from.to_usize();
+		let second = first + 32;
+		spawn(move ||
+		{
+			assert!(remote_free_queue.push(32.non_zero(), 8.non_zero(), MemoryAddress::from_usize(first)), "Did not queue remote free");
+			assert!(remote_free_queue.push(32.non_zero(), 8.non_zero(), MemoryAddress::from_usize(second)), "Did not queue remote free");
+		}).join().expect("Remote thread panicked");
+		assert!(remote_free_queue.has_remote_frees(), "Did not have remote frees");
+
+		let local_allocator = DeallocationCountingAllocator::default();
+		assert_eq!(remote_free_queue.retire(&local_allocator), 2, "Did not drain all remote frees");
+		assert_eq!(local_allocator.deallocated.get(), 64, "Did not deallocate all remote frees into the local allocator");
+		assert!(!remote_free_queue.has_remote_frees(), "Remote frees were left on a retired queue");
+	}
+
+	#[test]
+	pub fn remote_frees_too_small_for_book_keeping_are_drained_into_the_owning_local_allocator()
+	{
+		let memory_range = new_memory_range();
+		let remote_free_queue = RemoteFreeQueue::register(memory_range);
+
+		let first = memory_range.from.to_usize() + 1;
+		spawn(move || assert!(remote_free_queue.push(1.non_zero(), 1.non_zero(), MemoryAddress::from_usize(first)), "Did not queue remote free")).join().expect("Remote thread panicked");
+
+		let local_allocator = DeallocationCountingAllocator::default();
+		assert_eq!(remote_free_queue.drain_into(&local_allocator), 1, "Did not drain remote free");
+		assert_eq!(local_allocator.deallocated.get(), 1, "Did not deallocate remote free into the local allocator");
+
+		remote_free_queue.retire(&local_allocator);
+	}
+
+	#[test]
+	pub fn remote_frees_are_refused_once_retired()
+	{
+		let memory_range = new_memory_range();
+		let remote_free_queue = RemoteFreeQueue::register(memory_range);
+
+		let local_allocator = DeallocationCountingAllocator::default();
+		remote_free_queue.retire(&local_allocator);
+
+		assert!(!remote_free_queue.is_live(), "Retired queue was live");
+		assert!(!remote_free_queue.push(32.non_zero(), 8.non_zero(), memory_range.from), "Queued remote free on a retired queue");
+		assert!(!remote_free_queue.has_remote_frees(), "Remote frees were left on a retired queue");
+	}
+
+	#[test]
+	pub fn remote_frees_pushed_by_many_threads_whilst_retiring_are_all_drained()
+	{
+		const Producers: usize = 4;
+		const PushesPerProducer: usize = 1024;
+		const SlotSize: usize = 32;
+
+		let buffer: &'static mut [u64] = Box::leak(vec![0; Producers * PushesPerProducer * SlotSize / size_of::<u64>()].into_boxed_slice());
+		let from = NonNull::from(buffer).cast::<u8>();
+		let memory_range = MemoryRange::new(from, from.add(Producers * PushesPerProducer * SlotSize));
+		let remote_free_queue = RemoteFreeQueue::register(memory_range);
+
+		let started = Arc::new(Barrier::new(Producers + 1));
+		let producers: Vec<_> = (0 .. Producers).map(|producer|
+		{
+			let started = started.clone();
+			let first = memory_range.from.to_usize() + producer * PushesPerProducer * SlotSize;
+			spawn(move ||
+			{
+				started.wait();
+				let mut queued = 0;
+				while queued < PushesPerProducer && remote_free_queue.push(SlotSize.non_zero(), 8.non_zero(), MemoryAddress::from_usize(first + queued * SlotSize))
+				{
+					queued += 1
+				}
+				queued
+			})
+		}).collect();
+
+		started.wait();
+		let local_allocator = DeallocationCountingAllocator::default();
+		let mut drained = 0;
+		for _ in 0 .. 16
+		{
+			drained += remote_free_queue.drain_into(&local_allocator)
+		}
+		drained += remote_free_queue.retire(&local_allocator);
+
+		let queued: usize = producers.into_iter().map(|producer| producer.join().expect("Remote thread panicked")).sum();
+		assert_eq!(drained, queued, "Did not drain every remote free that was queued");
+		assert_eq!(local_allocator.deallocated.get(), queued * SlotSize, "Did not deallocate every remote free into the local allocator");
+		assert!(!remote_free_queue.has_remote_frees(), "Remote frees were left on a retired queue")
+	}
+
+	fn new_memory_range() -> MemoryRange
+	{
+		let buffer: &'static mut [u64; 8] = Box::leak(Box::new([0; 8]));
+		let from = NonNull::from(buffer).cast::<u8>();
+		MemoryRange::new(from, from.add(size_of::<[u64; 8]>()))
+	}
+
+	#[derive(Debug, Default)]
+	struct DeallocationCountingAllocator
+	{
+		deallocated: Cell<usize>,
+	}
+
+	impl Allocator for DeallocationCountingAllocator
+	{
+		#[inline(always)]
+		fn allocate(&self, _non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			Err(AllocError)
+		}
+
+		#[inline(always)]
+		fn deallocate(&self, non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize, _current_memory: NonNull<u8>)
+		{
+			self.deallocated.set(self.deallocated.get() + non_zero_size.get())
+		}
+
+		#[inline(always)]
+		fn growing_reallocate(&self, _non_zero_new_size: NonZeroUsize, _non_zero_power_of_two_new_alignment: NonZeroUsize, _non_zero_current_size: NonZeroUsize, _non_zero_power_of_two_current_alignment: NonZeroUsize, _current_memory: NonNull<u8>, _current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			Err(AllocError)
+		}
+
+		#[inline(always)]
+		fn shrinking_reallocate(&self, _non_zero_new_size: NonZeroUsize, _non_zero_power_of_two_new_alignment: NonZeroUsize, _non_zero_current_size: NonZeroUsize, _non_zero_power_of_two_current_alignment: NonZeroUsize, _current_memory: NonNull<u8>, _current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			Err(AllocError)
+		}
+	}
+}
@@ -0,0 +1,93 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Sampled memory allocated from one call stack.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SamplingHeapProfileCallStack
+{
+	/// Frames of the call stack, innermost first.
+	pub frames: Vec<SamplingHeapProfileFrame>,
+
+	/// Number of live sampled allocations.
+	pub live_samples: u64,
+
+	/// Bytes of live sampled allocations.
+	pub sampled_bytes: u64,
+
+	/// Estimated live bytes allocated from this call stack; each sample represents at least a sampling interval's worth of bytes.
+	pub estimated_bytes: u64,
+
+	/// Estimated number of live allocations from this call stack.
+	pub estimated_objects: u64,
+
+	/// Number of sampled allocations, live or deallocated, since the profiler was created.
+	pub allocated_samples: u64,
+
+	/// Bytes of sampled allocations, live or deallocated, since the profiler was created.
+	pub allocated_sampled_bytes: u64,
+
+	/// Estimated bytes allocated from this call stack, live or deallocated, since the profiler was created.
+	pub estimated_allocated_bytes: u64,
+
+	/// Estimated number of allocations from this call stack, live or deallocated, since the profiler was created.
+	pub estimated_allocated_objects: u64,
+}
+
+impl SamplingHeapProfileCallStack
+{
+	#[inline(always)]
+	fn new(frames: Vec<SamplingHeapProfileFrame>) -> Self
+	{
+		Self
+		{
+			frames,
+			live_samples: 0,
+			sampled_bytes: 0,
+			estimated_bytes: 0,
+			estimated_objects: 0,
+			allocated_samples: 0,
+			allocated_sampled_bytes: 0,
+			estimated_allocated_bytes: 0,
+			estimated_allocated_objects: 0,
+		}
+	}
+
+	/// Call stacks are keyed by the instruction pointers of their frames; symbols are only resolved, which is slow, for a new call stack.
+	#[inline(always)]
+	fn entry<'a>(call_stacks: &'a mut HashMap<Vec<usize>, Self>, backtrace: &Backtrace) -> &'a mut Self
+	{
+		let instruction_pointers: Vec<usize> = backtrace.frames().iter().map(|frame| frame.ip() as usize).collect();
+		call_stacks.entry(instruction_pointers).or_insert_with_key(|instruction_pointers| Self::new(instruction_pointers.iter().map(|&instruction_pointer| SamplingHeapProfileFrame::resolve(instruction_pointer)).collect()))
+	}
+
+	#[inline(always)]
+	fn record_live_sample(&mut self, size: usize, sampling_interval: u64)
+	{
+		let (estimated_bytes, estimated_objects) = Self::estimate(size, sampling_interval);
+		self.live_samples += 1;
+		self.sampled_bytes += size as u64;
+		self.estimated_bytes += estimated_bytes;
+		self.estimated_objects += estimated_objects;
+		self.record_allocated_sample(size, sampling_interval)
+	}
+
+	#[inline(always)]
+	fn record_allocated_sample(&mut self, size: usize, sampling_interval: u64)
+	{
+		let (estimated_bytes, estimated_objects) = Self::estimate(size, sampling_interval);
+		self.allocated_samples += 1;
+		self.allocated_sampled_bytes += size as u64;
+		self.estimated_allocated_bytes += estimated_bytes;
+		self.estimated_allocated_objects += estimated_objects;
+	}
+
+	/// A sample represents at least a sampling interval's worth of bytes, and so, for small allocations, several allocations of the same size.
+	#[inline(always)]
+	fn estimate(size: usize, sampling_interval: u64) -> (u64, u64)
+	{
+		let size = max(size as u64, 1);
+		let estimated_bytes = max(size, sampling_interval);
+		(estimated_bytes, estimated_bytes / size)
+	}
+}
@@ -0,0 +1,31 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A frame of the call stack of a `SamplingHeapProfileCallStack`, innermost first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SamplingHeapProfileFrame
+{
+	/// Instruction pointer.
+	pub instruction_pointer: usize,
+
+	/// Symbols the instruction pointer resolved to; more than one if functions were inlined, innermost first, in which case the last is the function inlined into; empty if unresolved.
+	pub symbols: Vec<SamplingHeapProfileSymbol>,
+}
+
+impl SamplingHeapProfileFrame
+{
+	/// Resolves symbols, so is slow.
+	#[inline(always)]
+	fn resolve(instruction_pointer: usize) -> Self
+	{
+		let mut symbols = Vec::new();
+		resolve(instruction_pointer as *mut c_void, |symbol| symbols.push(SamplingHeapProfileSymbol::new(symbol)));
+
+		Self
+		{
+			instruction_pointer,
+			symbols,
+		}
+	}
+}
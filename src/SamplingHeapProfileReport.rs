@@ -0,0 +1,67 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A report of sampled memory grouped by call stack.
+///
+/// Includes call stacks whose sampled allocations have all been deallocated.
+///
+/// Obtained using `SamplingHeapProfiler::report()`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SamplingHeapProfileReport
+{
+	/// Sampling interval in bytes.
+	pub sampling_interval: u64,
+
+	/// Call stacks, in descending order of estimated bytes.
+	pub call_stacks: Vec<SamplingHeapProfileCallStack>,
+}
+
+impl SamplingHeapProfileReport
+{
+	/// Total estimated live bytes.
+	#[inline(always)]
+	pub fn estimated_bytes(&self) -> u64
+	{
+		self.call_stacks.iter().map(|call_stack| call_stack.estimated_bytes).sum()
+	}
+
+	/// Writes this report as a gzip-compressed pprof heap profile (`profile.proto`), with the sample types `alloc_objects`, `alloc_space`, `inuse_objects` and `inuse_space`.
+	///
+	/// Allocates; call whilst the global allocator is in use.
+	#[inline(always)]
+	pub fn write_pprof(&self, writer: impl Write) -> io::Result<()>
+	{
+		let mut gzip_encoder = GzEncoder::new(writer, Compression::default());
+		gzip_encoder.write_all(&PprofHeapProfileEncoder::encode(self))?;
+		gzip_encoder.finish()?.flush()
+	}
+
+	/// Writes this report as a gzip-compressed pprof heap profile to a file, creating or truncating it.
+	///
+	/// Allocates; call whilst the global allocator is in use.
+	#[inline(always)]
+	pub fn write_pprof_file(&self, path: impl AsRef<Path>) -> io::Result<()>
+	{
+		self.write_pprof(BufWriter::new(File::create(path)?))
+	}
+
+	#[inline(always)]
+	fn new<'a>(sampling_interval: u64, live_samples: impl Iterator<Item=(usize, &'a Backtrace)>, deallocated_call_stacks: &HashMap<Vec<usize>, SamplingHeapProfileCallStack>) -> Self
+	{
+		let mut call_stacks = deallocated_call_stacks.clone();
+		for (size, backtrace) in live_samples
+		{
+			SamplingHeapProfileCallStack::entry(&mut call_stacks, backtrace).record_live_sample(size, sampling_interval)
+		}
+
+		let mut call_stacks: Vec<SamplingHeapProfileCallStack> = call_stacks.into_iter().map(|(_, call_stack)| call_stack).collect();
+		call_stacks.sort_by(|left, right| right.estimated_bytes.cmp(&left.estimated_bytes));
+
+		Self
+		{
+			sampling_interval,
+			call_stacks,
+		}
+	}
+}
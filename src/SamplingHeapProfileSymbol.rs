@@ -0,0 +1,36 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A symbol of a `SamplingHeapProfileFrame`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SamplingHeapProfileSymbol
+{
+	/// Demangled function name without its hash, if known.
+	pub name: Option<String>,
+
+	/// Function name as it appears in the binary, usually mangled, if known.
+	pub system_name: Option<String>,
+
+	/// Source file name, if known.
+	pub file_name: Option<String>,
+
+	/// Source line number, if known.
+	pub line: Option<u32>,
+}
+
+impl SamplingHeapProfileSymbol
+{
+	#[inline(always)]
+	fn new(symbol: &Symbol) -> Self
+	{
+		let symbol_name = symbol.name();
+		Self
+		{
+			name: symbol_name.as_ref().map(|symbol_name| format!("{:#}", symbol_name)),
+			system_name: symbol_name.as_ref().map(|symbol_name| String::from_utf8_lossy(symbol_name.as_bytes()).into_owned()),
+			file_name: symbol.filename().map(|file_name| file_name.to_string_lossy().into_owned()),
+			line: symbol.lineno(),
+		}
+	}
+}
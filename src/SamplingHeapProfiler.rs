@@ -0,0 +1,412 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A function which runs a callback with the global allocator as the current allocator in use; `null` if not set.
+static GlobalTierRunner: AtomicPtr<()> = AtomicPtr::new(null_mut());
+
+/// Samples allocations, capturing a backtrace for roughly one in every `sampling_interval` bytes allocated, and tracks live sampled allocations until they are deallocated.
+///
+/// The bytes between samples are exponentially distributed with a mean of `sampling_interval`, so that periodic patterns of allocation do not bias which allocations are sampled.
+///
+/// When a sampled allocation is deallocated, it is folded into totals per call stack, so memory used does not grow with the number of samples deallocated; symbols are resolved once per distinct call stack.
+///
+/// Usually used by `SamplingHeapProfilerAllocator`.
+///
+/// Capturing a backtrace allocates; to stop this recursing into the sampled allocator, all of the profiler's own allocations and deallocations are made by a function which runs them on the global tier, registered once per process with `run_on_global_tier_using()`.
+/// Until one is registered, nothing is sampled.
+///
+/// If the profiler is re-entered whilst it is recording a sample (eg by a deallocation made by a `Drop` run during `report()`), the re-entrant allocation is not sampled or tracked.
+///
+/// This profiler is not thread-safe.
+pub struct SamplingHeapProfiler
+{
+	sampling_interval: NonZeroU64,
+
+	bytes_until_next_sample: Cell<u64>,
+
+	random_state: Cell<u64>,
+
+	is_sampling: Cell<bool>,
+
+	live_samples: RefCell<HashMap<usize, (usize, Backtrace)>>,
+
+	deallocated_call_stacks: RefCell<HashMap<Vec<usize>, SamplingHeapProfileCallStack>>,
+}
+
+impl Debug for SamplingHeapProfiler
+{
+	#[inline(always)]
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result
+	{
+		write!(f, "SamplingHeapProfiler(sampling_interval: {:?}, live_samples: {:?})", self.sampling_interval, self.live_samples.try_borrow().map(|live_samples| live_samples.len()).ok())
+	}
+}
+
+impl Drop for SamplingHeapProfiler
+{
+	#[inline(always)]
+	fn drop(&mut self)
+	{
+		let live_samples = self.live_samples.get_mut();
+		let deallocated_call_stacks = self.deallocated_call_stacks.get_mut();
+		Self::on_global_tier(||
+		{
+			drop(take(live_samples));
+			drop(take(deallocated_call_stacks));
+		});
+	}
+}
+
+impl SamplingHeapProfiler
+{
+	/// Default sampling interval of 512Kb.
+	pub const DefaultSamplingInterval: NonZeroU64 = unsafe { NonZeroU64::new_unchecked(512 * 1024) };
+
+	/// Registers a function which runs a callback with the global allocator as the current allocator in use, eg:-
+	///
+	/// ```ignore
+	/// fn run_on_global_tier(callback: &mut dyn FnMut())
+	/// {
+	/// 	let _guard = GLOBAL.with_global();
+	/// 	callback()
+	/// }
+	///
+	/// SamplingHeapProfiler::run_on_global_tier_using(run_on_global_tier);
+	/// ```
+	#[inline(always)]
+	pub fn run_on_global_tier_using(run_on_global_tier: fn(&mut dyn FnMut()))
+	{
+		GlobalTierRunner.store(run_on_global_tier as *mut (), Release)
+	}
+
+	/// New instance.
+	///
+	/// Does not allocate.
+	#[inline(always)]
+	pub fn new(sampling_interval: NonZeroU64) -> Self
+	{
+		let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|since_epoch| since_epoch.as_nanos() as u64).unwrap_or(0) ^ sampling_interval.get();
+
+		let this = Self
+		{
+			sampling_interval,
+			bytes_until_next_sample: Cell::new(0),
+			random_state: Cell::new(seed | 1),
+			is_sampling: Cell::new(false),
+			live_samples: RefCell::new(HashMap::new()),
+			deallocated_call_stacks: RefCell::new(HashMap::new()),
+		};
+		this.bytes_until_next_sample.set(this.next_sample_interval());
+		this
+	}
+
+	/// Sampling interval in bytes.
+	#[inline(always)]
+	pub fn sampling_interval(&self) -> NonZeroU64
+	{
+		self.sampling_interval
+	}
+
+	/// Produces a report of sampled memory grouped by call stack, largest live first.
+	///
+	/// Resolves symbols, so is slow; the report is allocated from the global allocator.
+	///
+	/// Returns `None` if no function to run on the global tier has been registered, or if called re-entrantly whilst recording a sample.
+	#[inline(always)]
+	pub fn report(&self) -> Option<SamplingHeapProfileReport>
+	{
+		let (live_samples, deallocated_call_stacks) = match (self.live_samples.try_borrow(), self.deallocated_call_stacks.try_borrow())
+		{
+			(Ok(live_samples), Ok(deallocated_call_stacks)) => (live_samples, deallocated_call_stacks),
+
+			_ => return None,
+		};
+		let sampling_interval = self.sampling_interval.get();
+
+		let mut report = None;
+		let ran = Self::on_global_tier(||
+		{
+			self.is_sampling.set(true);
+			report = Some(SamplingHeapProfileReport::new(sampling_interval, live_samples.values().map(|&(size, ref backtrace)| (size, backtrace)), &deallocated_call_stacks));
+			self.is_sampling.set(false);
+		});
+
+		if ran
+		{
+			report
+		}
+		else
+		{
+			None
+		}
+	}
+
+	/// Produces a report and writes it as a gzip-compressed pprof heap profile to a file, creating or truncating it.
+	///
+	/// Returns an error if no function to run on the global tier has been registered.
+	#[inline(always)]
+	pub fn write_pprof_file(&self, path: impl AsRef<Path>) -> io::Result<()>
+	{
+		let report = self.report().ok_or_else(|| io::Error::new(io::ErrorKind::Other, "No function to run on the global tier has been registered"))?;
+
+		let mut report = Some(report);
+		let mut result = Ok(());
+		Self::on_global_tier(||
+		{
+			let report = report.take().unwrap();
+			result = report.write_pprof_file(path.as_ref());
+		});
+		result
+	}
+
+	#[inline(always)]
+	pub(crate) fn allocated(&self, memory_address: MemoryAddress, size: usize)
+	{
+		self.charge(memory_address, size, size)
+	}
+
+	#[inline(always)]
+	pub(crate) fn deallocated(&self, memory_address: MemoryAddress)
+	{
+		let sample =
+		{
+			let mut live_samples = match self.live_samples.try_borrow_mut()
+			{
+				Ok(live_samples) => live_samples,
+
+				Err(_) => return,
+			};
+
+			if likely!(live_samples.is_empty())
+			{
+				return
+			}
+
+			live_samples.remove(&memory_address.to_usize())
+		};
+
+		if let Some(sample) = sample
+		{
+			self.fold_deallocated_sample(sample)
+		}
+	}
+
+	#[inline(always)]
+	pub(crate) fn reallocated(&self, current_memory: MemoryAddress, current_size: usize, new_memory: MemoryAddress, new_size: usize)
+	{
+		let mut live_samples = match self.live_samples.try_borrow_mut()
+		{
+			Ok(live_samples) => live_samples,
+
+			Err(_) => return,
+		};
+
+		if likely!(live_samples.is_empty())
+		{
+			drop(live_samples);
+			if new_size > current_size
+			{
+				self.charge(new_memory, new_size - current_size, new_size)
+			}
+			return
+		}
+
+		match live_samples.remove(&current_memory.to_usize())
+		{
+			Some((_, backtrace)) =>
+			{
+				let mut backtrace = Some(backtrace);
+				Self::on_global_tier(|| { live_samples.insert(new_memory.to_usize(), (new_size, backtrace.take().unwrap())); });
+			}
+
+			None =>
+			{
+				drop(live_samples);
+				if new_size > current_size
+				{
+					self.charge(new_memory, new_size - current_size, new_size)
+				}
+			}
+		}
+	}
+
+	/// Charges `charged_size` bytes against the sampling interval; if a sample is taken, it records all `size` bytes of the memory.
+	#[inline(always)]
+	fn charge(&self, memory_address: MemoryAddress, charged_size: usize, size: usize)
+	{
+		let bytes_until_next_sample = self.bytes_until_next_sample.get();
+		let charged_size = charged_size as u64;
+		if likely!(charged_size < bytes_until_next_sample)
+		{
+			self.bytes_until_next_sample.set(bytes_until_next_sample - charged_size);
+			return
+		}
+		self.bytes_until_next_sample.set(self.next_sample_interval());
+		self.sample(memory_address, size)
+	}
+
+	/// Exponentially distributed with a mean of the sampling interval; uses a xorshift pseudo-random number generator, so does not allocate.
+	#[inline(always)]
+	fn next_sample_interval(&self) -> u64
+	{
+		let mut random_state = self.random_state.get();
+		random_state ^= random_state << 13;
+		random_state ^= random_state >> 7;
+		random_state ^= random_state << 17;
+		self.random_state.set(random_state);
+
+		const TwoToThePower53: f64 = (1u64 << 53) as f64;
+		let uniform_excluding_zero = ((random_state >> 11) as f64 + 1.0) / TwoToThePower53;
+		(-uniform_excluding_zero.ln() * (self.sampling_interval.get() as f64)) as u64
+	}
+
+	#[cold]
+	#[inline(never)]
+	fn sample(&self, memory_address: MemoryAddress, size: usize)
+	{
+		if unlikely!(self.is_sampling.get())
+		{
+			return
+		}
+		let mut live_samples = match self.live_samples.try_borrow_mut()
+		{
+			Ok(live_samples) => live_samples,
+
+			Err(_) => return,
+		};
+		self.is_sampling.set(true);
+
+		Self::on_global_tier(||
+		{
+			let backtrace = Backtrace::new_unresolved();
+			live_samples.insert(memory_address.to_usize(), (size, backtrace));
+		});
+
+		self.is_sampling.set(false);
+	}
+
+	/// Resolves symbols if the call stack is new, so may be slow.
+	#[cold]
+	#[inline(never)]
+	fn fold_deallocated_sample(&self, sample: (usize, Backtrace))
+	{
+		let sampling_interval = self.sampling_interval.get();
+
+		let was_sampling = self.is_sampling.replace(true);
+		let mut sample = Some(sample);
+		Self::on_global_tier(||
+		{
+			let (size, backtrace) = sample.take().unwrap();
+			if let Ok(mut deallocated_call_stacks) = self.deallocated_call_stacks.try_borrow_mut()
+			{
+				SamplingHeapProfileCallStack::entry(&mut deallocated_call_stacks, &backtrace).record_allocated_sample(size, sampling_interval)
+			}
+		});
+		self.is_sampling.set(was_sampling);
+	}
+
+	/// Returns `false` if no function to run on the global tier has been registered.
+	#[inline(always)]
+	fn on_global_tier(mut callback: impl FnMut()) -> bool
+	{
+		let global_tier_runner = GlobalTierRunner.load(Acquire);
+		if unlikely!(global_tier_runner.is_null())
+		{
+			return false
+		}
+		let global_tier_runner: fn(&mut dyn FnMut()) = unsafe { transmute(global_tier_runner) };
+		global_tier_runner(&mut callback);
+		true
+	}
+}
+
+#[cfg(test)]
+mod SamplingHeapProfilerTests
+{
+	use super::*;
+
+	#[test]
+	pub fn sample_intervals_are_exponentially_distributed()
+	{
+		const Samples: u64 = 10_000;
+		const SamplingInterval: u64 = 1000;
+
+		let sampling_heap_profiler = SamplingHeapProfiler::new(NonZeroU64::new(SamplingInterval).unwrap());
+		let intervals = (0 .. Samples).map(|_| sampling_heap_profiler.next_sample_interval()).collect::<Vec<_>>();
+
+		let mean = intervals.iter().sum::<u64>() / Samples;
+		assert!(mean > SamplingInterval * 9 / 10 && mean < SamplingInterval * 11 / 10, "Mean interval `{}` was not close to the sampling interval", mean);
+
+		let shorter_than_mean = intervals.iter().filter(|&&interval| interval < SamplingInterval).count() as u64;
+		assert!(shorter_than_mean > Samples * 58 / 100 && shorter_than_mean < Samples * 68 / 100, "`{}` intervals were shorter than the mean rather than about 1 - 1/e of them", shorter_than_mean);
+	}
+
+	#[test]
+	pub fn allocations_smaller_than_the_sampling_interval_are_rarely_sampled()
+	{
+		let sampling_heap_profiler = new_sampling_heap_profiler(1 << 40);
+
+		sampling_heap_profiler.allocated(memory_address(0x1000), 64);
+
+		assert_eq!(sampling_heap_profiler.report().expect("No report").call_stacks, vec![], "Sampled an allocation far smaller than the sampling interval");
+	}
+
+	#[test]
+	pub fn sampled_allocation_is_reported_live_until_deallocated()
+	{
+		let sampling_heap_profiler = new_sampling_heap_profiler(1);
+
+		sampling_heap_profiler.allocated(memory_address(0x1000), 4096);
+
+		let report = sampling_heap_profiler.report().expect("No report");
+		assert_eq!(report.call_stacks.len(), 1, "Did not sample");
+		let call_stack = &report.call_stacks[0];
+		assert_eq!((call_stack.live_samples, call_stack.sampled_bytes, call_stack.estimated_bytes, call_stack.allocated_samples), (1, 4096, 4096, 1), "Live sample was wrong");
+		assert!(call_stack.frames.iter().flat_map(|frame| frame.symbols.iter()).any(|symbol| symbol.name.as_ref().map(|name| name.ends_with("sampled_allocation_is_reported_live_until_deallocated")).unwrap_or(false)), "Call stack `{:?}` did not include the test", call_stack.frames);
+		assert_eq!(report.estimated_bytes(), 4096, "Estimated bytes were wrong");
+
+		sampling_heap_profiler.deallocated(memory_address(0x1000));
+
+		let report = sampling_heap_profiler.report().expect("No report");
+		assert_eq!(report.call_stacks.len(), 1, "Did not keep the call stack of a deallocated sample");
+		let call_stack = &report.call_stacks[0];
+		assert_eq!((call_stack.live_samples, call_stack.sampled_bytes, call_stack.estimated_bytes, call_stack.allocated_samples, call_stack.allocated_sampled_bytes), (0, 0, 0, 1, 4096), "Deallocated sample was wrong");
+		assert_eq!(report.estimated_bytes(), 0, "Deallocated sample was still live");
+	}
+
+	#[test]
+	pub fn reallocation_moves_a_sampled_allocation()
+	{
+		let sampling_heap_profiler = new_sampling_heap_profiler(1);
+
+		sampling_heap_profiler.allocated(memory_address(0x1000), 4096);
+		sampling_heap_profiler.reallocated(memory_address(0x1000), 4096, memory_address(0x2000), 8192);
+		sampling_heap_profiler.deallocated(memory_address(0x1000));
+
+		let report = sampling_heap_profiler.report().expect("No report");
+		assert_eq!(report.call_stacks.len(), 1, "Reallocation was sampled again");
+		assert_eq!((report.call_stacks[0].live_samples, report.call_stacks[0].sampled_bytes), (1, 8192), "Reallocation did not move the sample");
+
+		sampling_heap_profiler.deallocated(memory_address(0x2000));
+
+		assert_eq!(sampling_heap_profiler.report().expect("No report").estimated_bytes(), 0, "Moved sample was not deallocated");
+	}
+
+	fn new_sampling_heap_profiler(sampling_interval: u64) -> SamplingHeapProfiler
+	{
+		SamplingHeapProfiler::run_on_global_tier_using(run_on_global_tier);
+		SamplingHeapProfiler::new(NonZeroU64::new(sampling_interval).unwrap())
+	}
+
+	/// The profilers in these tests do not wrap the global allocator, so there is no recursion to prevent.
+	fn run_on_global_tier(callback: &mut dyn FnMut())
+	{
+		callback()
+	}
+
+	fn memory_address(address: usize) -> MemoryAddress
+	{
+		NonNull::new(address as *mut u8).unwrap()
+	}
+}
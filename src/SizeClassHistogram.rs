@@ -0,0 +1,140 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A histogram of requested sizes and alignments, bucketed by power of two.
+///
+/// Sizes are bucketed by rounding up to a power of two, so bucket `n` counts sizes in the range `2^(n-1) + 1 ..= 2^n`; alignments are always a power of two, so bucket `n` counts alignments of exactly `2^n`.
+///
+/// Usually recorded using `SizeClassHistogramRecordingAllocator`.
+///
+/// Not thread-safe; take a `snapshot()` on each thread and merge them.
+#[derive(Debug)]
+pub struct SizeClassHistogram
+{
+	sizes: Cell<[u64; SizeClassHistogram::Buckets]>,
+	
+	alignments: Cell<[u64; SizeClassHistogram::Buckets]>,
+}
+
+impl Default for SizeClassHistogram
+{
+	#[inline(always)]
+	fn default() -> Self
+	{
+		Self::new()
+	}
+}
+
+impl SizeClassHistogram
+{
+	/// Number of buckets; one for every power of two a `usize` can hold.
+	pub const Buckets: usize = size_of::<usize>() * 8;
+	
+	/// New instance.
+	#[inline(always)]
+	pub const fn new() -> Self
+	{
+		Self
+		{
+			sizes: Cell::new([0; Self::Buckets]),
+			alignments: Cell::new([0; Self::Buckets]),
+		}
+	}
+	
+	/// Records a requested size and alignment.
+	#[inline(always)]
+	pub fn record(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize)
+	{
+		Self::increment(&self.sizes, Self::size_class(non_zero_size));
+		Self::increment(&self.alignments, Self::alignment_class(non_zero_power_of_two_alignment));
+	}
+	
+	/// An immutable snapshot.
+	#[inline(always)]
+	pub fn snapshot(&self) -> SizeClassHistogramSnapshot
+	{
+		SizeClassHistogramSnapshot
+		{
+			sizes: self.sizes.get(),
+			alignments: self.alignments.get(),
+		}
+	}
+	
+	/// Resets all buckets to zero.
+	#[inline(always)]
+	pub fn reset(&self)
+	{
+		self.sizes.set([0; Self::Buckets]);
+		self.alignments.set([0; Self::Buckets]);
+	}
+	
+	/// Bucket index of a size, viz the logarithm base two of the size rounded up to a power of two.
+	///
+	/// Sizes too large to round up to a power of two a `usize` can hold are counted in the last bucket.
+	#[inline(always)]
+	pub fn size_class(non_zero_size: NonZeroUsize) -> usize
+	{
+		min(Self::Buckets - ((non_zero_size.get() - 1).leading_zeros() as usize), Self::Buckets - 1)
+	}
+	
+	/// Bucket index of an alignment, viz the logarithm base two of the alignment.
+	#[inline(always)]
+	pub fn alignment_class(non_zero_power_of_two_alignment: NonZeroUsize) -> usize
+	{
+		non_zero_power_of_two_alignment.logarithm_base2()
+	}
+	
+	#[inline(always)]
+	fn increment(buckets: &Cell<[u64; Self::Buckets]>, index: usize)
+	{
+		let buckets: &Cell<[u64]> = buckets;
+		let bucket = &buckets.as_slice_of_cells()[index];
+		bucket.set(bucket.get() + 1)
+	}
+}
+
+#[cfg(test)]
+mod SizeClassHistogramTests
+{
+	use super::*;
+
+	const LargestPowerOfTwo: usize = 1 << (SizeClassHistogram::Buckets - 1);
+
+	#[test]
+	pub fn sizes_are_bucketed_by_rounding_up_to_a_power_of_two()
+	{
+		for &(size, size_class) in &[(1, 0), (2, 1), (3, 2), (4, 2), (5, 3), (4096, 12), (4097, 13), (LargestPowerOfTwo, SizeClassHistogram::Buckets - 1), (LargestPowerOfTwo + 1, SizeClassHistogram::Buckets - 1), (usize::MAX, SizeClassHistogram::Buckets - 1)]
+		{
+			assert_eq!(SizeClassHistogram::size_class(size.non_zero()), size_class, "Wrong size class for size `{}`", size)
+		}
+	}
+
+	#[test]
+	pub fn alignments_are_bucketed_by_power_of_two()
+	{
+		for &(alignment, alignment_class) in &[(1, 0), (2, 1), (8, 3), (4096, 12), (LargestPowerOfTwo, SizeClassHistogram::Buckets - 1)]
+		{
+			assert_eq!(SizeClassHistogram::alignment_class(alignment.non_zero()), alignment_class, "Wrong alignment class for alignment `{}`", alignment)
+		}
+	}
+
+	#[test]
+	pub fn records_into_buckets_until_reset()
+	{
+		let histogram = SizeClassHistogram::new();
+		histogram.record(3.non_zero(), 8.non_zero());
+		histogram.record(4.non_zero(), 8.non_zero());
+		histogram.record(usize::MAX.non_zero(), 1.non_zero());
+
+		let snapshot = histogram.snapshot();
+		assert_eq!(snapshot.sizes[2], 2);
+		assert_eq!(snapshot.sizes[SizeClassHistogram::Buckets - 1], 1);
+		assert_eq!(snapshot.alignments[3], 2);
+		assert_eq!(snapshot.alignments[0], 1);
+		assert_eq!(snapshot.sizes.iter().sum::<u64>(), 3);
+
+		histogram.reset();
+		assert_eq!(histogram.snapshot(), SizeClassHistogramSnapshot::default(), "Did not reset")
+	}
+}
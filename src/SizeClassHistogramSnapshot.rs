@@ -0,0 +1,110 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// An immutable snapshot of `SizeClassHistogram`.
+///
+/// Add snapshots to merge them, eg across threads; subtract an earlier snapshot from a later one to find the change between them.
+/// Subtraction saturates at zero, so a histogram `reset()` between the two snapshots does not underflow.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SizeClassHistogramSnapshot
+{
+	/// Counts of requested sizes; bucket `n` counts sizes in the range `2^(n-1) + 1 ..= 2^n`.
+	pub sizes: [u64; SizeClassHistogram::Buckets],
+
+	/// Counts of requested alignments; bucket `n` counts alignments of exactly `2^n`.
+	pub alignments: [u64; SizeClassHistogram::Buckets],
+}
+
+impl Default for SizeClassHistogramSnapshot
+{
+	#[inline(always)]
+	fn default() -> Self
+	{
+		Self
+		{
+			sizes: [0; SizeClassHistogram::Buckets],
+			alignments: [0; SizeClassHistogram::Buckets],
+		}
+	}
+}
+
+impl Add for SizeClassHistogramSnapshot
+{
+	type Output = Self;
+
+	#[inline(always)]
+	fn add(mut self, rhs: Self) -> Self::Output
+	{
+		self += rhs;
+		self
+	}
+}
+
+impl AddAssign for SizeClassHistogramSnapshot
+{
+	#[inline(always)]
+	fn add_assign(&mut self, rhs: Self)
+	{
+		for index in 0 .. SizeClassHistogram::Buckets
+		{
+			self.sizes[index] += rhs.sizes[index];
+			self.alignments[index] += rhs.alignments[index];
+		}
+	}
+}
+
+impl Sub for SizeClassHistogramSnapshot
+{
+	type Output = Self;
+
+	#[inline(always)]
+	fn sub(mut self, rhs: Self) -> Self::Output
+	{
+		for index in 0 .. SizeClassHistogram::Buckets
+		{
+			self.sizes[index] = self.sizes[index].saturating_sub(rhs.sizes[index]);
+			self.alignments[index] = self.alignments[index].saturating_sub(rhs.alignments[index]);
+		}
+		self
+	}
+}
+
+impl SizeClassHistogramSnapshot
+{
+	/// Total number of recorded sizes (and alignments).
+	#[inline(always)]
+	pub fn total(&self) -> u64
+	{
+		self.sizes.iter().sum()
+	}
+
+	/// Non-empty size buckets as `(inclusive upper bound of size, count)`.
+	#[inline(always)]
+	pub fn size_classes(&self) -> impl Iterator<Item=(usize, u64)> + '_
+	{
+		Self::non_empty(&self.sizes)
+	}
+
+	/// Non-empty alignment buckets as `(alignment, count)`.
+	#[inline(always)]
+	pub fn alignment_classes(&self) -> impl Iterator<Item=(usize, u64)> + '_
+	{
+		Self::non_empty(&self.alignments)
+	}
+
+	/// The most frequently requested size class's inclusive upper bound, if any sizes have been recorded.
+	///
+	/// A reasonable starting point for a `block_size_hint`.
+	#[inline(always)]
+	pub fn most_frequent_size_class(&self) -> Option<usize>
+	{
+		self.size_classes().max_by_key(|&(_, count)| count).map(|(size_class, _)| size_class)
+	}
+
+	#[inline(always)]
+	fn non_empty(buckets: &[u64; SizeClassHistogram::Buckets]) -> impl Iterator<Item=(usize, u64)> + '_
+	{
+		buckets.iter().enumerate().filter(|&(_, &count)| count != 0).map(|(index, &count)| (1 << index, count))
+	}
+}
@@ -0,0 +1,108 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A type-erased reference to a `LocalAllocator` registered on a `LocalAllocatorStack`.
+///
+/// `LocalAllocator` and `Allocator` are not object-safe, so this holds a pointer to the local allocator and a hand-rolled table of functions monomorphized for it.
+///
+/// The memory range is captured when registered; local allocators NEVER grow or shrink their memory region.
+#[derive(Debug, Copy, Clone)]
+pub struct StackedLocalAllocator
+{
+	local_allocator: NonNull<()>,
+
+	memory_range: MemoryRange,
+
+	allocate: fn(NonNull<()>, NonZeroUsize, NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>,
+
+	deallocate: fn(NonNull<()>, NonZeroUsize, NonZeroUsize, NonNull<u8>),
+
+	growing_reallocate: fn(NonNull<()>, NonZeroUsize, NonZeroUsize, NonZeroUsize, NonZeroUsize, NonNull<u8>, bool) -> Result<(NonNull<u8>, usize), AllocError>,
+
+	shrinking_reallocate: fn(NonNull<()>, NonZeroUsize, NonZeroUsize, NonZeroUsize, NonZeroUsize, NonNull<u8>, bool) -> Result<(NonNull<u8>, usize), AllocError>,
+}
+
+impl Allocator for StackedLocalAllocator
+{
+	#[inline(always)]
+	fn allocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		(self.allocate)(self.local_allocator, non_zero_size, non_zero_power_of_two_alignment)
+	}
+
+	#[inline(always)]
+	fn deallocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		(self.deallocate)(self.local_allocator, non_zero_size, non_zero_power_of_two_alignment, current_memory)
+	}
+
+	#[inline(always)]
+	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		(self.growing_reallocate)(self.local_allocator, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+	}
+
+	#[inline(always)]
+	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		(self.shrinking_reallocate)(self.local_allocator, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+	}
+}
+
+impl StackedLocalAllocator
+{
+	/// The range of memory addresses that can be used to allocate memory by the stacked local allocator.
+	#[inline(always)]
+	pub fn memory_range(&self) -> MemoryRange
+	{
+		self.memory_range
+	}
+
+	/// Returns `true` if the stacked local allocator is responsible for an allocation starting with the given `from_memory_address`.
+	#[inline(always)]
+	pub fn contains(&self, from_memory_address: MemoryAddress) -> bool
+	{
+		self.memory_range.contains(from_memory_address)
+	}
+
+	#[inline(always)]
+	fn new<MS: MemorySource, LA: LocalAllocator<MS>>(local_allocator: &LA) -> Self
+	{
+		#[inline(always)]
+		fn reference<'a, LA>(local_allocator: NonNull<()>) -> &'a LA
+		{
+			local_allocator.cast::<LA>().reference()
+		}
+
+		fn allocate<MS: MemorySource, LA: LocalAllocator<MS>>(local_allocator: NonNull<()>, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			reference::<LA>(local_allocator).allocate(non_zero_size, non_zero_power_of_two_alignment)
+		}
+
+		fn deallocate<MS: MemorySource, LA: LocalAllocator<MS>>(local_allocator: NonNull<()>, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+		{
+			reference::<LA>(local_allocator).deallocate(non_zero_size, non_zero_power_of_two_alignment, current_memory)
+		}
+
+		fn growing_reallocate<MS: MemorySource, LA: LocalAllocator<MS>>(local_allocator: NonNull<()>, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			reference::<LA>(local_allocator).growing_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+		}
+
+		fn shrinking_reallocate<MS: MemorySource, LA: LocalAllocator<MS>>(local_allocator: NonNull<()>, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+		{
+			reference::<LA>(local_allocator).shrinking_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+		}
+
+		Self
+		{
+			local_allocator: NonNull::from(local_allocator).cast(),
+			memory_range: local_allocator.memory_range(),
+			allocate: allocate::<MS, LA>,
+			deallocate: deallocate::<MS, LA>,
+			growing_reallocate: growing_reallocate::<MS, LA>,
+			shrinking_reallocate: shrinking_reallocate::<MS, LA>,
+		}
+	}
+}
@@ -0,0 +1,77 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+thread_local!
+{
+	static ThreadExitTeardownOfThreadLocalAllocator: ThreadExitTeardown = ThreadExitTeardown(Cell::new(None));
+}
+
+/// Drops the thread-local allocator of a `GlobalThreadAndCoroutineSwitchableAllocator` when the current thread exits.
+///
+/// `#[thread_local]` statics never have their destructors run, so this uses a `thread_local!` static instead.
+/// The per-thread state is type-erased as `thread_local!` statics can not be generic; it, and not the global allocator, is held so that the global allocator need not be `'static`.
+struct ThreadExitTeardown(Cell<Option<RegisteredThreadExitTeardown>>);
+
+impl Drop for ThreadExitTeardown
+{
+	#[inline(always)]
+	fn drop(&mut self)
+	{
+		if let Some(registered_thread_exit_teardown) = self.0.take()
+		{
+			(registered_thread_exit_teardown.teardown)(registered_thread_exit_teardown.per_thread_state, registered_thread_exit_teardown.outstanding_memory_reporter)
+		}
+	}
+}
+
+impl ThreadExitTeardown
+{
+	/// Does nothing if the current thread is already exiting.
+	#[inline(always)]
+	fn register<CoroutineHeapSize: MemorySize, GTACSA: GlobalThreadAndCoroutineSwitchableAllocator<CoroutineHeapSize>>(global_allocator: &GTACSA, outstanding_memory_reporter: fn(u64))
+	{
+		fn teardown<CoroutineHeapSize: MemorySize, CoroutineLocalAllocator: LocalAllocator<CoroutineHeapMemorySource<CoroutineHeapSize>>, ThreadLocalAllocator: LocalAllocator<MemoryMapSource>>(per_thread_state: NonNull<()>, outstanding_memory_reporter: fn(u64))
+		{
+			let per_thread_state = per_thread_state.cast::<PerThreadState<CoroutineHeapSize, CoroutineLocalAllocator, ThreadLocalAllocator>>().mutable_reference();
+
+			if per_thread_state.thread_local_allocator.is_none()
+			{
+				return
+			}
+
+			per_thread_state.current_allocator_in_use = CurrentAllocatorInUse::Global;
+			per_thread_state.thread_local_allocator_factory_disabled = true;
+			per_thread_state.drain_remote_frees();
+
+			let outstanding_bytes = per_thread_state.thread_local_allocator_memory_usage(LocalAllocatorMemoryUsage::usage);
+			if unlikely!(outstanding_bytes != 0)
+			{
+				outstanding_memory_reporter(outstanding_bytes);
+			}
+
+			per_thread_state.drop_thread_local_allocator()
+		}
+
+		let registered_thread_exit_teardown = RegisteredThreadExitTeardown
+		{
+			per_thread_state: (global_allocator.per_thread_state())().cast(),
+			teardown: teardown::<CoroutineHeapSize, GTACSA::CoroutineLocalAllocator, GTACSA::ThreadLocalAllocator>,
+			outstanding_memory_reporter,
+		};
+
+		// The first access to a `thread_local!` static may allocate to register its destructor.
+		let _guard = global_allocator.with_global();
+		let _ = ThreadExitTeardownOfThreadLocalAllocator.try_with(|thread_exit_teardown| thread_exit_teardown.0.set(Some(registered_thread_exit_teardown)));
+	}
+}
+
+#[derive(Copy, Clone)]
+struct RegisteredThreadExitTeardown
+{
+	per_thread_state: NonNull<()>,
+
+	teardown: fn(NonNull<()>, fn(u64)),
+
+	outstanding_memory_reporter: fn(u64),
+}
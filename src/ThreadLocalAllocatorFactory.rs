@@ -0,0 +1,66 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Creates a thread-local allocator the first time it is needed on a thread, eg for threads spawned by third-party libraries which never call `initialize_thread_local_allocator()`.
+///
+/// If creation fails, allocations on that thread fall back to the global allocator.
+#[derive(Debug)]
+pub enum ThreadLocalAllocatorFactory<ThreadLocalAllocator: LocalAllocator<MemoryMapSource>>
+{
+	/// Never create a thread-local allocator; until one is initialized using `initialize_thread_local_allocator()`, allocations fall back to the global allocator.
+	Never,
+
+	/// Create using a function.
+	Function(fn() -> Result<ThreadLocalAllocator, AllocError>),
+
+	/// Create using `LocalAllocator::new_local_allocator()` from a new memory map.
+	MemoryMap
+	{
+		/// Size of memory map.
+		size: NonZeroU64,
+
+		/// Memory map settings; a function as `MappedMemorySettings` can not be created in a `const` context.
+		settings: fn() -> MappedMemorySettings,
+
+		/// Lifetime hint.
+		lifetime_hint: LifetimeHint,
+
+		/// Block size hint.
+		block_size_hint: NonZeroUsize,
+	},
+}
+
+impl<ThreadLocalAllocator: LocalAllocator<MemoryMapSource>> ThreadLocalAllocatorFactory<ThreadLocalAllocator>
+{
+	#[inline(always)]
+	fn is_never(&self) -> bool
+	{
+		match self
+		{
+			&ThreadLocalAllocatorFactory::Never => true,
+			_ => false,
+		}
+	}
+
+	/// Should be called with the global allocator in use, as creation may itself allocate.
+	#[inline(always)]
+	fn create(&self) -> Option<ThreadLocalAllocator>
+	{
+		use self::ThreadLocalAllocatorFactory::*;
+
+		match self
+		{
+			&Never => None,
+
+			&Function(function) => function().ok(),
+
+			&MemoryMap { size, settings, lifetime_hint, block_size_hint } => match MemoryMapSource::new(size, settings())
+			{
+				Ok(memory_source) => Some(ThreadLocalAllocator::new_local_allocator(memory_source, lifetime_hint, block_size_hint)),
+
+				Err(_) => None,
+			},
+		}
+	}
+}
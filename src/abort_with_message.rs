@@ -0,0 +1,70 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Writes `context-allocator: `, a message and a new line to standard error, then aborts the process.
+///
+/// Does not allocate or panic, so can be used inside an allocator; the message is formatted into a fixed-size buffer on the stack and truncated if too long.
+#[cold]
+#[inline(never)]
+pub(crate) fn abort_with_message(message: Arguments) -> !
+{
+	let mut buffer = AbortMessageBuffer
+	{
+		bytes: [0; AbortMessageBuffer::Capacity],
+		length: 0,
+	};
+	let _ = fmt::Write::write_str(&mut buffer, "context-allocator: ");
+	let _ = fmt::Write::write_fmt(&mut buffer, message);
+	let _ = fmt::Write::write_str(&mut buffer, "\n");
+	buffer.write_to_standard_error();
+	abort()
+}
+
+struct AbortMessageBuffer
+{
+	bytes: [u8; AbortMessageBuffer::Capacity],
+	length: usize,
+}
+
+impl fmt::Write for AbortMessageBuffer
+{
+	/// Truncates rather than failing, so that formatting always completes.
+	#[inline(always)]
+	fn write_str(&mut self, string: &str) -> fmt::Result
+	{
+		let remaining = Self::Capacity - self.length;
+		let length = min(remaining, string.len());
+		self.bytes[self.length .. self.length + length].copy_from_slice(&string.as_bytes()[.. length]);
+		self.length += length;
+		Ok(())
+	}
+}
+
+impl AbortMessageBuffer
+{
+	const Capacity: usize = 512;
+
+	/// Errors are ignored; there is nothing else that can be done.
+	#[inline(always)]
+	fn write_to_standard_error(&self)
+	{
+		let mut bytes = &self.bytes[.. self.length];
+		while !bytes.is_empty()
+		{
+			let result = unsafe { write(STDERR_FILENO, bytes.as_ptr() as *const c_void, bytes.len()) };
+			if likely!(result > 0)
+			{
+				bytes = &bytes[(result as usize) .. ];
+			}
+			else if result == -1 && errno().0 == EINTR
+			{
+				continue
+			}
+			else
+			{
+				return
+			}
+		}
+	}
+}
@@ -14,6 +14,8 @@
 ///
 /// This allocator NEVER grows or shrinks its memory region.
 ///
+/// Memory can be filled when allocated and poisoned when freed using `set_memory_filling()`.
+///
 /// This allocator is not thread-safe.
 #[derive(Debug)]
 pub struct BumpAllocator<MS: MemorySource>
@@ -22,6 +24,8 @@ pub struct BumpAllocator<MS: MemorySource>
 	next_allocation_at_pointer: Cell<MemoryAddress>,
 	ends_at_pointer: MemoryAddress,
 
+	memory_filling: Cell<MemoryFilling>,
+
 	memory_source: MS,
 }
 
@@ -65,12 +69,15 @@ impl<MS: MemorySource> Allocator for BumpAllocator<MS>
 		self.next_allocation_at_pointer.set(allocation_ends_at_pointer!(self, non_zero_size, next_allocation_at_rounded_up_pointer));
 		let actual_size = (self.next_allocation_at_pointer.get().as_ptr() as usize) - (next_allocation_at_rounded_up_pointer.as_ptr() as usize);
 
+		self.memory_filling().fill_allocated(next_allocation_at_rounded_up_pointer, non_zero_size.get());
 		Ok((next_allocation_at_rounded_up_pointer, actual_size))
 	}
 
 	#[inline(always)]
-	fn deallocate(&self, _non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	fn deallocate(&self, non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
 	{
+		self.memory_filling().poison_freed(current_memory, non_zero_size.get());
+
 		if unlikely!(current_memory == self.most_recent_allocation_pointer.get())
 		{
 			self.next_allocation_at_pointer.set(self.most_recent_allocation_pointer.get())
@@ -88,6 +95,7 @@ impl<MS: MemorySource> Allocator for BumpAllocator<MS>
 			self.next_allocation_at_pointer.set(allocation_ends_at_pointer!(self, non_zero_new_size, current_memory));
 
 			let actual_size = (self.next_allocation_at_pointer.get().as_ptr() as usize) - (last.as_ptr() as usize);
+			self.memory_filling().fill_grown(current_memory, non_zero_current_size, non_zero_new_size);
 			Ok((current_memory, actual_size))
 		}
 		else
@@ -105,10 +113,12 @@ impl<MS: MemorySource> Allocator for BumpAllocator<MS>
 		if unlikely!(self.fits_at_current_location(non_zero_power_of_two_new_alignment, current_memory))
 		{
 			self.next_allocation_at_pointer.set(current_memory.add(new_size));
+			self.memory_filling().poison_shrunk(current_memory, non_zero_current_size, non_zero_new_size);
 			Ok((current_memory, new_size))
 		}
 		else if likely!(Self::new_alignment_can_be_accommodated(non_zero_power_of_two_new_alignment, current_memory))
 		{
+			self.memory_filling().poison_shrunk(current_memory, non_zero_current_size, non_zero_new_size);
 			Ok((current_memory, new_size))
 		}
 		else
@@ -149,10 +159,26 @@ impl<MS: MemorySource> BumpAllocator<MS>
 			next_allocation_at_pointer: Cell::new(allocations_start_from),
 			ends_at_pointer: allocations_start_from.add_non_zero(memory_source.size()),
 
+			memory_filling: Cell::new(MemoryFilling::Off),
+
 			memory_source,
 		}
 	}
 
+	/// Memory filling.
+	#[inline(always)]
+	pub fn memory_filling(&self) -> MemoryFilling
+	{
+		self.memory_filling.get()
+	}
+
+	/// Changes memory filling; memory already allocated or freed is not affected.
+	#[inline(always)]
+	pub fn set_memory_filling(&self, memory_filling: MemoryFilling)
+	{
+		self.memory_filling.set(memory_filling)
+	}
+
 	#[inline(always)]
 	fn allocations_start_from(&self) -> MemoryAddress
 	{
@@ -185,3 +211,79 @@ impl<MS: MemorySource> BumpAllocator<MS>
 		Ok((new_memory, actual_size))
 	}
 }
+
+#[cfg(test)]
+mod BumpAllocatorTests
+{
+	use super::*;
+
+	const Allocated: u8 = MemoryFilling::DefaultAllocatedByte;
+
+	const Freed: u8 = MemoryFilling::DefaultFreedByte;
+
+	#[test]
+	pub fn rewinding_deallocation_is_poisoned_and_reallocation_is_filled()
+	{
+		let allocator = new_allocator();
+
+		let (first, _) = allocator.allocate(16.non_zero(), 8.non_zero()).expect("Did not allocate");
+		let (most_recent, _) = allocator.allocate(16.non_zero(), 8.non_zero()).expect("Did not allocate");
+		assert_eq!(bytes(most_recent, 16), [Allocated; 16], "Did not fill allocated memory");
+
+		allocator.deallocate(16.non_zero(), 8.non_zero(), most_recent);
+		assert_eq!(bytes(most_recent, 16), [Freed; 16], "Did not poison rewound memory");
+
+		let (rewound, _) = allocator.allocate(8.non_zero(), 8.non_zero()).expect("Did not allocate");
+		assert_eq!(rewound, most_recent, "Did not rewind");
+		assert_eq!(bytes(rewound, 16), [[Allocated; 8], [Freed; 8]].concat(), "Did not fill only the reallocated memory");
+		assert_eq!(bytes(first, 16), [Allocated; 16], "Rewinding changed earlier memory");
+	}
+
+	#[test]
+	pub fn reallocation_in_place_fills_grown_and_poisons_shrunk_memory()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate(8.non_zero(), 8.non_zero()).expect("Did not allocate");
+		unsafe { allocation.as_ptr().write_bytes(0x11, 8) };
+
+		let (grown, _) = allocator.growing_reallocate(24.non_zero(), 8.non_zero(), 8.non_zero(), 8.non_zero(), allocation, false).expect("Did not grow");
+		assert_eq!(grown, allocation, "Did not grow in place");
+		assert_eq!(bytes(grown, 24), [vec![0x11; 8], vec![Allocated; 16]].concat(), "Did not fill only the grown memory");
+
+		let (shrunk, _) = allocator.shrinking_reallocate(4.non_zero(), 8.non_zero(), 24.non_zero(), 8.non_zero(), grown, false).expect("Did not shrink");
+		assert_eq!(shrunk, allocation, "Did not shrink in place");
+		assert_eq!(bytes(shrunk, 24), [vec![0x11; 4], vec![Freed; 4], vec![Freed; 16]].concat(), "Did not poison only the shrunk memory");
+	}
+
+	#[derive(Debug)]
+	struct BufferMemorySource
+	{
+		buffer: Box<[u64; 32]>,
+	}
+
+	impl MemorySource for BufferMemorySource
+	{
+		fn size(&self) -> NonZeroUsize
+		{
+			size_of::<[u64; 32]>().non_zero()
+		}
+
+		fn allocations_start_from(&self) -> MemoryAddress
+		{
+			NonNull::from(&self.buffer[0]).cast()
+		}
+	}
+
+	fn new_allocator() -> BumpAllocator<BufferMemorySource>
+	{
+		let allocator = BumpAllocator::new(BufferMemorySource { buffer: Box::new([0; 32]) });
+		allocator.set_memory_filling(MemoryFilling::On);
+		allocator
+	}
+
+	fn bytes(memory_address: MemoryAddress, size: usize) -> Vec<u8>
+	{
+		unsafe { from_raw_parts(memory_address.as_ptr() as *const u8, size) }.to_vec()
+	}
+}
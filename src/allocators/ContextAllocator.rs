@@ -119,3 +119,38 @@ impl<MS: MemorySource> LocalAllocator<MS> for ContextAllocator<MS>
 		}
 	}
 }
+
+impl<MS: MemorySource> ContextAllocator<MS>
+{
+	/// Memory filling.
+	#[inline(always)]
+	pub fn memory_filling(&self) -> MemoryFilling
+	{
+		use self::ContextAllocator::*;
+
+		match *self
+		{
+			ShortLived(ref allocator) => allocator.memory_filling(),
+
+			MediumLived(ref allocator) => allocator.memory_filling(),
+
+			LongLived(ref allocator) => allocator.memory_filling(),
+		}
+	}
+
+	/// Changes memory filling; memory already allocated or freed is not affected.
+	#[inline(always)]
+	pub fn set_memory_filling(&self, memory_filling: MemoryFilling)
+	{
+		use self::ContextAllocator::*;
+
+		match *self
+		{
+			ShortLived(ref allocator) => allocator.set_memory_filling(memory_filling),
+
+			MediumLived(ref allocator) => allocator.set_memory_filling(memory_filling),
+
+			LongLived(ref allocator) => allocator.set_memory_filling(memory_filling),
+		}
+	}
+}
@@ -0,0 +1,295 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A debugging allocator which pads every allocation with leading and trailing red zones filled with a canary, to find buffer overruns and underruns, eg inside coroutine heaps.
+///
+/// The canaries are verified on deallocation and reallocation; each overwritten red zone is passed to a reporter, which by default aborts the process.
+///
+/// The leading red zone is at least `RedZoneSize` bytes and a multiple of the requested alignment, so the requested alignment is honoured; the trailing red zone is `RedZoneSize` bytes.
+/// The actual size returned is always the requested size, so callers never use bytes of the trailing red zone.
+///
+/// Wraps any `Allocator`; if that is a `LocalAllocator`, so is this, and so it can be used for any tier, eg as `GuardedAllocator<ContextAllocator<CoroutineHeapMemorySource<CoroutineHeapSize>>>` for coroutines.
+#[derive(Debug)]
+pub struct GuardedAllocator<A: Allocator>
+{
+	allocator: A,
+
+	corruption_reporter: fn(GuardedAllocatorCorruption),
+}
+
+impl<A: Allocator> Allocator for GuardedAllocator<A>
+{
+	#[inline(always)]
+	fn allocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let leading_red_zone_size = Self::leading_red_zone_size(non_zero_power_of_two_alignment);
+		let (guarded_memory, _actual_size) = self.allocator.allocate(Self::guarded_size(non_zero_size, leading_red_zone_size)?, non_zero_power_of_two_alignment)?;
+
+		let memory_address = guarded_memory.add(leading_red_zone_size);
+		Self::fill_red_zones(memory_address, non_zero_size, leading_red_zone_size);
+		Ok((memory_address, non_zero_size.get()))
+	}
+
+	#[inline(always)]
+	fn deallocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		let leading_red_zone_size = Self::leading_red_zone_size(non_zero_power_of_two_alignment);
+		self.verify_red_zones(current_memory, non_zero_size, leading_red_zone_size);
+
+		let guarded_size = Self::guarded_size(non_zero_size, leading_red_zone_size).expect("Allocated, so can not overflow");
+		self.allocator.deallocate(guarded_size, non_zero_power_of_two_alignment, current_memory.subtract(leading_red_zone_size))
+	}
+
+	#[inline(always)]
+	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		self.reallocate_guarded(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved, |allocator, guarded_new_size, guarded_current_size, guarded_memory| allocator.growing_reallocate(guarded_new_size, non_zero_power_of_two_new_alignment, guarded_current_size, non_zero_power_of_two_current_alignment, guarded_memory, current_memory_can_not_be_moved))
+	}
+
+	#[inline(always)]
+	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		self.reallocate_guarded(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved, |allocator, guarded_new_size, guarded_current_size, guarded_memory| allocator.shrinking_reallocate(guarded_new_size, non_zero_power_of_two_new_alignment, guarded_current_size, non_zero_power_of_two_current_alignment, guarded_memory, current_memory_can_not_be_moved))
+	}
+}
+
+impl<MS: MemorySource, LA: LocalAllocator<MS>> LocalAllocator<MS> for GuardedAllocator<LA>
+{
+	#[inline(always)]
+	fn new_local_allocator(memory_source: MS, lifetime_hint: LifetimeHint, block_size_hint: NonZeroUsize) -> Self
+	{
+		Self::new(LA::new_local_allocator(memory_source, lifetime_hint, block_size_hint), GuardedAllocatorCorruption::abort)
+	}
+
+	#[inline(always)]
+	fn memory_range(&self) -> MemoryRange
+	{
+		self.allocator.memory_range()
+	}
+}
+
+impl<A: Allocator> GuardedAllocator<A>
+{
+	/// Minimum size of each red zone.
+	pub const RedZoneSize: usize = 16;
+
+	/// Byte value red zones are filled with.
+	pub const Canary: u8 = 0xFD;
+
+	/// Create a new instance.
+	///
+	/// `corruption_reporter` is called for each overwritten red zone, before the memory is deallocated or reallocated; it must not allocate.
+	/// Use `GuardedAllocatorCorruption::abort` to abort the process.
+	#[inline(always)]
+	pub const fn new(allocator: A, corruption_reporter: fn(GuardedAllocatorCorruption)) -> Self
+	{
+		Self
+		{
+			allocator,
+			corruption_reporter,
+		}
+	}
+
+	/// Verifies the red zones of a live allocation, calling the corruption reporter for each overwritten red zone.
+	///
+	/// Returns `true` if neither red zone was overwritten.
+	#[inline(always)]
+	pub fn verify(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>) -> bool
+	{
+		self.verify_red_zones(current_memory, non_zero_size, Self::leading_red_zone_size(non_zero_power_of_two_alignment))
+	}
+
+	/// Wrapped allocator.
+	#[inline(always)]
+	pub fn allocator(&self) -> &A
+	{
+		&self.allocator
+	}
+
+	#[inline(always)]
+	fn reallocate_guarded(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool, reallocate: impl FnOnce(&A, NonZeroUsize, NonZeroUsize, NonNull<u8>) -> Result<(NonNull<u8>, usize), AllocError>) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let current_leading_red_zone_size = Self::leading_red_zone_size(non_zero_power_of_two_current_alignment);
+		self.verify_red_zones(current_memory, non_zero_current_size, current_leading_red_zone_size);
+
+		let new_leading_red_zone_size = Self::leading_red_zone_size(non_zero_power_of_two_new_alignment);
+		if unlikely!(new_leading_red_zone_size != current_leading_red_zone_size)
+		{
+			if unlikely!(current_memory_can_not_be_moved)
+			{
+				return Err(AllocError)
+			}
+
+			let (new_memory, actual_size) = self.allocate(non_zero_new_size, non_zero_power_of_two_new_alignment)?;
+			unsafe { new_memory.as_ptr().copy_from_nonoverlapping(current_memory.as_ptr(), min(non_zero_new_size.get(), non_zero_current_size.get())) };
+			self.deallocate(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory);
+			return Ok((new_memory, actual_size))
+		}
+
+		let guarded_new_size = Self::guarded_size(non_zero_new_size, new_leading_red_zone_size)?;
+		let guarded_current_size = Self::guarded_size(non_zero_current_size, current_leading_red_zone_size).expect("Allocated, so can not overflow");
+		let (guarded_memory, _actual_size) = reallocate(&self.allocator, guarded_new_size, guarded_current_size, current_memory.subtract(current_leading_red_zone_size))?;
+
+		let memory_address = guarded_memory.add(new_leading_red_zone_size);
+		Self::fill_red_zones(memory_address, non_zero_new_size, new_leading_red_zone_size);
+		Ok((memory_address, non_zero_new_size.get()))
+	}
+
+	#[inline(always)]
+	fn verify_red_zones(&self, memory_address: MemoryAddress, non_zero_size: NonZeroUsize, leading_red_zone_size: usize) -> bool
+	{
+		let leading_red_zone = unsafe { from_raw_parts(memory_address.subtract(leading_red_zone_size).as_ptr() as *const u8, leading_red_zone_size) };
+		let leading_corruption = leading_red_zone.iter().rposition(|&byte| byte != Self::Canary).map(|index| (RedZoneSide::Leading, (index as isize) - (leading_red_zone_size as isize)));
+
+		let trailing_red_zone = unsafe { from_raw_parts(memory_address.add(non_zero_size.get()).as_ptr() as *const u8, Self::RedZoneSize) };
+		let trailing_corruption = trailing_red_zone.iter().position(|&byte| byte != Self::Canary).map(|index| (RedZoneSide::Trailing, (non_zero_size.get() + index) as isize));
+
+		let mut is_intact = true;
+		for &(side, offset) in leading_corruption.iter().chain(trailing_corruption.iter())
+		{
+			is_intact = false;
+			(self.corruption_reporter)
+			(
+				GuardedAllocatorCorruption
+				{
+					memory_address,
+					size: non_zero_size,
+					side,
+					offset,
+				}
+			)
+		}
+		is_intact
+	}
+
+	#[inline(always)]
+	fn fill_red_zones(memory_address: MemoryAddress, non_zero_size: NonZeroUsize, leading_red_zone_size: usize)
+	{
+		unsafe
+		{
+			memory_address.subtract(leading_red_zone_size).as_ptr().write_bytes(Self::Canary, leading_red_zone_size);
+			memory_address.add(non_zero_size.get()).as_ptr().write_bytes(Self::Canary, Self::RedZoneSize);
+		}
+	}
+
+	#[inline(always)]
+	fn leading_red_zone_size(non_zero_power_of_two_alignment: NonZeroUsize) -> usize
+	{
+		max(Self::RedZoneSize, non_zero_power_of_two_alignment.get())
+	}
+
+	#[inline(always)]
+	fn guarded_size(non_zero_size: NonZeroUsize, leading_red_zone_size: usize) -> Result<NonZeroUsize, AllocError>
+	{
+		match non_zero_size.get().checked_add(leading_red_zone_size + Self::RedZoneSize)
+		{
+			None => Err(AllocError),
+			Some(guarded_size) => Ok(new_non_zero_usize(guarded_size)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod GuardedAllocatorTests
+{
+	use super::*;
+	use std::cell::RefCell;
+
+	thread_local!
+	{
+		static Corruptions: RefCell<Vec<GuardedAllocatorCorruption>> = RefCell::new(Vec::new());
+	}
+
+	#[test]
+	pub fn intact_allocation_is_not_reported()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, actual_size) = allocator.allocate(24.non_zero(), 8.non_zero()).expect("Did not allocate");
+		assert_eq!(actual_size, 24, "Actual size should be the requested size");
+		unsafe { allocation.as_ptr().write_bytes(0x00, 24) };
+
+		assert!(allocator.verify(24.non_zero(), 8.non_zero(), allocation), "Intact allocation was not verified");
+		allocator.deallocate(24.non_zero(), 8.non_zero(), allocation);
+		assert_eq!(corruptions(), vec![], "Intact allocation was reported");
+	}
+
+	#[test]
+	pub fn buffer_overrun_is_reported_on_deallocation()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate(24.non_zero(), 8.non_zero()).expect("Did not allocate");
+		unsafe { allocation.as_ptr().add(25).write(0x00) };
+
+		allocator.deallocate(24.non_zero(), 8.non_zero(), allocation);
+		assert_eq!(corruptions(), vec![corruption(allocation, 24, RedZoneSide::Trailing, 25)], "Buffer overrun was not reported");
+	}
+
+	#[test]
+	pub fn buffer_underrun_is_reported_on_deallocation()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate(24.non_zero(), 8.non_zero()).expect("Did not allocate");
+		unsafe { allocation.as_ptr().offset(-3).write(0x00) };
+
+		allocator.deallocate(24.non_zero(), 8.non_zero(), allocation);
+		assert_eq!(corruptions(), vec![corruption(allocation, 24, RedZoneSide::Leading, -3)], "Buffer underrun was not reported");
+	}
+
+	#[test]
+	pub fn buffer_overrun_is_reported_on_reallocation()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate(24.non_zero(), 8.non_zero()).expect("Did not allocate");
+		unsafe { allocation.as_ptr().add(24).write(0x00) };
+
+		let (reallocation, _) = allocator.growing_reallocate(48.non_zero(), 8.non_zero(), 24.non_zero(), 8.non_zero(), allocation, false).expect("Did not reallocate");
+		assert_eq!(corruptions(), vec![corruption(allocation, 24, RedZoneSide::Trailing, 24)], "Buffer overrun was not reported");
+
+		assert!(allocator.verify(48.non_zero(), 8.non_zero(), reallocation), "Red zones were not refilled after reallocation");
+		allocator.deallocate(48.non_zero(), 8.non_zero(), reallocation);
+		assert_eq!(corruptions(), vec![], "Reallocation was reported");
+	}
+
+	#[test]
+	pub fn requested_alignment_is_honoured()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate(24.non_zero(), 64.non_zero()).expect("Did not allocate");
+		assert_eq!(allocation.to_usize() % 64, 0, "Alignment was not honoured");
+
+		allocator.deallocate(24.non_zero(), 64.non_zero(), allocation);
+		assert_eq!(corruptions(), vec![], "Aligned allocation was reported");
+	}
+
+	fn new_allocator() -> GuardedAllocator<GlobalAllocToAllocatorAdaptor<System>>
+	{
+		GuardedAllocator::new(GlobalAllocToAllocatorAdaptor(System), record_corruption)
+	}
+
+	fn record_corruption(corruption: GuardedAllocatorCorruption)
+	{
+		Corruptions.with(|corruptions| corruptions.borrow_mut().push(corruption))
+	}
+
+	fn corruptions() -> Vec<GuardedAllocatorCorruption>
+	{
+		Corruptions.with(|corruptions| take(&mut *corruptions.borrow_mut()))
+	}
+
+	fn corruption(memory_address: MemoryAddress, size: usize, side: RedZoneSide, offset: isize) -> GuardedAllocatorCorruption
+	{
+		GuardedAllocatorCorruption
+		{
+			memory_address,
+			size: size.non_zero(),
+			side,
+			offset,
+		}
+	}
+}
@@ -0,0 +1,42 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A red zone of a `GuardedAllocator` allocation which was found to be overwritten.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct GuardedAllocatorCorruption
+{
+	/// Address of the allocation, as returned to the caller.
+	pub memory_address: MemoryAddress,
+
+	/// Requested size of the allocation.
+	pub size: NonZeroUsize,
+
+	/// Which red zone was overwritten.
+	pub side: RedZoneSide,
+
+	/// Offset, relative to `memory_address`, of the overwritten byte nearest the allocation.
+	pub offset: isize,
+}
+
+impl Display for GuardedAllocatorCorruption
+{
+	#[inline(always)]
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result
+	{
+		write!(f, "{:?} red zone of allocation at {:?} of {} bytes overwritten at offset {}", self.side, self.memory_address, self.size, self.offset)
+	}
+}
+
+impl GuardedAllocatorCorruption
+{
+	/// The default reporter of a `GuardedAllocator`; writes a message to standard error and aborts the process.
+	///
+	/// Does not allocate or panic, as the corruption is usually found whilst deallocating; the message is formatted on the stack and written with `write(2)`.
+	#[cold]
+	#[inline(never)]
+	pub fn abort(self)
+	{
+		abort_with_message(format_args!("{}", self))
+	}
+}
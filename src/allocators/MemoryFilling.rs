@@ -0,0 +1,201 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Fills newly allocated memory and poisons freed memory with byte patterns, so that reads of uninitialized or freed memory are visible.
+///
+/// Set per allocator instance, eg using `BitSetAllocator::set_memory_filling()`; the default is `Off`.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct MemoryFilling
+{
+	/// Byte to fill newly allocated memory with, if any; includes the newly added part of a growing reallocation.
+	pub allocated: Option<u8>,
+
+	/// Byte to poison freed memory with, if any; includes the tail of a shrinking reallocation.
+	pub freed: Option<u8>,
+}
+
+impl MemoryFilling
+{
+	/// Neither fills nor poisons memory.
+	pub const Off: Self = Self::new(None, None);
+
+	/// Fills with `DefaultAllocatedByte` and poisons with `DefaultFreedByte`.
+	pub const On: Self = Self::new(Some(Self::DefaultAllocatedByte), Some(Self::DefaultFreedByte));
+
+	/// Default byte to fill newly allocated memory with.
+	pub const DefaultAllocatedByte: u8 = 0xCD;
+
+	/// Default byte to poison freed memory with.
+	pub const DefaultFreedByte: u8 = 0xDD;
+
+	/// New instance.
+	#[inline(always)]
+	pub const fn new(allocated: Option<u8>, freed: Option<u8>) -> Self
+	{
+		Self
+		{
+			allocated,
+			freed,
+		}
+	}
+
+	#[inline(always)]
+	pub(crate) fn fill_allocated(self, memory_address: MemoryAddress, size: usize)
+	{
+		if let Some(allocated) = self.allocated
+		{
+			unsafe { memory_address.as_ptr().write_bytes(allocated, size) }
+		}
+	}
+
+	#[inline(always)]
+	pub(crate) fn poison_freed(self, memory_address: MemoryAddress, size: usize)
+	{
+		if let Some(freed) = self.freed
+		{
+			unsafe { memory_address.as_ptr().write_bytes(freed, size) }
+		}
+	}
+
+	/// Fills the newly added part of a growing reallocation.
+	#[inline(always)]
+	pub(crate) fn fill_grown(self, memory_address: MemoryAddress, non_zero_current_size: NonZeroUsize, non_zero_new_size: NonZeroUsize)
+	{
+		self.fill_allocated(memory_address.add_non_zero(non_zero_current_size), non_zero_new_size.get() - non_zero_current_size.get())
+	}
+
+	/// Poisons the tail of a shrinking reallocation.
+	#[inline(always)]
+	pub(crate) fn poison_shrunk(self, memory_address: MemoryAddress, non_zero_current_size: NonZeroUsize, non_zero_new_size: NonZeroUsize)
+	{
+		self.poison_freed(memory_address.add_non_zero(non_zero_new_size), non_zero_current_size.get() - non_zero_new_size.get())
+	}
+
+	/// Poisons freed memory which has been moved, except for any part it overlaps with the memory it was moved to.
+	#[inline(always)]
+	pub(crate) fn poison_moved(self, from: MemoryAddress, from_size: NonZeroUsize, to: MemoryAddress, to_size: NonZeroUsize)
+	{
+		if self.freed.is_none()
+		{
+			return
+		}
+
+		let from_end = from.add_non_zero(from_size);
+		let to_end = to.add_non_zero(to_size);
+
+		if from < to
+		{
+			let end = min(from_end, to);
+			self.poison_freed(from, end.difference(from))
+		}
+		if to_end < from_end
+		{
+			let start = max(from, to_end);
+			self.poison_freed(start, from_end.difference(start))
+		}
+	}
+}
+
+#[cfg(test)]
+mod MemoryFillingTests
+{
+	use super::*;
+	use std::ops::Range;
+
+	const Allocated: u8 = MemoryFilling::DefaultAllocatedByte;
+
+	const Freed: u8 = MemoryFilling::DefaultFreedByte;
+
+	#[test]
+	pub fn fills_allocated_memory()
+	{
+		let mut buffer = [0u8; 64];
+		MemoryFilling::On.fill_allocated(address(&mut buffer, 8), 16);
+		assert_eq!(buffer, expected(&[(8 .. 24, Allocated)]), "Did not fill allocated memory");
+	}
+
+	#[test]
+	pub fn poisons_freed_memory()
+	{
+		let mut buffer = [0u8; 64];
+		MemoryFilling::On.poison_freed(address(&mut buffer, 8), 16);
+		assert_eq!(buffer, expected(&[(8 .. 24, Freed)]), "Did not poison freed memory");
+	}
+
+	#[test]
+	pub fn off_neither_fills_nor_poisons()
+	{
+		let mut buffer = [0u8; 64];
+		MemoryFilling::Off.fill_allocated(address(&mut buffer, 0), 64);
+		MemoryFilling::Off.poison_freed(address(&mut buffer, 0), 64);
+		MemoryFilling::Off.poison_moved(address(&mut buffer, 0), 16.non_zero(), address(&mut buffer, 32), 16.non_zero());
+		assert_eq!(buffer, [0u8; 64], "Filled or poisoned memory when off");
+	}
+
+	#[test]
+	pub fn fills_only_the_newly_added_part_of_a_growing_reallocation()
+	{
+		let mut buffer = [0u8; 64];
+		MemoryFilling::On.fill_grown(address(&mut buffer, 8), 16.non_zero(), 40.non_zero());
+		assert_eq!(buffer, expected(&[(24 .. 48, Allocated)]), "Did not fill only the grown part");
+	}
+
+	#[test]
+	pub fn poisons_only_the_tail_of_a_shrinking_reallocation()
+	{
+		let mut buffer = [0u8; 64];
+		MemoryFilling::On.poison_shrunk(address(&mut buffer, 8), 40.non_zero(), 16.non_zero());
+		assert_eq!(buffer, expected(&[(24 .. 48, Freed)]), "Did not poison only the shrunk tail");
+	}
+
+	#[test]
+	pub fn poisons_all_of_memory_moved_without_overlap()
+	{
+		let mut buffer = [0u8; 64];
+		MemoryFilling::On.poison_moved(address(&mut buffer, 0), 16.non_zero(), address(&mut buffer, 32), 16.non_zero());
+		assert_eq!(buffer, expected(&[(0 .. 16, Freed)]), "Did not poison all of the moved memory");
+	}
+
+	#[test]
+	pub fn does_not_poison_memory_moved_to_overlapping_higher_memory()
+	{
+		let mut buffer = [0u8; 64];
+		MemoryFilling::On.poison_moved(address(&mut buffer, 0), 16.non_zero(), address(&mut buffer, 8), 16.non_zero());
+		assert_eq!(buffer, expected(&[(0 .. 8, Freed)]), "Poisoned the overlap when moving up");
+	}
+
+	#[test]
+	pub fn does_not_poison_memory_moved_to_overlapping_lower_memory()
+	{
+		let mut buffer = [0u8; 64];
+		MemoryFilling::On.poison_moved(address(&mut buffer, 8), 16.non_zero(), address(&mut buffer, 0), 16.non_zero());
+		assert_eq!(buffer, expected(&[(16 .. 24, Freed)]), "Poisoned the overlap when moving down");
+	}
+
+	#[test]
+	pub fn does_not_poison_memory_moved_to_enclosing_memory()
+	{
+		let mut buffer = [0u8; 64];
+		MemoryFilling::On.poison_moved(address(&mut buffer, 8), 8.non_zero(), address(&mut buffer, 0), 32.non_zero());
+		assert_eq!(buffer, [0u8; 64], "Poisoned memory moved to enclosing memory");
+	}
+
+	fn address(buffer: &mut [u8; 64], offset: usize) -> MemoryAddress
+	{
+		NonNull::from(&mut buffer[0]).add(offset)
+	}
+
+	fn expected(ranges: &[(Range<usize>, u8)]) -> [u8; 64]
+	{
+		let mut expected = [0u8; 64];
+		for &(ref range, byte) in ranges
+		{
+			for index in range.clone()
+			{
+				expected[index] = byte
+			}
+		}
+		expected
+	}
+}
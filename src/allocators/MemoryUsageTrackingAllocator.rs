@@ -7,57 +7,55 @@
 /// This allocator tracks memory usage based on requested memory sizes, not actualy allocated sizes.
 /// This is because growing (or shrinking) reallocations do not know the original actually allocated sizes.
 ///
+/// Wraps any `Allocator`; if that is a `LocalAllocator`, so is this.
+///
 /// This allocator is not thread-safe.
 #[derive(Debug)]
-pub struct MemoryUsageTrackingThreadLocalAllocator<LA: LocalAllocator<MemoryMapSource>>
+pub struct MemoryUsageTrackingAllocator<A: Allocator>
 {
-	local_allocator: LA,
+	allocator: A,
 	
 	local_allocator_memory_usage: LocalAllocatorMemoryUsage,
 }
 
-impl<LA: LocalAllocator<MemoryMapSource>> Allocator for MemoryUsageTrackingThreadLocalAllocator<LA>
+/// An allocator designed to track memory usage of a thread-local allocator.
+pub type MemoryUsageTrackingThreadLocalAllocator<LA> = MemoryUsageTrackingAllocator<LA>;
+
+impl<A: Allocator> Allocator for MemoryUsageTrackingAllocator<A>
 {
 	#[inline(always)]
 	fn allocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
 	{
-		let result = self.local_allocator.allocate(non_zero_size, non_zero_power_of_two_alignment)?;
-		self.local_allocator_memory_usage.allocated(result.1);
-		Ok(result)
+		self.local_allocator_memory_usage.allocate(&self.allocator, non_zero_size, non_zero_power_of_two_alignment)
 	}
 
 	#[inline(always)]
 	fn deallocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
 	{
-		self.local_allocator.deallocate(non_zero_size, non_zero_power_of_two_alignment, current_memory);
-		self.local_allocator_memory_usage.deallocated(non_zero_size);
+		self.local_allocator_memory_usage.deallocate(&self.allocator, non_zero_size, non_zero_power_of_two_alignment, current_memory)
 	}
 
 	#[inline(always)]
 	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
 	{
-		let result = self.local_allocator.growing_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)?;
-		self.local_allocator_memory_usage.growing_reallocated(non_zero_current_size, result.1);
-		Ok(result)
+		self.local_allocator_memory_usage.growing_reallocate(&self.allocator, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
 	}
 
 	#[inline(always)]
 	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
 	{
-		let result = self.local_allocator.shrinking_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)?;
-		self.local_allocator_memory_usage.shrinking_reallocated(non_zero_current_size, result.1);
-		Ok(result)
+		self.local_allocator_memory_usage.shrinking_reallocate(&self.allocator, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
 	}
 }
 
-impl<LA: LocalAllocator<MemoryMapSource>> LocalAllocator<MemoryMapSource> for MemoryUsageTrackingThreadLocalAllocator<LA>
+impl<MS: MemorySource, LA: LocalAllocator<MS>> LocalAllocator<MS> for MemoryUsageTrackingAllocator<LA>
 {
 	#[inline(always)]
-	fn new_local_allocator(memory_source: MemoryMapSource, lifetime_hint: LifetimeHint, block_size_hint: NonZeroUsize) -> Self
+	fn new_local_allocator(memory_source: MS, lifetime_hint: LifetimeHint, block_size_hint: NonZeroUsize) -> Self
 	{
 		Self
 		{
-			local_allocator: LA::new_local_allocator(memory_source, lifetime_hint, block_size_hint),
+			allocator: LA::new_local_allocator(memory_source, lifetime_hint, block_size_hint),
 			local_allocator_memory_usage: Default::default()
 		}
 	}
@@ -65,19 +63,19 @@ impl<LA: LocalAllocator<MemoryMapSource>> LocalAllocator<MemoryMapSource> for Me
 	#[inline(always)]
 	fn memory_range(&self) -> MemoryRange
 	{
-		self.local_allocator.memory_range()
+		self.allocator.memory_range()
 	}
 }
 
-impl<LA: LocalAllocator<MemoryMapSource>> MemoryUsageTrackingThreadLocalAllocator<LA>
+impl<A: Allocator> MemoryUsageTrackingAllocator<A>
 {
 	/// Create a new instance.
 	#[inline(always)]
-	pub const fn new(local_allocator: LA) -> Self
+	pub const fn new(allocator: A) -> Self
 	{
 		Self
 		{
-			local_allocator,
+			allocator,
 			local_allocator_memory_usage: LocalAllocatorMemoryUsage::new(),
 		}
 	}
@@ -88,4 +86,18 @@ impl<LA: LocalAllocator<MemoryMapSource>> MemoryUsageTrackingThreadLocalAllocato
 	{
 		&self.local_allocator_memory_usage
 	}
+	
+	/// Wrapped allocator.
+	#[inline(always)]
+	pub fn allocator(&self) -> &A
+	{
+		&self.allocator
+	}
+	
+	/// Into the wrapped allocator, discarding memory usage.
+	#[inline(always)]
+	pub fn into_allocator(self) -> A
+	{
+		self.allocator
+	}
 }
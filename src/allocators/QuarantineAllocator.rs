@@ -0,0 +1,374 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A debugging allocator which holds freed blocks in a first-in, first-out quarantine instead of returning them to the wrapped allocator immediately, to find use-after-free, eg inside coroutine heaps where memory is reused quickly.
+///
+/// Blocks are poisoned whilst held; when a block leaves the quarantine the poison is verified, and any write made after the block was freed is passed to a reporter, which by default aborts the process.
+///
+/// Blocks leave the quarantine when holding another would exceed either the byte budget or the maximum number of blocks, when an allocation from the wrapped allocator fails, when `flush()` is called and when dropped.
+/// Blocks larger than the byte budget are never held.
+/// Memory moved by a reallocation is held, too.
+///
+/// The quarantine's book-keeping is allocated when created, and never grows.
+///
+/// Wraps any `Allocator`; if that is a `LocalAllocator`, so is this, using `DefaultByteBudget` and `DefaultMaximumBlocks`.
+///
+/// This allocator is not thread-safe.
+pub struct QuarantineAllocator<A: Allocator>
+{
+	allocator: A,
+
+	byte_budget: usize,
+
+	maximum_blocks: usize,
+
+	use_after_free_reporter: fn(QuarantineUseAfterFree),
+
+	quarantined_bytes: Cell<usize>,
+
+	quarantined_blocks: UnsafeCell<VecDeque<QuarantinedBlock>>,
+}
+
+impl<A: Allocator> Debug for QuarantineAllocator<A>
+{
+	#[inline(always)]
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result
+	{
+		write!(f, "QuarantineAllocator(allocator: {:?}, byte_budget: {:?}, maximum_blocks: {:?}, quarantined_bytes: {:?}, quarantined_blocks: {:?})", self.allocator, self.byte_budget, self.maximum_blocks, self.quarantined_bytes(), self.quarantined_blocks().len())
+	}
+}
+
+impl<A: Allocator> Drop for QuarantineAllocator<A>
+{
+	#[inline(always)]
+	fn drop(&mut self)
+	{
+		self.flush()
+	}
+}
+
+impl<A: Allocator> Allocator for QuarantineAllocator<A>
+{
+	#[inline(always)]
+	fn allocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		match self.allocator.allocate(non_zero_size, non_zero_power_of_two_alignment)
+		{
+			Ok(allocated) => Ok(allocated),
+
+			Err(AllocError) =>
+			{
+				if self.quarantined_blocks().is_empty()
+				{
+					return Err(AllocError)
+				}
+				self.flush();
+				self.allocator.allocate(non_zero_size, non_zero_power_of_two_alignment)
+			}
+		}
+	}
+
+	#[inline(always)]
+	fn deallocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		let size = non_zero_size.get();
+		if unlikely!(size > self.byte_budget)
+		{
+			return self.allocator.deallocate(non_zero_size, non_zero_power_of_two_alignment, current_memory)
+		}
+
+		while self.quarantined_bytes() + size > self.byte_budget || self.quarantined_blocks().len() == self.maximum_blocks
+		{
+			self.release_oldest()
+		}
+
+		unsafe { current_memory.as_ptr().write_bytes(Self::Poison, size) };
+		self.quarantined_blocks().push_back
+		(
+			QuarantinedBlock
+			{
+				memory_address: current_memory,
+				non_zero_size,
+				non_zero_power_of_two_alignment,
+			}
+		);
+		self.quarantined_bytes.set(self.quarantined_bytes() + size)
+	}
+
+	#[inline(always)]
+	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		const CurrentMemoryCanNotBeMoved: bool = true;
+		if let Ok(reallocated) = self.allocator.growing_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, CurrentMemoryCanNotBeMoved)
+		{
+			return Ok(reallocated)
+		}
+
+		self.move_and_quarantine(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved, non_zero_current_size)
+	}
+
+	#[inline(always)]
+	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		const CurrentMemoryCanNotBeMoved: bool = true;
+		if let Ok(reallocated) = self.allocator.shrinking_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, CurrentMemoryCanNotBeMoved)
+		{
+			return Ok(reallocated)
+		}
+
+		self.move_and_quarantine(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved, non_zero_new_size)
+	}
+}
+
+impl<MS: MemorySource, LA: LocalAllocator<MS>> LocalAllocator<MS> for QuarantineAllocator<LA>
+{
+	#[inline(always)]
+	fn new_local_allocator(memory_source: MS, lifetime_hint: LifetimeHint, block_size_hint: NonZeroUsize) -> Self
+	{
+		Self::new(LA::new_local_allocator(memory_source, lifetime_hint, block_size_hint), Self::DefaultByteBudget, Self::DefaultMaximumBlocks, QuarantineUseAfterFree::abort)
+	}
+
+	#[inline(always)]
+	fn memory_range(&self) -> MemoryRange
+	{
+		self.allocator.memory_range()
+	}
+}
+
+impl<A: Allocator> QuarantineAllocator<A>
+{
+	/// Default byte budget of 64Kb.
+	pub const DefaultByteBudget: usize = 64 * 1024;
+
+	/// Default maximum number of blocks.
+	pub const DefaultMaximumBlocks: NonZeroUsize = new_non_zero_usize(1024);
+
+	/// Byte value quarantined blocks are poisoned with.
+	pub const Poison: u8 = MemoryFilling::DefaultFreedByte;
+
+	/// Create a new instance.
+	///
+	/// Allocates book-keeping for `maximum_blocks` from the current allocator in use.
+	///
+	/// `use_after_free_reporter` is called for each block found to be written to after being freed, before it is returned to the wrapped allocator; it must not allocate.
+	/// Use `QuarantineUseAfterFree::abort` to abort the process.
+	#[inline(always)]
+	pub fn new(allocator: A, byte_budget: usize, maximum_blocks: NonZeroUsize, use_after_free_reporter: fn(QuarantineUseAfterFree)) -> Self
+	{
+		Self
+		{
+			allocator,
+			byte_budget,
+			maximum_blocks: maximum_blocks.get(),
+			use_after_free_reporter,
+			quarantined_bytes: Cell::new(0),
+			quarantined_blocks: UnsafeCell::new(VecDeque::with_capacity(maximum_blocks.get())),
+		}
+	}
+
+	/// Bytes currently held in quarantine.
+	#[inline(always)]
+	pub fn quarantined_bytes(&self) -> usize
+	{
+		self.quarantined_bytes.get()
+	}
+
+	/// Number of blocks currently held in quarantine.
+	#[inline(always)]
+	pub fn quarantined_block_count(&self) -> usize
+	{
+		self.quarantined_blocks().len()
+	}
+
+	/// Verifies and returns all quarantined blocks to the wrapped allocator, oldest first.
+	#[inline(always)]
+	pub fn flush(&self)
+	{
+		while !self.quarantined_blocks().is_empty()
+		{
+			self.release_oldest()
+		}
+	}
+
+	/// Wrapped allocator.
+	#[inline(always)]
+	pub fn allocator(&self) -> &A
+	{
+		&self.allocator
+	}
+
+	#[inline(always)]
+	fn move_and_quarantine(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool, amount_to_copy: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		if unlikely!(current_memory_can_not_be_moved)
+		{
+			return Err(AllocError)
+		}
+
+		let (new_memory, actual_size) = self.allocate(non_zero_new_size, non_zero_power_of_two_new_alignment)?;
+		unsafe { new_memory.as_ptr().copy_from_nonoverlapping(current_memory.as_ptr(), amount_to_copy.get()) };
+		self.deallocate(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory);
+		Ok((new_memory, actual_size))
+	}
+
+	#[inline(always)]
+	fn release_oldest(&self)
+	{
+		let quarantined_block = match self.quarantined_blocks().pop_front()
+		{
+			None => return,
+			Some(quarantined_block) => quarantined_block,
+		};
+
+		let QuarantinedBlock { memory_address, non_zero_size, non_zero_power_of_two_alignment } = quarantined_block;
+		self.quarantined_bytes.set(self.quarantined_bytes() - non_zero_size.get());
+
+		let poisoned = unsafe { from_raw_parts(memory_address.as_ptr() as *const u8, non_zero_size.get()) };
+		if let Some(offset) = poisoned.iter().position(|&byte| byte != Self::Poison)
+		{
+			(self.use_after_free_reporter)
+			(
+				QuarantineUseAfterFree
+				{
+					memory_address,
+					size: non_zero_size,
+					offset,
+				}
+			)
+		}
+
+		self.allocator.deallocate(non_zero_size, non_zero_power_of_two_alignment, memory_address)
+	}
+
+	#[inline(always)]
+	fn quarantined_blocks(&self) -> &mut VecDeque<QuarantinedBlock>
+	{
+		unsafe { &mut * self.quarantined_blocks.get() }
+	}
+}
+
+#[cfg(test)]
+mod QuarantineAllocatorTests
+{
+	use super::*;
+	use std::cell::RefCell;
+
+	thread_local!
+	{
+		static UsesAfterFree: RefCell<Vec<QuarantineUseAfterFree>> = RefCell::new(Vec::new());
+	}
+
+	#[test]
+	pub fn freed_blocks_are_held_until_flushed()
+	{
+		let allocator = new_allocator(QuarantineAllocator::<GlobalAllocToAllocatorAdaptor<System>>::DefaultByteBudget, 16);
+
+		let (allocation, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		assert_eq!(allocator.quarantined_block_count(), 1, "Freed block was not held");
+		assert_eq!(allocator.quarantined_bytes(), 32, "Freed bytes were not held");
+
+		allocator.flush();
+		assert_eq!(allocator.quarantined_block_count(), 0, "Flush did not release held blocks");
+		assert_eq!(allocator.quarantined_bytes(), 0, "Flush did not release held bytes");
+		assert_eq!(uses_after_free(), vec![], "Block not written to after being freed was reported");
+	}
+
+	#[test]
+	pub fn write_after_free_is_reported_when_released()
+	{
+		let allocator = new_allocator(QuarantineAllocator::<GlobalAllocToAllocatorAdaptor<System>>::DefaultByteBudget, 16);
+
+		let (allocation, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		unsafe { allocation.as_ptr().add(5).write(0x00) };
+		assert_eq!(uses_after_free(), vec![], "Write after free was reported before the block was released");
+
+		allocator.flush();
+		assert_eq!(uses_after_free(), vec![use_after_free(allocation, 32, 5)], "Write after free was not reported");
+	}
+
+	#[test]
+	pub fn oldest_block_is_released_when_maximum_blocks_are_held()
+	{
+		let allocator = new_allocator(QuarantineAllocator::<GlobalAllocToAllocatorAdaptor<System>>::DefaultByteBudget, 2);
+
+		let (oldest, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate(32.non_zero(), 8.non_zero(), oldest);
+		unsafe { oldest.as_ptr().write(0x00) };
+
+		for _ in 0 .. 2
+		{
+			let (allocation, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect("Did not allocate");
+			allocator.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		}
+
+		assert_eq!(allocator.quarantined_block_count(), 2, "Held more than the maximum number of blocks");
+		assert_eq!(uses_after_free(), vec![use_after_free(oldest, 32, 0)], "Oldest block was not released and verified");
+	}
+
+	#[test]
+	pub fn oldest_block_is_released_when_byte_budget_would_be_exceeded()
+	{
+		let allocator = new_allocator(64, 16);
+
+		let (oldest, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate(32.non_zero(), 8.non_zero(), oldest);
+
+		let (allocation, _) = allocator.allocate(48.non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate(48.non_zero(), 8.non_zero(), allocation);
+
+		assert_eq!(allocator.quarantined_block_count(), 1, "Oldest block was not released");
+		assert_eq!(allocator.quarantined_bytes(), 48, "Held more than the byte budget");
+	}
+
+	#[test]
+	pub fn blocks_larger_than_byte_budget_are_not_held()
+	{
+		let allocator = new_allocator(64, 16);
+
+		let (allocation, _) = allocator.allocate(128.non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate(128.non_zero(), 8.non_zero(), allocation);
+
+		assert_eq!(allocator.quarantined_block_count(), 0, "Block larger than the byte budget was held");
+	}
+
+	#[test]
+	pub fn held_blocks_are_verified_when_dropped()
+	{
+		let allocator = new_allocator(QuarantineAllocator::<GlobalAllocToAllocatorAdaptor<System>>::DefaultByteBudget, 16);
+
+		let (allocation, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		unsafe { allocation.as_ptr().add(31).write(0x00) };
+
+		drop(allocator);
+		assert_eq!(uses_after_free(), vec![use_after_free(allocation, 32, 31)], "Write after free was not reported when dropped");
+	}
+
+	fn new_allocator(byte_budget: usize, maximum_blocks: usize) -> QuarantineAllocator<GlobalAllocToAllocatorAdaptor<System>>
+	{
+		QuarantineAllocator::new(GlobalAllocToAllocatorAdaptor(System), byte_budget, maximum_blocks.non_zero(), record_use_after_free)
+	}
+
+	fn record_use_after_free(use_after_free: QuarantineUseAfterFree)
+	{
+		UsesAfterFree.with(|uses_after_free| uses_after_free.borrow_mut().push(use_after_free))
+	}
+
+	fn uses_after_free() -> Vec<QuarantineUseAfterFree>
+	{
+		UsesAfterFree.with(|uses_after_free| take(&mut *uses_after_free.borrow_mut()))
+	}
+
+	fn use_after_free(memory_address: MemoryAddress, size: usize, offset: usize) -> QuarantineUseAfterFree
+	{
+		QuarantineUseAfterFree
+		{
+			memory_address,
+			size: size.non_zero(),
+			offset,
+		}
+	}
+}
@@ -0,0 +1,39 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A block held in the quarantine of a `QuarantineAllocator` which was found to be written to after it was freed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct QuarantineUseAfterFree
+{
+	/// Address of the freed block.
+	pub memory_address: MemoryAddress,
+
+	/// Size of the freed block.
+	pub size: NonZeroUsize,
+
+	/// Offset, relative to `memory_address`, of the first byte written to.
+	pub offset: usize,
+}
+
+impl Display for QuarantineUseAfterFree
+{
+	#[inline(always)]
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result
+	{
+		write!(f, "Freed block at {:?} of {} bytes written to after being freed at offset {}", self.memory_address, self.size, self.offset)
+	}
+}
+
+impl QuarantineUseAfterFree
+{
+	/// The default reporter of a `QuarantineAllocator`; writes a message to standard error and aborts the process.
+	///
+	/// Does not allocate or panic, as the write is usually found whilst deallocating; the message is formatted on the stack and written with `write(2)`.
+	#[cold]
+	#[inline(never)]
+	pub fn abort(self)
+	{
+		abort_with_message(format_args!("{}", self))
+	}
+}
@@ -0,0 +1,95 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// An allocator which samples allocations with a `SamplingHeapProfiler` to find which call sites hold the most memory.
+///
+/// Use it as, or to wrap, the coroutine-local or thread-local allocator of a `GlobalThreadAndCoroutineSwitchableAllocatorInstance`.
+///
+/// Wraps any `Allocator`; if that is a `LocalAllocator`, so is this, using `SamplingHeapProfiler::DefaultSamplingInterval`.
+///
+/// This allocator is not thread-safe.
+#[derive(Debug)]
+pub struct SamplingHeapProfilerAllocator<A: Allocator>
+{
+	allocator: A,
+	
+	sampling_heap_profiler: SamplingHeapProfiler,
+}
+
+impl<A: Allocator> Allocator for SamplingHeapProfilerAllocator<A>
+{
+	#[inline(always)]
+	fn allocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let (memory_address, actual_size) = self.allocator.allocate(non_zero_size, non_zero_power_of_two_alignment)?;
+		self.sampling_heap_profiler.allocated(memory_address, non_zero_size.get());
+		Ok((memory_address, actual_size))
+	}
+
+	#[inline(always)]
+	fn deallocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		self.sampling_heap_profiler.deallocated(current_memory);
+		self.allocator.deallocate(non_zero_size, non_zero_power_of_two_alignment, current_memory)
+	}
+
+	#[inline(always)]
+	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let (new_memory, actual_size) = self.allocator.growing_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)?;
+		self.sampling_heap_profiler.reallocated(current_memory, non_zero_current_size.get(), new_memory, non_zero_new_size.get());
+		Ok((new_memory, actual_size))
+	}
+
+	#[inline(always)]
+	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let (new_memory, actual_size) = self.allocator.shrinking_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)?;
+		self.sampling_heap_profiler.reallocated(current_memory, non_zero_current_size.get(), new_memory, non_zero_new_size.get());
+		Ok((new_memory, actual_size))
+	}
+}
+
+impl<MS: MemorySource, LA: LocalAllocator<MS>> LocalAllocator<MS> for SamplingHeapProfilerAllocator<LA>
+{
+	#[inline(always)]
+	fn new_local_allocator(memory_source: MS, lifetime_hint: LifetimeHint, block_size_hint: NonZeroUsize) -> Self
+	{
+		Self::new(LA::new_local_allocator(memory_source, lifetime_hint, block_size_hint), SamplingHeapProfiler::DefaultSamplingInterval)
+	}
+	
+	#[inline(always)]
+	fn memory_range(&self) -> MemoryRange
+	{
+		self.allocator.memory_range()
+	}
+}
+
+impl<A: Allocator> SamplingHeapProfilerAllocator<A>
+{
+	/// Create a new instance.
+	#[inline(always)]
+	pub fn new(allocator: A, sampling_interval: NonZeroU64) -> Self
+	{
+		Self
+		{
+			allocator,
+			sampling_heap_profiler: SamplingHeapProfiler::new(sampling_interval),
+		}
+	}
+	
+	/// Sampling heap profiler.
+	#[inline(always)]
+	pub fn sampling_heap_profiler(&self) -> &SamplingHeapProfiler
+	{
+		&self.sampling_heap_profiler
+	}
+	
+	/// Wrapped allocator.
+	#[inline(always)]
+	pub fn allocator(&self) -> &A
+	{
+		&self.allocator
+	}
+}
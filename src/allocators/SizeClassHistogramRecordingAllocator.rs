@@ -0,0 +1,99 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// An allocator which records a histogram of requested sizes and alignments.
+///
+/// Reallocations are recorded with their new size and alignment.
+/// Failed allocations are recorded, too, as they are still requests.
+///
+/// Wraps any `Allocator`; if that is a `LocalAllocator`, so is this, so it can be used as the coroutine-local or thread-local allocator of a `GlobalThreadAndCoroutineSwitchableAllocatorInstance` to record a histogram for just that tier.
+///
+/// This allocator is not thread-safe, so it must not be used as, or to wrap, the global allocator, which is used by all threads.
+#[derive(Debug)]
+pub struct SizeClassHistogramRecordingAllocator<A: Allocator>
+{
+	allocator: A,
+	
+	size_class_histogram: SizeClassHistogram,
+}
+
+impl<A: Allocator> Allocator for SizeClassHistogramRecordingAllocator<A>
+{
+	#[inline(always)]
+	fn allocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		self.size_class_histogram.record(non_zero_size, non_zero_power_of_two_alignment);
+		self.allocator.allocate(non_zero_size, non_zero_power_of_two_alignment)
+	}
+
+	#[inline(always)]
+	fn deallocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		self.allocator.deallocate(non_zero_size, non_zero_power_of_two_alignment, current_memory)
+	}
+
+	#[inline(always)]
+	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		self.size_class_histogram.record(non_zero_new_size, non_zero_power_of_two_new_alignment);
+		self.allocator.growing_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+	}
+
+	#[inline(always)]
+	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		self.size_class_histogram.record(non_zero_new_size, non_zero_power_of_two_new_alignment);
+		self.allocator.shrinking_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+	}
+}
+
+impl<MS: MemorySource, LA: LocalAllocator<MS>> LocalAllocator<MS> for SizeClassHistogramRecordingAllocator<LA>
+{
+	#[inline(always)]
+	fn new_local_allocator(memory_source: MS, lifetime_hint: LifetimeHint, block_size_hint: NonZeroUsize) -> Self
+	{
+		Self::new(LA::new_local_allocator(memory_source, lifetime_hint, block_size_hint))
+	}
+	
+	#[inline(always)]
+	fn memory_range(&self) -> MemoryRange
+	{
+		self.allocator.memory_range()
+	}
+}
+
+impl<A: Allocator> SizeClassHistogramRecordingAllocator<A>
+{
+	/// Create a new instance.
+	#[inline(always)]
+	pub const fn new(allocator: A) -> Self
+	{
+		Self
+		{
+			allocator,
+			size_class_histogram: SizeClassHistogram::new(),
+		}
+	}
+	
+	/// Size class histogram.
+	#[inline(always)]
+	pub fn size_class_histogram(&self) -> &SizeClassHistogram
+	{
+		&self.size_class_histogram
+	}
+	
+	/// Wrapped allocator.
+	#[inline(always)]
+	pub fn allocator(&self) -> &A
+	{
+		&self.allocator
+	}
+	
+	/// Into the wrapped allocator, discarding the histogram.
+	#[inline(always)]
+	pub fn into_allocator(self) -> A
+	{
+		self.allocator
+	}
+}
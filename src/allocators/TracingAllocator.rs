@@ -0,0 +1,112 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// An allocator which records every allocate, deallocate, grow and shrink as an `AllocationEvent` into the current thread's `AllocationTraceRingBuffer`, if any.
+///
+/// If the current thread is not recording (see `AllocationTraceRingBuffer::record_on_current_thread()`), the only overhead is a check of a thread-local.
+///
+/// The tier recorded is either that given to `new()` or, if none, that of the memory's registered `MemoryRangeOwner`, if any.
+///
+/// Wraps any `Allocator`; if that is a `LocalAllocator`, so is this.
+#[derive(Debug)]
+pub struct TracingAllocator<A: Allocator>
+{
+	allocator: A,
+	
+	tier: Option<CurrentAllocatorInUse>,
+}
+
+impl<A: Allocator> Allocator for TracingAllocator<A>
+{
+	#[inline(always)]
+	fn allocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let result = self.allocator.allocate(non_zero_size, non_zero_power_of_two_alignment);
+		AllocationTraceRingBuffer::record(|| AllocationEvent::new(AllocationEventKind::Allocate, self.tier(result.map(|(memory_address, _)| memory_address).ok()), non_zero_size, non_zero_power_of_two_alignment, None, result));
+		result
+	}
+
+	#[inline(always)]
+	fn deallocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		AllocationTraceRingBuffer::record(|| AllocationEvent::deallocation(self.tier(Some(current_memory)), non_zero_size, non_zero_power_of_two_alignment, current_memory));
+		self.allocator.deallocate(non_zero_size, non_zero_power_of_two_alignment, current_memory)
+	}
+
+	#[inline(always)]
+	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let tier = self.tier(Some(current_memory));
+		let result = self.allocator.growing_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved);
+		AllocationTraceRingBuffer::record(|| AllocationEvent::new(AllocationEventKind::GrowingReallocate, tier, non_zero_new_size, non_zero_power_of_two_new_alignment, Some((non_zero_current_size, current_memory)), result));
+		result
+	}
+
+	#[inline(always)]
+	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let tier = self.tier(Some(current_memory));
+		let result = self.allocator.shrinking_reallocate(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved);
+		AllocationTraceRingBuffer::record(|| AllocationEvent::new(AllocationEventKind::ShrinkingReallocate, tier, non_zero_new_size, non_zero_power_of_two_new_alignment, Some((non_zero_current_size, current_memory)), result));
+		result
+	}
+}
+
+impl<MS: MemorySource, LA: LocalAllocator<MS>> LocalAllocator<MS> for TracingAllocator<LA>
+{
+	#[inline(always)]
+	fn new_local_allocator(memory_source: MS, lifetime_hint: LifetimeHint, block_size_hint: NonZeroUsize) -> Self
+	{
+		Self::new(LA::new_local_allocator(memory_source, lifetime_hint, block_size_hint), None)
+	}
+	
+	#[inline(always)]
+	fn memory_range(&self) -> MemoryRange
+	{
+		self.allocator.memory_range()
+	}
+}
+
+impl<A: Allocator> TracingAllocator<A>
+{
+	/// Create a new instance.
+	///
+	/// If `tier` is `None`, the tier is looked up for each event, which is slower.
+	#[inline(always)]
+	pub const fn new(allocator: A, tier: Option<CurrentAllocatorInUse>) -> Self
+	{
+		Self
+		{
+			allocator,
+			tier,
+		}
+	}
+	
+	/// Wrapped allocator.
+	#[inline(always)]
+	pub fn allocator(&self) -> &A
+	{
+		&self.allocator
+	}
+	
+	/// Into the wrapped allocator.
+	#[inline(always)]
+	pub fn into_allocator(self) -> A
+	{
+		self.allocator
+	}
+	
+	#[inline(always)]
+	fn tier(&self, memory_address: Option<MemoryAddress>) -> Option<CurrentAllocatorInUse>
+	{
+		match (self.tier, memory_address)
+		{
+			(Some(tier), _) => Some(tier),
+			
+			(None, Some(memory_address)) => MemoryRangeRegistry::owner_of(memory_address).and_then(MemoryRangeOwner::tier),
+			
+			(None, None) => None,
+		}
+	}
+}
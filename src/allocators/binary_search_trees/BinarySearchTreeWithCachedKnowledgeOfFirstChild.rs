@@ -36,6 +36,18 @@ impl BinarySearchTreeWithCachedKnowledgeOfFirstChild
 		self.tree.find(key)
 	}
 
+	#[inline(always)]
+	pub(crate) fn lower_bound(&self, bound: Bound<MemoryAddress>) -> NodePointer
+	{
+		self.tree.lower_bound(bound)
+	}
+
+	#[inline(always)]
+	pub(crate) fn upper_bound(&self, bound: Bound<MemoryAddress>) -> NodePointer
+	{
+		self.tree.upper_bound(bound)
+	}
+
 	#[inline(always)]
 	pub(crate) fn blocks_to_coalesce(&mut self, inserted_node_pointer: NodePointer, difference: NonZeroUsize, block_size: NonZeroUsize, furthest_back_contiguous_with_inserted_node_pointer_memory_address: MemoryAddress, furthest_forward_contiguous_with_inserted_node_pointer_memory_address: MemoryAddress) -> (MemoryAddress, MemoryAddress)
 	{
@@ -118,6 +130,20 @@ impl BinarySearchTreeWithCachedKnowledgeOfFirstChild
 		self.tree.double_ended_iterate()
 	}
 
+	/// Walks the entire tree.
+	#[inline(always)]
+	pub(crate) fn count(&self) -> usize
+	{
+		if self.has_blocks()
+		{
+			self.double_ended_iterate().count()
+		}
+		else
+		{
+			0
+		}
+	}
+
 	#[inline(always)]
 	pub(crate) fn cached_first_child(&self) -> NodePointer
 	{
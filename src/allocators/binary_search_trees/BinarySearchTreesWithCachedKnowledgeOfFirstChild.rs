@@ -22,13 +22,7 @@ impl Debug for BinarySearchTreesWithCachedKnowledgeOfFirstChild
 			let has_blocks = binary_search_tree.has_blocks();
 			if has_blocks
 			{
-				let mut count = 0;
-				for _ in binary_search_tree.double_ended_iterate()
-				{
-					count += 1;
-				}
-
-				writeln!(f, "\t{:?} => {:?}  {:?}", block_size, count, binary_search_tree.cached_first_child().is_null())?;
+				writeln!(f, "\t{:?} => {:?}  {:?}", block_size, binary_search_tree.count(), binary_search_tree.cached_first_child().is_null())?;
 			}
 		}
 		Ok(())
@@ -139,6 +133,26 @@ impl BinarySearchTreesWithCachedKnowledgeOfFirstChild
 		self.binary_search_trees_of_free_blocks_sorted_by_ascending_memory_address_and_indexed_by_power_of_two_exponent_less_smallest_power_of_two.get_unchecked_safe(binary_search_tree_index).get().mutable_reference()
 	}
 
+	/// Walks every binary search tree; not cheap.
+	#[inline(always)]
+	pub(crate) fn free_space_report(&self) -> FreeSpaceReport
+	{
+		let mut free_space_report = FreeSpaceReport::default();
+		for binary_search_tree_index in 0 .. Self::NumberOfBinarySearchTrees
+		{
+			let block_size = Self::binary_search_tree_index_to_block_size(binary_search_tree_index);
+			let free_block_count = self.binary_search_tree_for(binary_search_tree_index).count();
+
+			free_space_report.size_classes[binary_search_tree_index] = FreeSpaceReportSizeClass
+			{
+				block_size,
+				free_block_count,
+				free_bytes: block_size * free_block_count,
+			};
+		}
+		free_space_report
+	}
+
 	#[inline(always)]
 	pub(crate) fn smallest_power_of_two_difference(difference: usize) -> NonZeroUsize
 	{
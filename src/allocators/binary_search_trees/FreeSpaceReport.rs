@@ -0,0 +1,55 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A report of the free space of a `MultipleBinarySearchTreeAllocator`.
+///
+/// Obtained using `MultipleBinarySearchTreeAllocator::free_space_report()`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FreeSpaceReport
+{
+	/// Free blocks for each size class, in ascending order of block size.
+	pub size_classes: [FreeSpaceReportSizeClass; BinarySearchTreesWithCachedKnowledgeOfFirstChild::NumberOfBinarySearchTrees],
+}
+
+impl FreeSpaceReport
+{
+	/// Total number of free blocks.
+	#[inline(always)]
+	pub fn free_block_count(&self) -> usize
+	{
+		self.size_classes.iter().map(|size_class| size_class.free_block_count).sum()
+	}
+
+	/// Total free bytes.
+	#[inline(always)]
+	pub fn free_bytes(&self) -> usize
+	{
+		self.size_classes.iter().map(|size_class| size_class.free_bytes).sum()
+	}
+
+	/// The size of the largest free block, if any.
+	///
+	/// An allocation of this size or less will succeed, as long as its alignment does not exceed the block size.
+	/// Only contiguous free blocks of the same size are coalesced, so a larger allocation can fail even when contiguous free memory is larger (eg a free 32b block next to a free 64b block does not satisfy a 96b allocation).
+	#[inline(always)]
+	pub fn largest_free_block(&self) -> Option<NonZeroUsize>
+	{
+		self.size_classes.iter().rev().find(|size_class| size_class.free_block_count != 0).map(|size_class| size_class.block_size.non_zero())
+	}
+
+	/// Fragmentation ratio, from `0.0` (all free space is in the largest free block, or there is no free space) to nearly `1.0` (free space is split into many small blocks).
+	///
+	/// Calculated as `1 - (largest free block / free bytes)`; free blocks are not necessarily coalesced (see `largest_free_block()`), so free blocks that are contiguous count as fragmented.
+	#[inline(always)]
+	pub fn fragmentation_ratio(&self) -> f64
+	{
+		let free_bytes = self.free_bytes();
+		match self.largest_free_block()
+		{
+			None => 0.0,
+
+			Some(largest_free_block) => 1.0 - (largest_free_block.get() as f64) / (free_bytes as f64),
+		}
+	}
+}
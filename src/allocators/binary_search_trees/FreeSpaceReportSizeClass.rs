@@ -0,0 +1,17 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Free blocks of one size class in a `FreeSpaceReport`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FreeSpaceReportSizeClass
+{
+	/// Block size; always a power of two.
+	pub block_size: usize,
+
+	/// Number of free blocks.
+	pub free_block_count: usize,
+
+	/// Free bytes, viz `block_size * free_block_count`.
+	pub free_bytes: usize,
+}
@@ -15,10 +15,16 @@
 ///
 /// This allocator NEVER grows or shrinks its memory region.
 ///
+/// Memory can be filled when allocated and poisoned when freed using `set_memory_filling()`; freed memory is poisoned before its block is inserted into a binary search tree, so the `Node` stored in place at the start of a free block is never overwritten.
+///
+/// Deallocations can be validated using `set_invalid_deallocation_reporter()`.
+///
 /// This allocator is not thread-safe.
 pub struct MultipleBinarySearchTreeAllocator<MS: MemorySource>
 {
 	inner: BinarySearchTreesWithCachedKnowledgeOfFirstChild,
+	memory_filling: Cell<MemoryFilling>,
+	invalid_deallocation_reporter: Cell<Option<fn(MultipleBinarySearchTreeAllocatorInvalidDeallocation)>>,
 	memory_source: MS,
 }
 
@@ -35,6 +41,213 @@ impl<MS: MemorySource> Allocator for MultipleBinarySearchTreeAllocator<MS>
 {
 	#[inline(always)]
 	fn allocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let (memory_address, block_size) = self.allocate_block(non_zero_size, non_zero_power_of_two_alignment)?;
+		self.memory_filling().fill_allocated(memory_address, non_zero_size.get());
+		Ok((memory_address, block_size))
+	}
+
+	#[inline(always)]
+	fn deallocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		if unlikely!(self.is_invalid_deallocation(non_zero_size, non_zero_power_of_two_alignment, current_memory))
+		{
+			return
+		}
+
+		self.memory_filling().poison_freed(current_memory, non_zero_size.get());
+		self.deallocate_block(non_zero_size, non_zero_power_of_two_alignment, current_memory)
+	}
+
+	#[inline(always)]
+	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		debug_assert!(non_zero_new_size > non_zero_current_size, "non_zero_new_size `{}` should be greater than non_zero_current_size `{}`", non_zero_new_size, non_zero_current_size);
+		
+		if unlikely!(self.is_invalid_deallocation(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory))
+		{
+			return Err(AllocError)
+		}
+		
+		if Self::new_alignment_can_be_accommodated(non_zero_power_of_two_new_alignment, current_memory)
+		{
+			let old_block_size = Self::block_size(non_zero_current_size);
+			let new_block_size = Self::block_size(non_zero_new_size);
+			
+			// (1) Satisfy from within existing block.
+			if new_block_size == old_block_size
+			{
+				self.memory_filling().fill_grown(current_memory, non_zero_current_size, non_zero_new_size);
+				return Ok((current_memory, new_block_size.get()))
+			}
+			
+			// (2) For a simple doubling, it can be more efficient to try to coalesce two blocks.
+			//
+			// This technique could work for other approaches, eg quadrupling, but it becomes a lot more complex - and the gain over an efficient memory copy is probably lost.
+			// However, quadrupling does allow for broader `current_memory_can_not_be_moved` support to reallocate-in-place.
+			if new_block_size == old_block_size.doubled()
+			{
+				let binary_search_tree = self.binary_search_tree_for_block_size(old_block_size);
+				let contiguous_block_node_pointer = binary_search_tree.find(current_memory.add_non_zero(old_block_size));
+				if contiguous_block_node_pointer.is_not_null()
+				{
+					let is_first_child = contiguous_block_node_pointer == binary_search_tree.cached_first_child();
+					binary_search_tree.remove(contiguous_block_node_pointer, is_first_child);
+					
+					self.memory_filling().fill_grown(current_memory, non_zero_current_size, non_zero_new_size);
+					return Ok((current_memory, new_block_size.get()))
+				}
+			}
+		}
+		
+		// (3) Allocate a new block and copy over data.
+		self.allocate_and_copy(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved, non_zero_current_size.get())
+	}
+	
+	#[inline(always)]
+	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		debug_assert!(non_zero_new_size < non_zero_current_size, "non_zero_new_size `{}` should be less than non_zero_current_size `{}`", non_zero_new_size, non_zero_current_size);
+		
+		if unlikely!(self.is_invalid_deallocation(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory))
+		{
+			return Err(AllocError)
+		}
+		
+		if Self::new_alignment_can_be_accommodated(non_zero_power_of_two_new_alignment, current_memory)
+		{
+			let old_block_size = Self::block_size(non_zero_current_size);
+			let new_block_size = Self::block_size(non_zero_new_size);
+			
+			self.memory_filling().poison_shrunk(current_memory, non_zero_current_size, non_zero_new_size);
+			self.split_up_block(current_memory.add_non_zero(new_block_size), current_memory.add_non_zero(old_block_size));
+			
+			Ok((current_memory, new_block_size.get()))
+		}
+		else
+		{
+			self.allocate_and_copy(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved, non_zero_new_size.get())
+		}
+	}
+}
+
+impl<MS: MemorySource> LocalAllocator<MS> for MultipleBinarySearchTreeAllocator<MS>
+{
+	#[inline(always)]
+	fn new_local_allocator(memory_source: MS, _lifetime_hint: LifetimeHint, _block_size_hint: NonZeroUsize) -> Self
+	{
+		Self::new(memory_source)
+	}
+	
+	#[inline(always)]
+	fn memory_range(&self) -> MemoryRange
+	{
+		self.memory_source.memory_range()
+	}
+}
+
+impl<MS: MemorySource> MultipleBinarySearchTreeAllocator<MS>
+{
+	/// If the provided memory's length is not a multiple of 2, then the remainder is unused.
+	///
+	/// The provided memory must be at least as long as the minimum block size.
+	///
+	/// The memory must be aligned to `BinarySearchTreesWithCachedKnowledgeOfFirstChild::MinimumAlignment`, which is the same as the size of a `Node`.
+	pub fn new(memory_source: MS) -> Self
+	{
+		debug_assert_ne!(BinarySearchTreesWithCachedKnowledgeOfFirstChild::NumberOfBinarySearchTrees, 0, "There must be at least one binary search tree");
+
+		let mut memory_address = memory_source.allocations_start_from();
+		debug_assert!(memory_address.is_aligned_to(BinarySearchTreesWithCachedKnowledgeOfFirstChild::MinimumAlignment), "memory is not aligned to `{:?}`", BinarySearchTreesWithCachedKnowledgeOfFirstChild::MinimumAlignment);
+
+		let this = Self
+		{
+			inner: BinarySearchTreesWithCachedKnowledgeOfFirstChild::default(),
+			memory_filling: Cell::new(MemoryFilling::Off),
+			invalid_deallocation_reporter: Cell::new(None),
+			memory_source,
+		};
+
+		let mut size = this.memory_source.size().get();
+		let mut last_binary_search_tree_index = BinarySearchTreesWithCachedKnowledgeOfFirstChild::NumberOfBinarySearchTrees;
+		while likely!(last_binary_search_tree_index > 0)
+		{
+			let binary_search_tree_index = last_binary_search_tree_index - 1;
+
+			let block_size = BinarySearchTreesWithCachedKnowledgeOfFirstChild::binary_search_tree_index_to_block_size(binary_search_tree_index);
+
+			if unlikely!(size < block_size)
+			{
+				if unlikely!(BinarySearchTreesWithCachedKnowledgeOfFirstChild::size_is_less_than_minimum_allocation_size(size))
+				{
+					break
+				}
+
+				last_binary_search_tree_index = binary_search_tree_index;
+				continue
+			}
+
+			let binary_search_tree = this.binary_search_tree_for(binary_search_tree_index);
+			while
+			{
+				binary_search_tree.insert_memory_address(memory_address);
+
+				memory_address.add_assign(block_size);
+				size -= block_size;
+
+				likely!(size >= block_size)
+			}
+			{
+			}
+			last_binary_search_tree_index = binary_search_tree_index;
+		}
+		
+		this
+	}
+
+	/// Memory filling.
+	#[inline(always)]
+	pub fn memory_filling(&self) -> MemoryFilling
+	{
+		self.memory_filling.get()
+	}
+
+	/// Changes memory filling; memory already allocated or freed is not affected.
+	#[inline(always)]
+	pub fn set_memory_filling(&self, memory_filling: MemoryFilling)
+	{
+		self.memory_filling.set(memory_filling)
+	}
+
+	/// Reporter of invalid deallocations, if validating.
+	#[inline(always)]
+	pub fn invalid_deallocation_reporter(&self) -> Option<fn(MultipleBinarySearchTreeAllocatorInvalidDeallocation)>
+	{
+		self.invalid_deallocation_reporter.get()
+	}
+
+	/// Validates every deallocation and the current memory of every reallocation if `Some`: the block must lie inside `memory_range()`, be aligned to the minimum block size, and neither be nor overlap a free block of any size.
+	///
+	/// Invalid deallocations are passed to the reporter and ignored, and invalid reallocations are passed to the reporter and fail, rather than corrupting the binary search trees; the reporter must not allocate from this allocator.
+	///
+	/// Validation searches the binary search tree of every block size, so is relatively expensive.
+	#[inline(always)]
+	pub fn set_invalid_deallocation_reporter(&self, invalid_deallocation_reporter: Option<fn(MultipleBinarySearchTreeAllocatorInvalidDeallocation)>)
+	{
+		self.invalid_deallocation_reporter.set(invalid_deallocation_reporter)
+	}
+
+	/// A report of free blocks by size class, with the largest free block and a fragmentation ratio.
+	///
+	/// Walks every free block, so is relatively expensive; intended for periodic monitoring, eg of long-lived contexts.
+	#[inline(always)]
+	pub fn free_space_report(&self) -> FreeSpaceReport
+	{
+		self.inner.free_space_report()
+	}
+
+	#[inline(always)]
+	fn allocate_block(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
 	{
 		macro_rules! try_to_allocate_exact_size_block
 		{
@@ -137,7 +350,7 @@ impl<MS: MemorySource> Allocator for MultipleBinarySearchTreeAllocator<MS>
 	}
 
 	#[inline(always)]
-	fn deallocate(&self, non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	fn deallocate_block(&self, non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
 	{
 		let block_size = Self::block_size(non_zero_size);
 
@@ -153,135 +366,82 @@ impl<MS: MemorySource> Allocator for MultipleBinarySearchTreeAllocator<MS>
 		}
 	}
 
+	/// Reports the deallocation if it is invalid and deallocations are validated.
 	#[inline(always)]
-	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+	fn is_invalid_deallocation(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: MemoryAddress) -> bool
 	{
-		debug_assert!(non_zero_new_size > non_zero_current_size, "non_zero_new_size `{}` should be greater than non_zero_current_size `{}`", non_zero_new_size, non_zero_current_size);
-		
-		if Self::new_alignment_can_be_accommodated(non_zero_power_of_two_new_alignment, current_memory)
+		if let Some(invalid_deallocation_reporter) = self.invalid_deallocation_reporter.get()
 		{
-			let old_block_size = Self::block_size(non_zero_current_size);
-			let new_block_size = Self::block_size(non_zero_new_size);
-			
-			// (1) Satisfy from within existing block.
-			if new_block_size == old_block_size
-			{
-				return Ok((current_memory, new_block_size.get()))
-			}
-			
-			// (2) For a simple doubling, it can be more efficient to try to coalesce two blocks.
-			//
-			// This technique could work for other approaches, eg quadrupling, but it becomes a lot more complex - and the gain over an efficient memory copy is probably lost.
-			// However, quadrupling does allow for broader `current_memory_can_not_be_moved` support to reallocate-in-place.
-			if new_block_size == old_block_size.doubled()
+			if let Err(kind) = self.validate_deallocation(non_zero_size, non_zero_power_of_two_alignment, current_memory)
 			{
-				let binary_search_tree = self.binary_search_tree_for_block_size(old_block_size);
-				let contiguous_block_node_pointer = binary_search_tree.find(current_memory.add_non_zero(old_block_size));
-				if contiguous_block_node_pointer.is_not_null()
-				{
-					let is_first_child = contiguous_block_node_pointer == binary_search_tree.cached_first_child();
-					binary_search_tree.remove(contiguous_block_node_pointer, is_first_child);
-					
-					return Ok((current_memory, new_block_size.get()))
-				}
+				invalid_deallocation_reporter
+				(
+					MultipleBinarySearchTreeAllocatorInvalidDeallocation
+					{
+						memory_address: current_memory,
+						size: non_zero_size,
+						kind,
+					}
+				);
+				return true
 			}
 		}
-		
-		// (3) Allocate a new block and copy over data.
-		self.allocate_and_copy(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved, non_zero_current_size.get())
+		false
 	}
-	
-	#[inline(always)]
-	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
+
+	/// Blocks are split and coalesced without regard to buddy alignment, so a block is not necessarily aligned to its own size; it is only guaranteed to be aligned to the minimum block size and to the alignment it was allocated with.
+	fn validate_deallocation(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: MemoryAddress) -> Result<(), MultipleBinarySearchTreeAllocatorInvalidDeallocationKind>
 	{
-		debug_assert!(non_zero_new_size < non_zero_current_size, "non_zero_new_size `{}` should be less than non_zero_current_size `{}`", non_zero_new_size, non_zero_current_size);
-		
-		if Self::new_alignment_can_be_accommodated(non_zero_power_of_two_new_alignment, current_memory)
+		use self::MultipleBinarySearchTreeAllocatorInvalidDeallocationKind::*;
+
+		if unlikely!(BinarySearchTreesWithCachedKnowledgeOfFirstChild::size_exceeds_maximum_allocation_size(non_zero_size))
 		{
-			let old_block_size = Self::block_size(non_zero_current_size);
-			let new_block_size = Self::block_size(non_zero_new_size);
-			
-			self.split_up_block(current_memory.add_non_zero(new_block_size), current_memory.add_non_zero(old_block_size));
-			
-			Ok((current_memory, new_block_size.get()))
+			return Err(OutsideMemoryRange)
 		}
-		else
+
+		let block_size = Self::block_size(non_zero_size);
+		let memory_range = self.memory_range();
+		let end_of_block = match current_memory.checked_add(block_size.get())
 		{
-			self.allocate_and_copy(non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved, non_zero_new_size.get())
+			None => return Err(OutsideMemoryRange),
+			Some(end_of_block) => end_of_block,
+		};
+		if unlikely!(current_memory < memory_range.from || end_of_block > memory_range.to)
+		{
+			return Err(OutsideMemoryRange)
 		}
-	}
-}
 
-impl<MS: MemorySource> LocalAllocator<MS> for MultipleBinarySearchTreeAllocator<MS>
-{
-	#[inline(always)]
-	fn new_local_allocator(memory_source: MS, _lifetime_hint: LifetimeHint, _block_size_hint: NonZeroUsize) -> Self
-	{
-		Self::new(memory_source)
-	}
-	
-	#[inline(always)]
-	fn memory_range(&self) -> MemoryRange
-	{
-		self.memory_source.memory_range()
-	}
-}
-
-impl<MS: MemorySource> MultipleBinarySearchTreeAllocator<MS>
-{
-	/// If the provided memory's length is not a multiple of 2, then the remainder is unused.
-	///
-	/// The provided memory must be at least as long as the minimum block size.
-	///
-	/// The memory must be aligned to `BinarySearchTreesWithCachedKnowledgeOfFirstChild::MinimumAlignment`, which is the same as the size of a `Node`.
-	pub fn new(memory_source: MS) -> Self
-	{
-		debug_assert_ne!(BinarySearchTreesWithCachedKnowledgeOfFirstChild::NumberOfBinarySearchTrees, 0, "There must be at least one binary search tree");
-
-		let mut memory_address = memory_source.allocations_start_from();
-		debug_assert!(memory_address.is_aligned_to(BinarySearchTreesWithCachedKnowledgeOfFirstChild::MinimumAlignment), "memory is not aligned to `{:?}`", BinarySearchTreesWithCachedKnowledgeOfFirstChild::MinimumAlignment);
-
-		let this = Self
+		if unlikely!(!current_memory.is_aligned_to(BinarySearchTreesWithCachedKnowledgeOfFirstChild::floor_alignment_to_minimum(non_zero_power_of_two_alignment)))
 		{
-			inner: BinarySearchTreesWithCachedKnowledgeOfFirstChild::default(),
-			memory_source,
-		};
+			return Err(Misaligned)
+		}
 
-		let mut size = this.memory_source.size().get();
-		let mut last_binary_search_tree_index = BinarySearchTreesWithCachedKnowledgeOfFirstChild::NumberOfBinarySearchTrees;
-		while likely!(last_binary_search_tree_index > 0)
+		if unlikely!(self.binary_search_tree_for_block_size(block_size).find(current_memory).is_not_null())
 		{
-			let binary_search_tree_index = last_binary_search_tree_index - 1;
+			return Err(AlreadyFree)
+		}
 
-			let block_size = BinarySearchTreesWithCachedKnowledgeOfFirstChild::binary_search_tree_index_to_block_size(binary_search_tree_index);
+		for binary_search_tree_index in 0 .. BinarySearchTreesWithCachedKnowledgeOfFirstChild::NumberOfBinarySearchTrees
+		{
+			let binary_search_tree = self.binary_search_tree_for(binary_search_tree_index);
+			let free_block_size = BinarySearchTreesWithCachedKnowledgeOfFirstChild::binary_search_tree_index_to_block_size(binary_search_tree_index);
 
-			if unlikely!(size < block_size)
+			// A free block starting at or after the start of the block, but before its end.
+			let at_or_after = binary_search_tree.lower_bound(Included(current_memory));
+			if at_or_after.is_not_null() && at_or_after.value() < end_of_block
 			{
-				if unlikely!(BinarySearchTreesWithCachedKnowledgeOfFirstChild::size_is_less_than_minimum_allocation_size(size))
-				{
-					break
-				}
-
-				last_binary_search_tree_index = binary_search_tree_index;
-				continue
+				return Err(OverlapsFreeBlock { free_block: at_or_after.value(), free_block_size: free_block_size.non_zero() })
 			}
 
-			let binary_search_tree = this.binary_search_tree_for(binary_search_tree_index);
-			while
-			{
-				binary_search_tree.insert_memory_address(memory_address);
-
-				memory_address.add_assign(block_size);
-				size -= block_size;
-
-				likely!(size >= block_size)
-			}
+			// A free block starting before the block, but ending after its start; free blocks of the same size never overlap, so only the nearest needs to be checked.
+			let before = binary_search_tree.upper_bound(Excluded(current_memory));
+			if before.is_not_null() && before.value().add(free_block_size) > current_memory
 			{
+				return Err(OverlapsFreeBlock { free_block: before.value(), free_block_size: free_block_size.non_zero() })
 			}
-			last_binary_search_tree_index = binary_search_tree_index;
 		}
-		
-		this
+
+		Ok(())
 	}
 
 	#[inline(always)]
@@ -292,7 +452,7 @@ impl<MS: MemorySource> MultipleBinarySearchTreeAllocator<MS>
 		{
 			let smallest_power_of_two_difference = BinarySearchTreesWithCachedKnowledgeOfFirstChild::smallest_power_of_two_difference(difference);
 
-			self.deallocate(smallest_power_of_two_difference, smallest_power_of_two_difference, from);
+			self.deallocate_block(smallest_power_of_two_difference, smallest_power_of_two_difference, from);
 
 			from.add_assign_non_zero(smallest_power_of_two_difference);
 			difference -= smallest_power_of_two_difference.get();
@@ -333,7 +493,7 @@ impl<MS: MemorySource> MultipleBinarySearchTreeAllocator<MS>
 			let smallest_power_of_two_difference = BinarySearchTreesWithCachedKnowledgeOfFirstChild::smallest_power_of_two_difference(difference);
 			debug_assert_ne!(smallest_power_of_two_difference, block_size, "difference should never be block_size");
 
-			self.deallocate(smallest_power_of_two_difference, smallest_power_of_two_difference, from);
+			self.deallocate_block(smallest_power_of_two_difference, smallest_power_of_two_difference, from);
 
 			from.add_assign_non_zero(smallest_power_of_two_difference);
 			difference -= smallest_power_of_two_difference.get();
@@ -377,7 +537,8 @@ impl<MS: MemorySource> MultipleBinarySearchTreeAllocator<MS>
 		
 		let (block_to_copy_into, new_block_size) = self.allocate(non_zero_new_size, non_zero_power_of_two_new_alignment)?;
 		unsafe { current_memory.as_ptr().copy_to_nonoverlapping(block_to_copy_into.as_ptr(), amount_to_copy) };
-		self.deallocate(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory);
+		self.memory_filling().poison_freed(current_memory, non_zero_current_size.get());
+		self.deallocate_block(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory);
 		Ok((block_to_copy_into, new_block_size))
 	}
 }
@@ -388,6 +549,12 @@ mod MultipleBinarySearchTreeAllocatorTests
 	use super::*;
 	use crate::allocators::binary_search_trees::BinarySearchTreesWithCachedKnowledgeOfFirstChild;
 	use crate::memory_sources::mmap::MemoryMapSource;
+	use std::cell::RefCell;
+
+	thread_local!
+	{
+		static InvalidDeallocations: RefCell<Vec<MultipleBinarySearchTreeAllocatorInvalidDeallocation>> = RefCell::new(Vec::new());
+	}
 
 	#[test]
 	pub fn repeated_small_allocations()
@@ -474,6 +641,171 @@ mod MultipleBinarySearchTreeAllocatorTests
 		assert_allocator_is_empty(&allocator);
 	}
 
+	#[test]
+	pub fn double_free_is_reported()
+	{
+		let allocator = new_validating_allocator(64);
+
+		let (allocation, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		let _other_allocation = allocator.allocate(32.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		allocator.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		assert_eq!(invalid_deallocations(), vec![], "Valid deallocation was reported");
+
+		allocator.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(allocation, 32, MultipleBinarySearchTreeAllocatorInvalidDeallocationKind::AlreadyFree)], "Double free was not reported");
+
+		let _allocation = allocator.allocate(32.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		assert_allocator_is_empty(&allocator);
+	}
+
+	#[test]
+	pub fn double_free_of_coalesced_block_is_reported_as_overlap()
+	{
+		let allocator = new_validating_allocator(256);
+
+		let (allocation, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		allocator.deallocate(32.non_zero(), 8.non_zero(), allocation);
+		allocator.deallocate(32.non_zero(), 8.non_zero(), allocation);
+
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(allocation, 32, MultipleBinarySearchTreeAllocatorInvalidDeallocationKind::OverlapsFreeBlock { free_block: allocation, free_block_size: 256.non_zero() })], "Double free of a coalesced block was not reported");
+
+		let _allocation = allocator.allocate(256.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		assert_allocator_is_empty(&allocator);
+	}
+
+	#[test]
+	pub fn misaligned_deallocation_is_reported()
+	{
+		let allocator = new_validating_allocator(64);
+
+		let (allocation, _) = allocator.allocate(64.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		let misaligned = allocation.add(8);
+		allocator.deallocate(32.non_zero(), 8.non_zero(), misaligned);
+
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(misaligned, 32, MultipleBinarySearchTreeAllocatorInvalidDeallocationKind::Misaligned)], "Misaligned deallocation was not reported");
+		assert_allocator_is_empty(&allocator);
+	}
+
+	#[test]
+	pub fn deallocation_misaligned_for_its_alignment_is_reported()
+	{
+		let allocator = new_validating_allocator(128);
+
+		let (allocation, _) = allocator.allocate(64.non_zero(), 64.non_zero()).expect(&format!("Did not allocate"));
+		let _other_allocation = allocator.allocate(64.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		let misaligned = allocation.add(32);
+		allocator.deallocate(32.non_zero(), 64.non_zero(), misaligned);
+
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(misaligned, 32, MultipleBinarySearchTreeAllocatorInvalidDeallocationKind::Misaligned)], "Deallocation misaligned for its alignment was not reported");
+		assert_allocator_is_empty(&allocator);
+	}
+
+	#[test]
+	pub fn moved_growing_reallocation_is_valid()
+	{
+		let allocator = new_validating_allocator(128);
+
+		let (allocation, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		let _other_allocation = allocator.allocate(32.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+
+		let (reallocation, _) = allocator.growing_reallocate(64.non_zero(), 8.non_zero(), 32.non_zero(), 8.non_zero(), allocation, false).expect(&format!("Did not reallocate"));
+		assert_ne!(reallocation, allocation, "Reallocation was not moved");
+		assert_eq!(invalid_deallocations(), vec![], "Moved reallocation was reported as invalid");
+
+		let (allocation_of_moved_from, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect(&format!("Moved from block was not freed"));
+		assert_eq!(allocation_of_moved_from, allocation, "Moved from block was not freed");
+		assert_allocator_is_empty(&allocator);
+	}
+
+	#[test]
+	pub fn growing_reallocation_of_freed_memory_is_reported_and_fails()
+	{
+		let allocator = new_validating_allocator(64);
+
+		let (allocation, _) = allocator.allocate(32.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		let _other_allocation = allocator.allocate(32.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		allocator.deallocate(32.non_zero(), 8.non_zero(), allocation);
+
+		assert_eq!(allocator.growing_reallocate(64.non_zero(), 8.non_zero(), 32.non_zero(), 8.non_zero(), allocation, false), Err(AllocError), "Growing reallocation of freed memory did not fail");
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(allocation, 32, MultipleBinarySearchTreeAllocatorInvalidDeallocationKind::AlreadyFree)], "Growing reallocation of freed memory was not reported");
+
+		let _allocation = allocator.allocate(32.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		assert_allocator_is_empty(&allocator);
+	}
+
+	#[test]
+	pub fn shrinking_reallocation_of_freed_memory_is_reported_and_fails()
+	{
+		let allocator = new_validating_allocator(128);
+
+		let (allocation, _) = allocator.allocate(64.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		let _other_allocation = allocator.allocate(64.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		allocator.deallocate(64.non_zero(), 8.non_zero(), allocation);
+
+		assert_eq!(allocator.shrinking_reallocate(32.non_zero(), 8.non_zero(), 64.non_zero(), 8.non_zero(), allocation, false), Err(AllocError), "Shrinking reallocation of freed memory did not fail");
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(allocation, 64, MultipleBinarySearchTreeAllocatorInvalidDeallocationKind::AlreadyFree)], "Shrinking reallocation of freed memory was not reported");
+
+		let _allocation = allocator.allocate(64.non_zero(), 8.non_zero()).expect(&format!("Did not allocate"));
+		assert_allocator_is_empty(&allocator);
+	}
+
+	#[test]
+	pub fn free_space_report_of_fragmented_heap()
+	{
+		let allocator = new_allocator(256);
+
+		let free_space_report = allocator.free_space_report();
+		assert_eq!(free_space_report.free_block_count(), 1, "Unused heap was not one free block");
+		assert_eq!(free_space_report.free_bytes(), 256, "Unused heap was not all free");
+		assert_eq!(free_space_report.largest_free_block(), Some(256.non_zero()), "Unused heap was not one largest free block");
+		assert_eq!(free_space_report.fragmentation_ratio(), 0.0, "Unused heap was fragmented");
+
+		let mut allocations = (0 .. 256 / SmallestAllocation).map(|_| allocator.allocate(SmallestAllocation.non_zero(), 8.non_zero()).expect(&format!("Did not allocate")).0).collect::<Vec<_>>();
+		assert_allocator_is_empty(&allocator);
+		assert_eq!(allocator.free_space_report().largest_free_block(), None, "Full heap had a free block");
+		assert_eq!(allocator.free_space_report().fragmentation_ratio(), 0.0, "Full heap was fragmented");
+
+		allocations.sort();
+		for allocation in allocations.iter().step_by(2)
+		{
+			allocator.deallocate(SmallestAllocation.non_zero(), 8.non_zero(), *allocation);
+		}
+
+		let free_space_report = allocator.free_space_report();
+		assert_eq!(free_space_report.free_block_count(), 4, "Every other block was not free");
+		assert_eq!(free_space_report.free_bytes(), 128, "Half of the heap was not free");
+		assert_eq!(free_space_report.largest_free_block(), Some(SmallestAllocation.non_zero()), "Largest free block was not the smallest block size");
+		assert_eq!(free_space_report.fragmentation_ratio(), 0.75, "Fragmentation ratio was not 1 - 32 / 128");
+		assert_eq!(allocator.allocate((SmallestAllocation * 2).non_zero(), 8.non_zero()), Err(AllocError), "Allocated a block larger than the largest free block");
+	}
+
+	#[test]
+	pub fn poisoning_freed_blocks_does_not_corrupt_node_headers()
+	{
+		let allocator = new_allocator(256);
+		allocator.set_memory_filling(MemoryFilling::On);
+
+		let mut allocations = (0 .. 256 / SmallestAllocation).map(|_| allocator.allocate(SmallestAllocation.non_zero(), 8.non_zero()).expect(&format!("Did not allocate")).0).collect::<Vec<_>>();
+		allocations.sort();
+		let freed = allocations.iter().step_by(2).cloned().collect::<Vec<_>>();
+		for &allocation in freed.iter()
+		{
+			assert_eq!(bytes(allocation, SmallestAllocation), vec![MemoryFilling::DefaultAllocatedByte; SmallestAllocation], "Did not fill allocated memory");
+			allocator.deallocate(SmallestAllocation.non_zero(), 8.non_zero(), allocation);
+			assert_eq!(bytes(allocation.add(size_of::<Node>()), SmallestAllocation - size_of::<Node>()), vec![MemoryFilling::DefaultFreedByte; SmallestAllocation - size_of::<Node>()], "Did not poison freed memory after the Node header");
+		}
+		assert_eq!(allocator.free_space_report().free_block_count(), freed.len(), "Free blocks were lost");
+
+		let mut reallocations = (0 .. freed.len()).map(|_| allocator.allocate(SmallestAllocation.non_zero(), 8.non_zero()).expect(&format!("Did not reallocate a freed block")).0).collect::<Vec<_>>();
+		reallocations.sort();
+		assert_eq!(reallocations, freed, "Did not reallocate the freed blocks");
+		for &reallocation in reallocations.iter()
+		{
+			assert_eq!(bytes(reallocation, SmallestAllocation), vec![MemoryFilling::DefaultAllocatedByte; SmallestAllocation], "Did not fill reallocated memory over the Node header");
+		}
+		assert_allocator_is_empty(&allocator);
+	}
+
 	fn test_repeated_small_allocations(memory_size: usize)
 	{
 		let allocator = new_allocator(memory_size);
@@ -497,5 +829,37 @@ mod MultipleBinarySearchTreeAllocatorTests
 		allocator
 	}
 
+	fn new_validating_allocator(memory_size: usize) -> MultipleBinarySearchTreeAllocator<MemoryMapSource>
+	{
+		let allocator = new_allocator(memory_size);
+		allocator.set_invalid_deallocation_reporter(Some(record_invalid_deallocation));
+		allocator
+	}
+
+	fn record_invalid_deallocation(invalid_deallocation: MultipleBinarySearchTreeAllocatorInvalidDeallocation)
+	{
+		InvalidDeallocations.with(|invalid_deallocations| invalid_deallocations.borrow_mut().push(invalid_deallocation))
+	}
+
+	fn invalid_deallocations() -> Vec<MultipleBinarySearchTreeAllocatorInvalidDeallocation>
+	{
+		InvalidDeallocations.with(|invalid_deallocations| take(&mut *invalid_deallocations.borrow_mut()))
+	}
+
+	fn invalid_deallocation(memory_address: MemoryAddress, size: usize, kind: MultipleBinarySearchTreeAllocatorInvalidDeallocationKind) -> MultipleBinarySearchTreeAllocatorInvalidDeallocation
+	{
+		MultipleBinarySearchTreeAllocatorInvalidDeallocation
+		{
+			memory_address,
+			size: size.non_zero(),
+			kind,
+		}
+	}
+
+	fn bytes(memory_address: MemoryAddress, size: usize) -> Vec<u8>
+	{
+		unsafe { from_raw_parts(memory_address.as_ptr() as *const u8, size) }.to_vec()
+	}
+
 	const SmallestAllocation: usize = BinarySearchTreesWithCachedKnowledgeOfFirstChild::MinimumAllocationSize.get();
 }
@@ -0,0 +1,28 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// An invalid deallocation found by a `MultipleBinarySearchTreeAllocator` in validating mode; the binary search trees are left unchanged.
+///
+/// See `MultipleBinarySearchTreeAllocator::set_invalid_deallocation_reporter()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MultipleBinarySearchTreeAllocatorInvalidDeallocation
+{
+	/// Memory address passed to `deallocate()`.
+	pub memory_address: MemoryAddress,
+
+	/// Size passed to `deallocate()`.
+	pub size: NonZeroUsize,
+
+	/// Why the deallocation was invalid.
+	pub kind: MultipleBinarySearchTreeAllocatorInvalidDeallocationKind,
+}
+
+impl Display for MultipleBinarySearchTreeAllocatorInvalidDeallocation
+{
+	#[inline(always)]
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result
+	{
+		write!(f, "Invalid deallocation of {} bytes at {:?}: {:?}", self.size, self.memory_address, self.kind)
+	}
+}
@@ -0,0 +1,27 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Why a deallocation from a `MultipleBinarySearchTreeAllocator` was invalid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MultipleBinarySearchTreeAllocatorInvalidDeallocationKind
+{
+	/// The block does not lie entirely inside the allocator's `memory_range()`, or its size exceeds the maximum allocation size.
+	OutsideMemoryRange,
+
+	/// The memory address is not aligned to the minimum block size or to the alignment it was allocated with, so can not be the start of the block allocated.
+	Misaligned,
+
+	/// The block is already free; usually a double free.
+	AlreadyFree,
+
+	/// The block overlaps a free block of a different address or size; usually a double free of part of a since coalesced block, or a free with the wrong size.
+	OverlapsFreeBlock
+	{
+		/// Memory address of the free block.
+		free_block: MemoryAddress,
+
+		/// Size of the free block.
+		free_block_size: NonZeroUsize,
+	},
+}
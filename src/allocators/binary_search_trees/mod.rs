@@ -35,4 +35,8 @@ pub(crate) mod red_black_tree;
 
 
 include!("BinarySearchTreesWithCachedKnowledgeOfFirstChild.rs");include!("BinarySearchTreeWithCachedKnowledgeOfFirstChild.rs");
+include!("FreeSpaceReport.rs");
+include!("FreeSpaceReportSizeClass.rs");
 include!("MultipleBinarySearchTreeAllocator.rs");
+include!("MultipleBinarySearchTreeAllocatorInvalidDeallocation.rs");
+include!("MultipleBinarySearchTreeAllocatorInvalidDeallocationKind.rs");
@@ -192,7 +192,6 @@ impl RedBlackTree
 	/// Returns a `NodePointer` pointing to the first element whose key is above the given bound.
 	///
 	/// If no such element is found then a null `NodePointer` is returned.
-	#[allow(dead_code)]
 	#[inline(always)]
 	pub(crate) fn lower_bound(&self, bound: Bound<MemoryAddress>) -> NodePointer
 	{
@@ -225,7 +224,6 @@ impl RedBlackTree
 	/// Returns a `NodePointer` pointing to the last element whose key is below the given bound.
 	///
 	/// If no such element is found then a null `NodePointer` is returned.
-	#[allow(dead_code)]
 	#[inline(always)]
 	pub(crate) fn upper_bound(&self, bound: Bound<MemoryAddress>) -> NodePointer
 	{
@@ -3,6 +3,10 @@
 
 
 /// Bit set based allocator.
+///
+/// Memory can be filled when allocated and poisoned when freed using `set_memory_filling()`.
+///
+/// Deallocations can be checked using `set_invalid_deallocation_reporter()`.
 #[derive(Debug)]
 pub struct BitSetAllocator<MS: MemorySource>
 {
@@ -15,6 +19,10 @@ pub struct BitSetAllocator<MS: MemorySource>
 
 	block_size: BlockSize,
 
+	memory_filling: Cell<MemoryFilling>,
+
+	invalid_deallocation_reporter: Cell<Option<fn(BitSetAllocatorInvalidDeallocation)>>,
+
 	memory_source: MS,
 }
 
@@ -23,86 +31,31 @@ impl<MS: MemorySource> Allocator for BitSetAllocator<MS>
 	#[inline(always)]
 	fn allocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
 	{
-		let number_of_bits_required = self.number_of_bits_required(non_zero_size);
-
-		let power_of_two_exponent = if self.block_size.alignment_is_minimum(non_zero_power_of_two_alignment)
-		{
-			0
-		}
-		else
-		{
-			let power_of_two_exponent = non_zero_power_of_two_alignment.logarithm_base2() - self.block_size.block_size_power_of_two_exponent;
-
-			let alignment_exceeds_that_which_can_be_accommodated_in_one_bit_set_word = power_of_two_exponent > BitSetWord::SizeInBits;
-			if unlikely!(alignment_exceeds_that_which_can_be_accommodated_in_one_bit_set_word)
-			{
-				return Err(AllocError)
-			}
-
-			power_of_two_exponent
-		};
-
-		self.try_to_set_number_of_bits(number_of_bits_required, power_of_two_exponent)
+		let (memory_address, actual_size) = self.allocate_blocks(non_zero_size, non_zero_power_of_two_alignment)?;
+		self.memory_filling().fill_allocated(memory_address, non_zero_size.get());
+		Ok((memory_address, actual_size))
 	}
 
 	#[inline(always)]
-	fn deallocate(&self, non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	fn deallocate(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
 	{
-		#[inline(always)]
-		fn unset_unaligned_trailing_bits_at_front(location: AbsoluteLocationInBitSet, number_of_bits_required: NumberOfBits) -> (BitSetWordPointer, NumberOfBits)
-		{
-			let (location_major, bits_unset_to_reach_alignment) = location.align_upwards_to_next_bit_set_word_pointer(NumberOfBits::Zero, |location|
-			{
-				let number_of_lower_bits = NumberOfBits::InBitSetWord - location.minor;
-
-				if likely!(number_of_bits_required >= number_of_lower_bits)
-				{
-					location.major.unset_bottom_bits(number_of_lower_bits);
-					number_of_lower_bits
-				}
-				else
-				{
-					location.major.unset_middle_bits(number_of_bits_required, number_of_lower_bits);
-					number_of_bits_required
-				}
-			});
-
-			let remaining_bits_to_unset_in_middle_and_at_end = number_of_bits_required - bits_unset_to_reach_alignment;
-			(location_major, remaining_bits_to_unset_in_middle_and_at_end)
-		}
-
-		#[inline(always)]
-		fn unset_aligned_bits_in_middle(mut location_major: BitSetWordPointer, mut remaining_bits_to_unset_in_middle_and_at_end: NumberOfBits) -> (BitSetWordPointer, NumberOfBits)
-		{
-			while remaining_bits_to_unset_in_middle_and_at_end >= NumberOfBits::InBitSetWord
-			{
-				location_major.unset_all_bits_and_increment_assign();
-				remaining_bits_to_unset_in_middle_and_at_end -= NumberOfBits::InBitSetWord;
-			}
-
-			(location_major, remaining_bits_to_unset_in_middle_and_at_end)
-		}
-
-		#[inline(always)]
-		fn unset_unaligned_leading_bits_at_end(location_major: BitSetWordPointer, remaining_bits_to_unset_at_end: NumberOfBits)
+		if unlikely!(self.is_invalid_deallocation(non_zero_size, current_memory))
 		{
-			if likely!(remaining_bits_to_unset_at_end.is_not_zero())
-			{
-				location_major.unset_top_bits(remaining_bits_to_unset_at_end);
-			}
+			return
 		}
 
-		let location = self.absolute_location_in_bit_set(current_memory);
-		let number_of_bits_required = self.number_of_bits_required(non_zero_size);
-
-		let (location_major, remaining_bits_to_unset_in_middle_and_at_end) = unset_unaligned_trailing_bits_at_front(location, number_of_bits_required);
-		let (location_major, remaining_bits_to_unset_at_end) = unset_aligned_bits_in_middle(location_major, remaining_bits_to_unset_in_middle_and_at_end);
-		unset_unaligned_leading_bits_at_end(location_major, remaining_bits_to_unset_at_end);
+		self.memory_filling().poison_freed(current_memory, non_zero_size.get());
+		self.deallocate_blocks(non_zero_size, non_zero_power_of_two_alignment, current_memory)
 	}
 
 	#[inline(always)]
 	fn growing_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
 	{
+		if unlikely!(self.is_invalid_deallocation(non_zero_current_size, current_memory))
+		{
+			return Err(AllocError)
+		}
+
 		if Self::new_alignment_can_be_accommodated(non_zero_power_of_two_current_alignment, current_memory)
 		{
 			let current_number_of_bits_required = self.number_of_bits_required(non_zero_current_size);
@@ -114,6 +67,7 @@ impl<MS: MemorySource> Allocator for BitSetAllocator<MS>
 			let reallocate_size = new_memory_offset_in_bytes - current_memory_offset_in_bytes;
 			if unlikely!(reallocate_size.is_zero())
 			{
+				self.memory_filling().fill_grown(current_memory, non_zero_current_size, non_zero_new_size);
 				return Ok((current_memory, new_memory_offset_in_bytes.to_usize()))
 			}
 		}
@@ -123,13 +77,13 @@ impl<MS: MemorySource> Allocator for BitSetAllocator<MS>
 			return Err(AllocError)
 		}
 		
-		self.deallocate(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory);
+		self.deallocate_blocks(non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory);
 		self.start_search_for_next_allocation_at.set
 		({
 			let location = self.absolute_location_in_bit_set(current_memory);
 			location.major
 		});
-		let (allocated, actual_size) = self.allocate(non_zero_new_size, non_zero_power_of_two_new_alignment)?;
+		let (allocated, actual_size) = self.allocate_blocks(non_zero_new_size, non_zero_power_of_two_new_alignment)?;
 
 		if likely!(allocated != current_memory)
 		{
@@ -138,14 +92,21 @@ impl<MS: MemorySource> Allocator for BitSetAllocator<MS>
 			{
 				unsafe { to.as_ptr().copy_from(from.as_ptr() as *const _, non_zero_current_size.get()) };
 			}
-			memmove(current_memory, allocated, non_zero_current_size)
+			memmove(current_memory, allocated, non_zero_current_size);
+			self.memory_filling().poison_moved(current_memory, non_zero_current_size, allocated, non_zero_new_size)
 		}
+		self.memory_filling().fill_grown(allocated, non_zero_current_size, non_zero_new_size);
 		Ok((allocated, actual_size))
 	}
 	
 	#[inline(always)]
 	fn shrinking_reallocate(&self, non_zero_new_size: NonZeroUsize, non_zero_power_of_two_new_alignment: NonZeroUsize, non_zero_current_size: NonZeroUsize, non_zero_power_of_two_current_alignment: NonZeroUsize, current_memory: NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(NonNull<u8>, usize), AllocError>
 	{
+		if unlikely!(self.is_invalid_deallocation(non_zero_current_size, current_memory))
+		{
+			return Err(AllocError)
+		}
+
 		if Self::new_alignment_can_be_accommodated(non_zero_power_of_two_current_alignment, current_memory)
 		{
 			let current_number_of_bits_required = self.number_of_bits_required(non_zero_current_size);
@@ -154,11 +115,13 @@ impl<MS: MemorySource> Allocator for BitSetAllocator<MS>
 			let current_memory_offset_in_bytes = current_number_of_bits_required.scale_to_memory_offset_in_bytes(&self.block_size);
 			let new_memory_offset_in_bytes = new_number_of_bits_required.scale_to_memory_offset_in_bytes(&self.block_size);
 			
+			self.memory_filling().poison_shrunk(current_memory, non_zero_current_size, non_zero_new_size);
+			
 			let deallocate_size = current_memory_offset_in_bytes - new_memory_offset_in_bytes;
 			if likely!(deallocate_size.is_not_zero())
 			{
 				let end_of_new_memory = current_memory.add(new_memory_offset_in_bytes.to_usize());
-				self.deallocate(deallocate_size.to_non_zero(), non_zero_power_of_two_current_alignment, end_of_new_memory)
+				self.deallocate_blocks(deallocate_size.to_non_zero(), non_zero_power_of_two_current_alignment, end_of_new_memory)
 			}
 			
 			return Ok((current_memory, new_memory_offset_in_bytes.to_usize()))
@@ -248,11 +211,202 @@ impl<MS: MemorySource> BitSetAllocator<MS>
 			allocations_end_at,
 
 			block_size: BlockSize::new(block_size),
+
+			memory_filling: Cell::new(MemoryFilling::Off),
+
+			invalid_deallocation_reporter: Cell::new(None),
 			
 			memory_source,
 		}
 	}
 
+	/// Memory filling.
+	#[inline(always)]
+	pub fn memory_filling(&self) -> MemoryFilling
+	{
+		self.memory_filling.get()
+	}
+
+	/// Changes memory filling; memory already allocated or freed is not affected.
+	#[inline(always)]
+	pub fn set_memory_filling(&self, memory_filling: MemoryFilling)
+	{
+		self.memory_filling.set(memory_filling)
+	}
+
+	/// Reporter of invalid deallocations, if checked.
+	#[inline(always)]
+	pub fn invalid_deallocation_reporter(&self) -> Option<fn(BitSetAllocatorInvalidDeallocation)>
+	{
+		self.invalid_deallocation_reporter.get()
+	}
+
+	/// Checks every deallocation and the current memory of every reallocation if `Some`: the memory must lie inside `memory_range()`, start on a block boundary and every block to be freed must be allocated.
+	///
+	/// Invalid deallocations are passed to the reporter and ignored, and invalid reallocations are passed to the reporter and fail, rather than corrupting the bit set; the reporter must not allocate from this allocator.
+	#[inline(always)]
+	pub fn set_invalid_deallocation_reporter(&self, invalid_deallocation_reporter: Option<fn(BitSetAllocatorInvalidDeallocation)>)
+	{
+		self.invalid_deallocation_reporter.set(invalid_deallocation_reporter)
+	}
+
+	/// Block size.
+	#[inline(always)]
+	pub fn block_size(&self) -> NonZeroUsize
+	{
+		self.block_size.block_size
+	}
+
+	/// Total number of blocks, used or free.
+	#[inline(always)]
+	pub fn total_blocks(&self) -> usize
+	{
+		self.exclusive_end_of_bit_set.difference_in_number_of_bits(self.inclusive_start_of_bit_set).to_usize()
+	}
+
+	/// Where the next allocation will start searching from, as an offset in blocks; always at the start of a bit set word.
+	#[inline(always)]
+	pub fn start_search_for_next_allocation_at(&self) -> usize
+	{
+		self.start_search_for_next_allocation_at.get().difference_in_number_of_bits(self.inclusive_start_of_bit_set).to_usize()
+	}
+
+	/// Occupancy.
+	///
+	/// Scans the entire bit set, so is relatively expensive; intended for periodic monitoring, eg to size coroutine heaps.
+	#[inline(always)]
+	pub fn occupancy(&self) -> BitSetAllocatorOccupancy
+	{
+		let mut used_blocks = 0;
+		let mut longest_free_run = 0;
+		for run in self.runs()
+		{
+			if run.is_allocated
+			{
+				used_blocks += run.block_count
+			}
+			else
+			{
+				longest_free_run = max(longest_free_run, run.block_count)
+			}
+		}
+
+		let total_blocks = self.total_blocks();
+		BitSetAllocatorOccupancy
+		{
+			block_size: self.block_size().get(),
+			total_blocks,
+			used_blocks,
+			free_blocks: total_blocks - used_blocks,
+			longest_free_run,
+			start_search_for_next_allocation_at: self.start_search_for_next_allocation_at(),
+		}
+	}
+
+	/// Iterates over contiguous runs of allocated and free blocks, in ascending order of offset.
+	///
+	/// Runs alternate between allocated and free; adjacent allocations are reported as one run.
+	#[inline(always)]
+	pub fn runs(&self) -> BitSetAllocatorRuns<MS>
+	{
+		BitSetAllocatorRuns
+		{
+			bit_set_allocator: self,
+			offset: 0,
+			total_blocks: self.total_blocks(),
+		}
+	}
+
+	/// Iterates over contiguous runs of allocated blocks as `(offset, block count)` pairs.
+	#[inline(always)]
+	pub fn allocated_runs(&self) -> impl Iterator<Item=(usize, usize)> + '_
+	{
+		self.runs().filter(|run| run.is_allocated).map(|run| (run.offset, run.block_count))
+	}
+
+	/// Iterates over contiguous runs of free blocks as `(offset, block count)` pairs.
+	#[inline(always)]
+	pub fn free_runs(&self) -> impl Iterator<Item=(usize, usize)> + '_
+	{
+		self.runs().filter(|run| !run.is_allocated).map(|run| (run.offset, run.block_count))
+	}
+
+	/// Memory address of the block at `offset`.
+	#[inline(always)]
+	pub fn block_memory_address(&self, offset: usize) -> MemoryAddress
+	{
+		debug_assert!(offset < self.total_blocks(), "offset `{}` is out of range", offset);
+
+		self.allocations_start_from.add(self.block_size.scale_to_memory_offset_in_bytes(offset).to_usize())
+	}
+
+	/// Blocks are ordered from the most significant bit to the least significant bit of each bit set word.
+	#[inline(always)]
+	fn bit_set_word_containing(&self, offset: usize) -> u64
+	{
+		self.inclusive_start_of_bit_set.increment_in_bit_set_words(NumberOfBitSetWords(offset / BitSetWord::SizeInBits)).bit_set_word().to_u64()
+	}
+
+	#[inline(always)]
+	fn is_block_allocated(&self, offset: usize) -> bool
+	{
+		let minor = offset % BitSetWord::SizeInBits;
+		(self.bit_set_word_containing(offset) >> (BitSetWord::SizeInBits - 1 - minor)) & 1 == 1
+	}
+
+	/// Reports the deallocation if it is invalid and deallocations are checked.
+	#[inline(always)]
+	fn is_invalid_deallocation(&self, non_zero_size: NonZeroUsize, current_memory: MemoryAddress) -> bool
+	{
+		if let Some(invalid_deallocation_reporter) = self.invalid_deallocation_reporter.get()
+		{
+			if let Err(kind) = self.check_deallocation(non_zero_size, current_memory)
+			{
+				invalid_deallocation_reporter
+				(
+					BitSetAllocatorInvalidDeallocation
+					{
+						memory_address: current_memory,
+						size: non_zero_size,
+						kind,
+					}
+				);
+				return true
+			}
+		}
+		false
+	}
+
+	#[inline(always)]
+	fn check_deallocation(&self, non_zero_size: NonZeroUsize, current_memory: MemoryAddress) -> Result<(), BitSetAllocatorInvalidDeallocationKind>
+	{
+		use self::BitSetAllocatorInvalidDeallocationKind::*;
+
+		if unlikely!(current_memory < self.allocations_start_from || current_memory >= self.allocations_end_at)
+		{
+			return Err(OutsideMemoryRange)
+		}
+
+		let memory_offset_in_bytes = current_memory.difference(self.allocations_start_from);
+		if unlikely!(memory_offset_in_bytes & self.block_size.block_size_less_one != 0)
+		{
+			return Err(NotOnBlockBoundary)
+		}
+
+		let first_block = self.block_size.blocks_offset(self.allocations_start_from, current_memory).to_usize();
+		let end_block = first_block + self.number_of_bits_required(non_zero_size).to_usize();
+		if unlikely!(end_block > self.total_blocks())
+		{
+			return Err(OutsideMemoryRange)
+		}
+
+		match (first_block .. end_block).find(|&offset| !self.is_block_allocated(offset))
+		{
+			None => Ok(()),
+			Some(offset) => Err(NotAllocated { offset }),
+		}
+	}
+
 	#[inline(always)]
 	fn initialize_bit_set_so_all_memory_is_unallocated(allocations_end_at: MemoryAddress, bit_set_size_in_bytes: usize) -> (BitSetWordPointer, BitSetWordPointer)
 	{
@@ -274,6 +428,86 @@ impl<MS: MemorySource> BitSetAllocator<MS>
 		self.block_size.number_of_blocks_required(non_zero_size)
 	}
 
+	#[inline(always)]
+	fn allocate_blocks(&self, non_zero_size: NonZeroUsize, non_zero_power_of_two_alignment: NonZeroUsize) -> Result<(NonNull<u8>, usize), AllocError>
+	{
+		let number_of_bits_required = self.number_of_bits_required(non_zero_size);
+
+		let power_of_two_exponent = if self.block_size.alignment_is_minimum(non_zero_power_of_two_alignment)
+		{
+			0
+		}
+		else
+		{
+			let power_of_two_exponent = non_zero_power_of_two_alignment.logarithm_base2() - self.block_size.block_size_power_of_two_exponent;
+
+			let alignment_exceeds_that_which_can_be_accommodated_in_one_bit_set_word = power_of_two_exponent > BitSetWord::SizeInBits;
+			if unlikely!(alignment_exceeds_that_which_can_be_accommodated_in_one_bit_set_word)
+			{
+				return Err(AllocError)
+			}
+
+			power_of_two_exponent
+		};
+
+		self.try_to_set_number_of_bits(number_of_bits_required, power_of_two_exponent)
+	}
+
+	#[inline(always)]
+	fn deallocate_blocks(&self, non_zero_size: NonZeroUsize, _non_zero_power_of_two_alignment: NonZeroUsize, current_memory: NonNull<u8>)
+	{
+		#[inline(always)]
+		fn unset_unaligned_trailing_bits_at_front(location: AbsoluteLocationInBitSet, number_of_bits_required: NumberOfBits) -> (BitSetWordPointer, NumberOfBits)
+		{
+			let (location_major, bits_unset_to_reach_alignment) = location.align_upwards_to_next_bit_set_word_pointer(NumberOfBits::Zero, |location|
+			{
+				let number_of_lower_bits = NumberOfBits::InBitSetWord - location.minor;
+
+				if likely!(number_of_bits_required >= number_of_lower_bits)
+				{
+					location.major.unset_bottom_bits(number_of_lower_bits);
+					number_of_lower_bits
+				}
+				else
+				{
+					location.major.unset_middle_bits(number_of_bits_required, number_of_lower_bits);
+					number_of_bits_required
+				}
+			});
+
+			let remaining_bits_to_unset_in_middle_and_at_end = number_of_bits_required - bits_unset_to_reach_alignment;
+			(location_major, remaining_bits_to_unset_in_middle_and_at_end)
+		}
+
+		#[inline(always)]
+		fn unset_aligned_bits_in_middle(mut location_major: BitSetWordPointer, mut remaining_bits_to_unset_in_middle_and_at_end: NumberOfBits) -> (BitSetWordPointer, NumberOfBits)
+		{
+			while remaining_bits_to_unset_in_middle_and_at_end >= NumberOfBits::InBitSetWord
+			{
+				location_major.unset_all_bits_and_increment_assign();
+				remaining_bits_to_unset_in_middle_and_at_end -= NumberOfBits::InBitSetWord;
+			}
+
+			(location_major, remaining_bits_to_unset_in_middle_and_at_end)
+		}
+
+		#[inline(always)]
+		fn unset_unaligned_leading_bits_at_end(location_major: BitSetWordPointer, remaining_bits_to_unset_at_end: NumberOfBits)
+		{
+			if likely!(remaining_bits_to_unset_at_end.is_not_zero())
+			{
+				location_major.unset_top_bits(remaining_bits_to_unset_at_end);
+			}
+		}
+
+		let location = self.absolute_location_in_bit_set(current_memory);
+		let number_of_bits_required = self.number_of_bits_required(non_zero_size);
+
+		let (location_major, remaining_bits_to_unset_in_middle_and_at_end) = unset_unaligned_trailing_bits_at_front(location, number_of_bits_required);
+		let (location_major, remaining_bits_to_unset_at_end) = unset_aligned_bits_in_middle(location_major, remaining_bits_to_unset_in_middle_and_at_end);
+		unset_unaligned_leading_bits_at_end(location_major, remaining_bits_to_unset_at_end);
+	}
+
 	#[inline(always)]
 	fn try_to_set_number_of_bits(&self, number_of_bits_required: NumberOfBits, power_of_two_exponent: usize) -> Result<(MemoryAddress, usize), AllocError>
 	{
@@ -494,3 +728,251 @@ impl<MS: MemorySource> BitSetAllocator<MS>
 		current_memory.is_aligned_to(non_zero_power_of_two_current_alignment)
 	}
 }
+
+#[cfg(test)]
+mod BitSetAllocatorTests
+{
+	use super::*;
+	use std::cell::RefCell;
+
+	thread_local!
+	{
+		static InvalidDeallocations: RefCell<Vec<BitSetAllocatorInvalidDeallocation>> = RefCell::new(Vec::new());
+	}
+
+	const BlockSizeInBytes: usize = 64;
+
+	#[test]
+	pub fn valid_deallocation_is_not_reported()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate(BlockSizeInBytes.non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate(BlockSizeInBytes.non_zero(), 8.non_zero(), allocation);
+
+		assert_eq!(invalid_deallocations(), vec![], "Valid deallocation was reported");
+		assert_eq!(allocator.occupancy().used_blocks, 0, "Did not deallocate");
+	}
+
+	#[test]
+	pub fn double_free_is_reported()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate(BlockSizeInBytes.non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate(BlockSizeInBytes.non_zero(), 8.non_zero(), allocation);
+		allocator.deallocate(BlockSizeInBytes.non_zero(), 8.non_zero(), allocation);
+
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(allocation, BlockSizeInBytes, BitSetAllocatorInvalidDeallocationKind::NotAllocated { offset: block_offset(&allocator, allocation) })], "Double free was not reported");
+	}
+
+	#[test]
+	pub fn deallocation_not_on_block_boundary_is_reported()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate(BlockSizeInBytes.non_zero(), 8.non_zero()).expect("Did not allocate");
+		let misaligned = allocation.add(8);
+		allocator.deallocate(8.non_zero(), 8.non_zero(), misaligned);
+
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(misaligned, 8, BitSetAllocatorInvalidDeallocationKind::NotOnBlockBoundary)], "Deallocation not on a block boundary was not reported");
+		assert_eq!(allocator.occupancy().used_blocks, 1, "Invalid deallocation freed blocks");
+	}
+
+	#[test]
+	pub fn deallocation_outside_memory_range_is_reported()
+	{
+		let allocator = new_allocator();
+
+		let mut outside = [0u8; BlockSizeInBytes];
+		let outside = NonNull::from(&mut outside[0]);
+		allocator.deallocate(BlockSizeInBytes.non_zero(), 8.non_zero(), outside);
+
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(outside, BlockSizeInBytes, BitSetAllocatorInvalidDeallocationKind::OutsideMemoryRange)], "Deallocation outside the memory range was not reported");
+	}
+
+	#[test]
+	pub fn growing_reallocation_of_freed_memory_is_reported_and_fails()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate(BlockSizeInBytes.non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate(BlockSizeInBytes.non_zero(), 8.non_zero(), allocation);
+
+		assert_eq!(allocator.growing_reallocate((BlockSizeInBytes * 2).non_zero(), 8.non_zero(), BlockSizeInBytes.non_zero(), 8.non_zero(), allocation, false), Err(AllocError), "Growing reallocation of freed memory did not fail");
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(allocation, BlockSizeInBytes, BitSetAllocatorInvalidDeallocationKind::NotAllocated { offset: block_offset(&allocator, allocation) })], "Growing reallocation of freed memory was not reported");
+		assert_eq!(allocator.occupancy().used_blocks, 0, "Invalid reallocation changed the bit set");
+	}
+
+	#[test]
+	pub fn shrinking_reallocation_of_freed_memory_is_reported_and_fails()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate((BlockSizeInBytes * 2).non_zero(), 8.non_zero()).expect("Did not allocate");
+		allocator.deallocate((BlockSizeInBytes * 2).non_zero(), 8.non_zero(), allocation);
+
+		assert_eq!(allocator.shrinking_reallocate(BlockSizeInBytes.non_zero(), 8.non_zero(), (BlockSizeInBytes * 2).non_zero(), 8.non_zero(), allocation, false), Err(AllocError), "Shrinking reallocation of freed memory did not fail");
+		assert_eq!(invalid_deallocations(), vec![invalid_deallocation(allocation, BlockSizeInBytes * 2, BitSetAllocatorInvalidDeallocationKind::NotAllocated { offset: block_offset(&allocator, allocation) })], "Shrinking reallocation of freed memory was not reported");
+		assert_eq!(allocator.occupancy().used_blocks, 0, "Invalid reallocation changed the bit set");
+	}
+
+	#[test]
+	pub fn valid_reallocations_are_not_reported()
+	{
+		let allocator = new_allocator();
+
+		let (allocation, _) = allocator.allocate(BlockSizeInBytes.non_zero(), 8.non_zero()).expect("Did not allocate");
+		let (allocation, _) = allocator.growing_reallocate((BlockSizeInBytes * 3).non_zero(), 8.non_zero(), BlockSizeInBytes.non_zero(), 8.non_zero(), allocation, false).expect("Did not grow");
+		let (allocation, _) = allocator.shrinking_reallocate(BlockSizeInBytes.non_zero(), 8.non_zero(), (BlockSizeInBytes * 3).non_zero(), 8.non_zero(), allocation, false).expect("Did not shrink");
+		allocator.deallocate(BlockSizeInBytes.non_zero(), 8.non_zero(), allocation);
+
+		assert_eq!(invalid_deallocations(), vec![], "Valid reallocations were reported");
+		assert_eq!(allocator.occupancy().used_blocks, 0, "Did not deallocate");
+	}
+
+	#[test]
+	pub fn unused_allocator_is_one_free_run()
+	{
+		let allocator = new_allocator_of_bit_set_words(2);
+
+		assert_eq!(allocator.runs().collect::<Vec<_>>(), vec![BitSetAllocatorRun { is_allocated: false, offset: 0, block_count: 128 }], "Unused allocator was not one free run");
+		assert_eq!(allocator.allocated_runs().count(), 0, "Unused allocator had allocated runs");
+
+		let occupancy = allocator.occupancy();
+		assert_eq!(occupancy.total_blocks, 128, "Total blocks was not two bit set words");
+		assert_eq!(occupancy.used_blocks, 0, "Unused allocator had used blocks");
+		assert_eq!(occupancy.longest_free_run, 128, "Unused allocator's longest free run was not every block");
+		assert_eq!(occupancy.fragmentation_ratio(), 0.0, "Unused allocator was fragmented");
+	}
+
+	#[test]
+	pub fn runs_and_occupancy_span_bit_set_words_from_the_most_significant_bit()
+	{
+		let allocator = new_allocator_of_bit_set_words(2);
+
+		let (first, _) = allocator.allocate((BlockSizeInBytes * 60).non_zero(), 8.non_zero()).expect("Did not allocate");
+		let (crossing, _) = allocator.allocate((BlockSizeInBytes * 8).non_zero(), 8.non_zero()).expect("Did not allocate");
+		let (last, _) = allocator.allocate(BlockSizeInBytes.non_zero(), 8.non_zero()).expect("Did not allocate");
+		assert_eq!((block_offset(&allocator, first), block_offset(&allocator, crossing), block_offset(&allocator, last)), (0, 60, 68), "Did not allocate contiguously");
+
+		allocator.deallocate((BlockSizeInBytes * 60).non_zero(), 8.non_zero(), first);
+
+		assert_eq!(allocator.bit_set_word_containing(0), 0x0000_0000_0000_000F, "Blocks 60 to 63 are not the least significant bits of the first bit set word");
+		assert_eq!(allocator.bit_set_word_containing(64), 0xF800_0000_0000_0000, "Blocks 64 to 68 are not the most significant bits of the second bit set word");
+		assert!(!allocator.is_block_allocated(59), "Block 59 was allocated");
+		assert!(allocator.is_block_allocated(60), "Block 60 was not allocated");
+		assert!(allocator.is_block_allocated(63), "Block 63 was not allocated");
+		assert!(allocator.is_block_allocated(64), "Block 64 was not allocated");
+		assert!(allocator.is_block_allocated(68), "Block 68 was not allocated");
+		assert!(!allocator.is_block_allocated(69), "Block 69 was allocated");
+
+		let expected_runs = vec!
+		[
+			BitSetAllocatorRun { is_allocated: false, offset: 0, block_count: 60 },
+			BitSetAllocatorRun { is_allocated: true, offset: 60, block_count: 9 },
+			BitSetAllocatorRun { is_allocated: false, offset: 69, block_count: 59 },
+		];
+		assert_eq!(allocator.runs().collect::<Vec<_>>(), expected_runs, "Runs were wrong");
+		assert_eq!(allocator.allocated_runs().collect::<Vec<_>>(), vec![(60, 9)], "Allocated runs were wrong");
+		assert_eq!(allocator.free_runs().collect::<Vec<_>>(), vec![(0, 60), (69, 59)], "Free runs were wrong");
+
+		let occupancy = allocator.occupancy();
+		assert_eq!(occupancy.used_blocks, 9, "Used blocks were wrong");
+		assert_eq!(occupancy.free_blocks, 119, "Free blocks were wrong");
+		assert_eq!(occupancy.longest_free_run, 60, "Longest free run was wrong");
+		assert_eq!(occupancy.fragmentation_ratio(), 1.0 - 60.0 / 119.0, "Fragmentation ratio was wrong");
+	}
+
+	#[test]
+	pub fn moved_growing_reallocation_poisons_old_memory_and_fills_grown_memory()
+	{
+		let allocator = new_allocator();
+		allocator.set_memory_filling(MemoryFilling::On);
+
+		let (allocation, _) = allocator.allocate(BlockSizeInBytes.non_zero(), 8.non_zero()).expect("Did not allocate");
+		let _blocks_growing_in_place = allocator.allocate(BlockSizeInBytes.non_zero(), 8.non_zero()).expect("Did not allocate");
+		unsafe { allocation.as_ptr().write_bytes(0x11, BlockSizeInBytes) };
+
+		let (moved, _) = allocator.growing_reallocate((BlockSizeInBytes * 2).non_zero(), 8.non_zero(), BlockSizeInBytes.non_zero(), 8.non_zero(), allocation, false).expect("Did not grow");
+		assert_ne!(moved, allocation, "Did not move");
+		assert_eq!(bytes(moved, BlockSizeInBytes * 2), [vec![0x11; BlockSizeInBytes], vec![MemoryFilling::DefaultAllocatedByte; BlockSizeInBytes]].concat(), "Did not copy the moved memory and fill only the grown memory");
+		assert_eq!(bytes(allocation, BlockSizeInBytes), vec![MemoryFilling::DefaultFreedByte; BlockSizeInBytes], "Did not poison the memory moved from");
+	}
+
+	/// `BitSetAllocator::new()` requires the memory source to hold exactly the blocks followed by the bit set.
+	#[derive(Debug)]
+	struct SystemMemorySource
+	{
+		memory: MemoryAddress,
+		layout: Layout,
+	}
+
+	impl Drop for SystemMemorySource
+	{
+		fn drop(&mut self)
+		{
+			unsafe { System.dealloc(self.memory.as_ptr(), self.layout) }
+		}
+	}
+
+	impl MemorySource for SystemMemorySource
+	{
+		fn size(&self) -> NonZeroUsize
+		{
+			self.layout.size().non_zero()
+		}
+
+		fn allocations_start_from(&self) -> MemoryAddress
+		{
+			self.memory
+		}
+	}
+
+	fn new_allocator() -> BitSetAllocator<SystemMemorySource>
+	{
+		new_allocator_of_bit_set_words(1)
+	}
+
+	/// `BitSetAllocator::new()` sizes its bit set as one byte for every 64 blocks, so each bit set word (of 64 blocks) needs 512 blocks of memory.
+	fn new_allocator_of_bit_set_words(number_of_bit_set_words: usize) -> BitSetAllocator<SystemMemorySource>
+	{
+		let number_of_blocks = number_of_bit_set_words * BitSetWord::SizeInBytes * NumberOfBits::InBitSetWord.to_usize();
+
+		let layout = Layout::from_size_align(number_of_blocks * BlockSizeInBytes + number_of_blocks / NumberOfBits::InBitSetWord.to_usize(), 4096).unwrap();
+		let memory = NonNull::new(unsafe { System.alloc(layout) }).expect("Out of memory");
+		let allocator = BitSetAllocator::new(SystemMemorySource { memory, layout }, BlockSizeInBytes.non_zero(), number_of_blocks.non_zero());
+		allocator.set_invalid_deallocation_reporter(Some(record_invalid_deallocation));
+		allocator
+	}
+
+	fn record_invalid_deallocation(invalid_deallocation: BitSetAllocatorInvalidDeallocation)
+	{
+		InvalidDeallocations.with(|invalid_deallocations| invalid_deallocations.borrow_mut().push(invalid_deallocation))
+	}
+
+	fn invalid_deallocations() -> Vec<BitSetAllocatorInvalidDeallocation>
+	{
+		InvalidDeallocations.with(|invalid_deallocations| take(&mut *invalid_deallocations.borrow_mut()))
+	}
+
+	fn invalid_deallocation(memory_address: MemoryAddress, size: usize, kind: BitSetAllocatorInvalidDeallocationKind) -> BitSetAllocatorInvalidDeallocation
+	{
+		BitSetAllocatorInvalidDeallocation
+		{
+			memory_address,
+			size: size.non_zero(),
+			kind,
+		}
+	}
+
+	fn block_offset(allocator: &BitSetAllocator<SystemMemorySource>, memory_address: MemoryAddress) -> usize
+	{
+		memory_address.difference(allocator.block_memory_address(0)) / BlockSizeInBytes
+	}
+
+	fn bytes(memory_address: MemoryAddress, size: usize) -> Vec<u8>
+	{
+		unsafe { from_raw_parts(memory_address.as_ptr() as *const u8, size) }.to_vec()
+	}
+}
@@ -0,0 +1,21 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Why a deallocation from a `BitSetAllocator` was invalid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BitSetAllocatorInvalidDeallocationKind
+{
+	/// The memory does not lie entirely inside the allocator's `memory_range()`.
+	OutsideMemoryRange,
+
+	/// The memory address is not on a block boundary.
+	NotOnBlockBoundary,
+
+	/// A block to be freed is not allocated; usually a double free or a free with the wrong size.
+	NotAllocated
+	{
+		/// Offset in blocks of the first block which is not allocated.
+		offset: usize,
+	},
+}
@@ -0,0 +1,66 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Occupancy of a `BitSetAllocator`.
+///
+/// Obtained using `BitSetAllocator::occupancy()`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BitSetAllocatorOccupancy
+{
+	/// Block size in bytes.
+	pub block_size: usize,
+
+	/// Total number of blocks.
+	pub total_blocks: usize,
+
+	/// Number of allocated blocks.
+	pub used_blocks: usize,
+
+	/// Number of free blocks.
+	pub free_blocks: usize,
+
+	/// Length in blocks of the longest run of contiguous free blocks; the largest allocation that can succeed, ignoring alignment, is this multiplied by `block_size`.
+	pub longest_free_run: usize,
+
+	/// Where the next allocation will start searching from, as an offset in blocks.
+	pub start_search_for_next_allocation_at: usize,
+}
+
+impl BitSetAllocatorOccupancy
+{
+	/// Total number of bytes.
+	#[inline(always)]
+	pub fn total_bytes(&self) -> usize
+	{
+		self.total_blocks * self.block_size
+	}
+
+	/// Number of allocated bytes.
+	#[inline(always)]
+	pub fn used_bytes(&self) -> usize
+	{
+		self.used_blocks * self.block_size
+	}
+
+	/// Number of free bytes.
+	#[inline(always)]
+	pub fn free_bytes(&self) -> usize
+	{
+		self.free_blocks * self.block_size
+	}
+
+	/// Fragmentation ratio, from `0.0` (all free blocks are contiguous, or there are no free blocks) to nearly `1.0` (free blocks are scattered).
+	#[inline(always)]
+	pub fn fragmentation_ratio(&self) -> f64
+	{
+		if self.free_blocks == 0
+		{
+			0.0
+		}
+		else
+		{
+			1.0 - (self.longest_free_run as f64) / (self.free_blocks as f64)
+		}
+	}
+}
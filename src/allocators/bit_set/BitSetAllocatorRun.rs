@@ -0,0 +1,17 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// A contiguous run of allocated or free blocks in a `BitSetAllocator`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BitSetAllocatorRun
+{
+	/// Are the blocks allocated (or free)?
+	pub is_allocated: bool,
+
+	/// Offset in blocks of the first block; use `BitSetAllocator::block_memory_address()` to find its memory address.
+	pub offset: usize,
+
+	/// Number of blocks; never zero.
+	pub block_count: usize,
+}
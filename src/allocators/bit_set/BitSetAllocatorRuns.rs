@@ -0,0 +1,68 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// An iterator over contiguous runs of allocated and free blocks in a `BitSetAllocator`.
+///
+/// Obtained using `BitSetAllocator::runs()`.
+#[derive(Debug)]
+pub struct BitSetAllocatorRuns<'a, MS: MemorySource>
+{
+	bit_set_allocator: &'a BitSetAllocator<MS>,
+
+	offset: usize,
+
+	total_blocks: usize,
+}
+
+impl<'a, MS: MemorySource> Iterator for BitSetAllocatorRuns<'a, MS>
+{
+	type Item = BitSetAllocatorRun;
+
+	#[inline(always)]
+	fn next(&mut self) -> Option<Self::Item>
+	{
+		const BitsInBitSetWord: usize = BitSetWord::SizeInBits;
+
+		if unlikely!(self.offset >= self.total_blocks)
+		{
+			return None
+		}
+
+		let start = self.offset;
+		let is_allocated = self.bit_set_allocator.is_block_allocated(start);
+
+		while
+		{
+			let minor = self.offset % BitsInBitSetWord;
+			let remaining_bits_in_bit_set_word = BitsInBitSetWord - minor;
+
+			let bit_set_word = self.bit_set_allocator.bit_set_word_containing(self.offset);
+			let bits_which_end_run = if is_allocated
+			{
+				!bit_set_word
+			}
+			else
+			{
+				bit_set_word
+			};
+			let run_length_in_bit_set_word = min((bits_which_end_run << minor).leading_zeros() as usize, remaining_bits_in_bit_set_word);
+
+			self.offset += run_length_in_bit_set_word;
+			run_length_in_bit_set_word == remaining_bits_in_bit_set_word && self.offset < self.total_blocks
+		}
+		{
+		}
+		self.offset = min(self.offset, self.total_blocks);
+
+		Some
+		(
+			BitSetAllocatorRun
+			{
+				is_allocated,
+				offset: start,
+				block_count: self.offset - start,
+			}
+		)
+	}
+}
@@ -7,6 +7,11 @@ use super::*;
 
 include!("AbsoluteLocationInBitSet.rs");
 include!("BitSetAllocator.rs");
+include!("BitSetAllocatorInvalidDeallocation.rs");
+include!("BitSetAllocatorInvalidDeallocationKind.rs");
+include!("BitSetAllocatorOccupancy.rs");
+include!("BitSetAllocatorRun.rs");
+include!("BitSetAllocatorRuns.rs");
 include!("BitSetWord.rs");
 include!("BitSetWordPointer.rs");
 include!("BitsInAByte.rs");
@@ -19,4 +19,14 @@ pub mod bit_set;
 include!("Allocator.rs");
 include!("BumpAllocator.rs");
 include!("ContextAllocator.rs");
-include!("MemoryUsageTrackingThreadLocalAllocator.rs");
+include!("GuardedAllocator.rs");
+include!("GuardedAllocatorCorruption.rs");
+include!("MemoryFilling.rs");
+include!("MemoryUsageTrackingAllocator.rs");
+include!("QuarantineAllocator.rs");
+include!("QuarantinedBlock.rs");
+include!("QuarantineUseAfterFree.rs");
+include!("RedZoneSide.rs");
+include!("SamplingHeapProfilerAllocator.rs");
+include!("SizeClassHistogramRecordingAllocator.rs");
+include!("TracingAllocator.rs");
@@ -0,0 +1,196 @@
+// This file is part of context-allocator. It is subject to the license terms in the COPYRIGHT file found in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT. No part of context-allocator, including this file, may be copied, modified, propagated, or distributed except according to the terms contained in the COPYRIGHT file.
+// Copyright © 2019 The developers of context-allocator. See the COPYRIGHT file in the top-level directory of this distribution and at https://raw.githubusercontent.com/lemonrock/context-allocator/master/COPYRIGHT.
+
+
+/// Creates a global allocator suitable for use with `#[global_allocator]`.
+///
+/// Generates:-
+///
+/// * a wrapper type, `$name`, which implements `GlobalThreadAndCoroutineSwitchableAllocator`, `GlobalAlloc`, `Alloc` and `Allocator`;
+/// * a genuinely thread-local `PerThreadState` and an accessor function for it;
+/// * a static, also called `$name`, marked `#[global_allocator]`.
+///
+/// The crate using this macro must enable `#![feature(thread_local)]` and `#![feature(allocator_api)]`.
+///
+/// The second argument, the coroutine heap size, is required as it can not be derived from the type of the coroutine local allocator.
+///
+/// Example (not a doctest, as a doctest can not install a global allocator; the crate's own tests compile the same invocation):-
+///
+/// ```ignore
+/// use context_allocator::*;
+/// use context_allocator::adaptors::GlobalAllocToAllocatorAdaptor;
+/// use context_allocator::allocators::BumpAllocator;
+/// use context_allocator::allocators::binary_search_trees::MultipleBinarySearchTreeAllocator;
+/// use context_allocator::memory_sources::CoroutineHeapMemorySource;
+/// use context_allocator::memory_sources::MemoryMapSource;
+/// use magic_ring_buffer::memory_sizes::MemorySize64Kb;
+/// use std::alloc::System;
+///
+/// global_thread_and_coroutine_switchable_allocator!(MyGlobalAllocator, MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, MultipleBinarySearchTreeAllocator<MemoryMapSource>, GlobalAllocToAllocatorAdaptor<System>, GlobalAllocToAllocatorAdaptor(System));
+///
+/// MyGlobalAllocator.initialize_thread_local_allocator(thread_local_allocator);
+/// ```
+///
+/// An optional seventh argument, a `ThreadLocalAllocatorFactory`, creates thread-local allocators on demand for threads which do not call `initialize_thread_local_allocator()`.
+/// An optional eighth argument, an `ExhaustionPolicy`, controls spilling to the next tier when an allocator is exhausted.
+/// An optional ninth argument, a `fn(u64)`, reports memory still allocated when a thread exits from a thread-local allocator created by the `ThreadLocalAllocatorFactory`.
+#[macro_export]
+macro_rules! global_thread_and_coroutine_switchable_allocator
+{
+	($name: ident, $coroutine_heap_size: ty, $coroutine_local_allocator: ty, $thread_local_allocator: ty, $global_allocator: ty, $global_allocator_instance: expr) =>
+	{
+		$crate::global_thread_and_coroutine_switchable_allocator!($name, $coroutine_heap_size, $coroutine_local_allocator, $thread_local_allocator, $global_allocator, $global_allocator_instance, $crate::ThreadLocalAllocatorFactory::Never);
+	};
+
+	($name: ident, $coroutine_heap_size: ty, $coroutine_local_allocator: ty, $thread_local_allocator: ty, $global_allocator: ty, $global_allocator_instance: expr, $thread_local_allocator_factory: expr) =>
+	{
+		$crate::global_thread_and_coroutine_switchable_allocator!($name, $coroutine_heap_size, $coroutine_local_allocator, $thread_local_allocator, $global_allocator, $global_allocator_instance, $thread_local_allocator_factory, $crate::ExhaustionPolicy::Strict);
+	};
+
+	($name: ident, $coroutine_heap_size: ty, $coroutine_local_allocator: ty, $thread_local_allocator: ty, $global_allocator: ty, $global_allocator_instance: expr, $thread_local_allocator_factory: expr, $exhaustion_policy: expr) =>
+	{
+		$crate::global_thread_and_coroutine_switchable_allocator!($name, $coroutine_heap_size, $coroutine_local_allocator, $thread_local_allocator, $global_allocator, $global_allocator_instance, $thread_local_allocator_factory, $exhaustion_policy, $crate::GlobalThreadAndCoroutineSwitchableAllocatorInstance::<$coroutine_heap_size, $coroutine_local_allocator, $thread_local_allocator, $global_allocator>::ignore_outstanding_memory);
+	};
+
+	($name: ident, $coroutine_heap_size: ty, $coroutine_local_allocator: ty, $thread_local_allocator: ty, $global_allocator: ty, $global_allocator_instance: expr, $thread_local_allocator_factory: expr, $exhaustion_policy: expr, $lazily_created_thread_local_allocator_outstanding_memory_reporter: expr) =>
+	{
+		/// A global allocator which can switch between thread, coroutine and global allocators.
+		#[derive(Debug)]
+		pub struct $name
+		{
+			inner: $crate::GlobalThreadAndCoroutineSwitchableAllocatorInstance<$coroutine_heap_size, $coroutine_local_allocator, $thread_local_allocator, $global_allocator>,
+		}
+
+		unsafe impl ::std::alloc::GlobalAlloc for $name
+		{
+			#[inline(always)]
+			unsafe fn alloc(&self, layout: ::std::alloc::Layout) -> *mut u8
+			{
+				::std::alloc::GlobalAlloc::alloc(&self.inner, layout)
+			}
+
+			#[inline(always)]
+			unsafe fn alloc_zeroed(&self, layout: ::std::alloc::Layout) -> *mut u8
+			{
+				::std::alloc::GlobalAlloc::alloc_zeroed(&self.inner, layout)
+			}
+
+			#[inline(always)]
+			unsafe fn dealloc(&self, ptr: *mut u8, layout: ::std::alloc::Layout)
+			{
+				::std::alloc::GlobalAlloc::dealloc(&self.inner, ptr, layout)
+			}
+
+			#[inline(always)]
+			unsafe fn realloc(&self, ptr: *mut u8, layout: ::std::alloc::Layout, new_size: usize) -> *mut u8
+			{
+				::std::alloc::GlobalAlloc::realloc(&self.inner, ptr, layout, new_size)
+			}
+		}
+
+		unsafe impl ::std::alloc::Allocator for $name
+		{
+			#[inline(always)]
+			fn allocate(&self, layout: ::std::alloc::Layout) -> Result<::std::ptr::NonNull<[u8]>, ::std::alloc::AllocError>
+			{
+				::std::alloc::Allocator::allocate(&self.inner, layout)
+			}
+
+			#[inline(always)]
+			unsafe fn deallocate(&self, ptr: ::std::ptr::NonNull<u8>, layout: ::std::alloc::Layout)
+			{
+				::std::alloc::Allocator::deallocate(&self.inner, ptr, layout)
+			}
+
+			#[inline(always)]
+			unsafe fn grow(&self, ptr: ::std::ptr::NonNull<u8>, old_layout: ::std::alloc::Layout, new_layout: ::std::alloc::Layout) -> Result<::std::ptr::NonNull<[u8]>, ::std::alloc::AllocError>
+			{
+				::std::alloc::Allocator::grow(&self.inner, ptr, old_layout, new_layout)
+			}
+
+			#[inline(always)]
+			unsafe fn shrink(&self, ptr: ::std::ptr::NonNull<u8>, old_layout: ::std::alloc::Layout, new_layout: ::std::alloc::Layout) -> Result<::std::ptr::NonNull<[u8]>, ::std::alloc::AllocError>
+			{
+				::std::alloc::Allocator::shrink(&self.inner, ptr, old_layout, new_layout)
+			}
+		}
+
+		impl $crate::allocators::Allocator for $name
+		{
+			#[inline(always)]
+			fn allocate(&self, non_zero_size: ::std::num::NonZeroUsize, non_zero_power_of_two_alignment: ::std::num::NonZeroUsize) -> Result<(::std::ptr::NonNull<u8>, usize), ::std::alloc::AllocError>
+			{
+				$crate::allocators::Allocator::allocate(&self.inner, non_zero_size, non_zero_power_of_two_alignment)
+			}
+
+			#[inline(always)]
+			fn deallocate(&self, non_zero_size: ::std::num::NonZeroUsize, non_zero_power_of_two_alignment: ::std::num::NonZeroUsize, current_memory: ::std::ptr::NonNull<u8>)
+			{
+				$crate::allocators::Allocator::deallocate(&self.inner, non_zero_size, non_zero_power_of_two_alignment, current_memory)
+			}
+
+			#[inline(always)]
+			fn growing_reallocate(&self, non_zero_new_size: ::std::num::NonZeroUsize, non_zero_power_of_two_new_alignment: ::std::num::NonZeroUsize, non_zero_current_size: ::std::num::NonZeroUsize, non_zero_power_of_two_current_alignment: ::std::num::NonZeroUsize, current_memory: ::std::ptr::NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(::std::ptr::NonNull<u8>, usize), ::std::alloc::AllocError>
+			{
+				$crate::allocators::Allocator::growing_reallocate(&self.inner, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+			}
+
+			#[inline(always)]
+			fn shrinking_reallocate(&self, non_zero_new_size: ::std::num::NonZeroUsize, non_zero_power_of_two_new_alignment: ::std::num::NonZeroUsize, non_zero_current_size: ::std::num::NonZeroUsize, non_zero_power_of_two_current_alignment: ::std::num::NonZeroUsize, current_memory: ::std::ptr::NonNull<u8>, current_memory_can_not_be_moved: bool) -> Result<(::std::ptr::NonNull<u8>, usize), ::std::alloc::AllocError>
+			{
+				$crate::allocators::Allocator::shrinking_reallocate(&self.inner, non_zero_new_size, non_zero_power_of_two_new_alignment, non_zero_current_size, non_zero_power_of_two_current_alignment, current_memory, current_memory_can_not_be_moved)
+			}
+		}
+
+		impl $crate::GlobalThreadAndCoroutineSwitchableAllocator<$coroutine_heap_size> for $name
+		{
+			type CoroutineLocalAllocator = $coroutine_local_allocator;
+
+			type ThreadLocalAllocator = $thread_local_allocator;
+
+			type GlobalAllocator = $global_allocator;
+
+			#[inline(always)]
+			fn per_thread_state(&self) -> fn() -> ::std::ptr::NonNull<$crate::PerThreadState<$coroutine_heap_size, $coroutine_local_allocator, $thread_local_allocator>>
+			{
+				$crate::GlobalThreadAndCoroutineSwitchableAllocator::per_thread_state(&self.inner)
+			}
+
+			#[inline(always)]
+			fn global_allocator(&self) -> &Self::GlobalAllocator
+			{
+				$crate::GlobalThreadAndCoroutineSwitchableAllocator::global_allocator(&self.inner)
+			}
+
+			#[inline(always)]
+			fn exhaustion_spills(&self) -> $crate::ExhaustionSpills
+			{
+				$crate::GlobalThreadAndCoroutineSwitchableAllocator::exhaustion_spills(&self.inner)
+			}
+		}
+
+		impl $name
+		{
+			#[inline(always)]
+			const fn new() -> Self
+			{
+				Self
+				{
+					inner: $crate::GlobalThreadAndCoroutineSwitchableAllocatorInstance::new_with_thread_local_allocator_factory($global_allocator_instance, $thread_local_allocator_factory, Self::thread_local_per_thread_state).with_exhaustion_policy($exhaustion_policy).with_lazily_created_thread_local_allocator_outstanding_memory_reporter($lazily_created_thread_local_allocator_outstanding_memory_reporter),
+				}
+			}
+
+			#[inline(always)]
+			fn thread_local_per_thread_state() -> ::std::ptr::NonNull<$crate::PerThreadState<$coroutine_heap_size, $coroutine_local_allocator, $thread_local_allocator>>
+			{
+				#[allow(non_upper_case_globals)] #[thread_local] static mut per_thread_state: $crate::PerThreadState<$coroutine_heap_size, $coroutine_local_allocator, $thread_local_allocator> = $crate::PerThreadState::empty();
+
+				unsafe { ::std::ptr::NonNull::new_unchecked(::std::ptr::addr_of_mut!(per_thread_state)) }
+			}
+		}
+
+		#[allow(non_upper_case_globals)]
+		#[global_allocator]
+		pub static $name: $name = $name::new();
+	}
+}
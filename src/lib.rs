@@ -26,7 +26,7 @@
 //!
 //! All of these allocators implement the traits `std::alloc::GlobalAlloc` and `std::alloc::Alloc`, as we as a common base trait, `Allocator`.
 //!
-//! The most useful is a global allocator which allows switching between thread, coroutine and global (and thuse lockable) memory allocators, using the macro `global_thread_and_coroutine_switchable_allocator()`.
+//! The most useful is a global allocator which allows switching between thread, coroutine and global (and thuse lockable) memory allocators, using the macro `global_thread_and_coroutine_switchable_allocator!(Name, CoroutineHeapSize, CoroutineLocalAllocator, ThreadLocalAllocator, GlobalAllocator, global_allocator_instance)`.
 //!
 //! Allocators provided include:-
 //!
@@ -35,6 +35,13 @@
 //! * `MultipleBinarySearchTreeAllocator`, an efficient allocator which minimizes fragmentation by using multiple red-black trees of free blocks which are aggresively defragmented.
 //! * `ContextAllocator`, a choice of either `BumpAllocator`, `BitSetAllocator` or `MultipleBinarySearchTreeAllocator`.
 //! * `MemoryMapAllocator`, a NUMA-aware mmap allocator with support for NUMA policies.
+//! * `GuardedAllocator`, which wraps any allocator to pad every allocation with red zones filled with a canary, verified on deallocation and reallocation, to find buffer overruns and underruns.
+//! * `QuarantineAllocator`, which wraps any allocator to hold freed blocks, poisoned, in a first-in, first-out quarantine with a byte budget, verifying the poison when they leave to find use-after-free.
+//! * `SizeClassHistogramRecordingAllocator`, which wraps any allocator to record a `SizeClassHistogram` of requested sizes and alignments, eg to choose a `block_size_hint`.
+//! * `TracingAllocator`, which wraps any allocator to record every allocation event into a per-thread `AllocationTraceRingBuffer`, which a consumer thread can drain.
+//! * `AllocationTraceWriter` and `AllocationTraceReader`, which persist allocation events in a compact, versioned binary format, and `AllocationTraceReplayer`, which replays them against any allocator to compare time taken, peak memory, failures and fragmentation.
+//! * `SamplingHeapProfilerAllocator`, which wraps any allocator to capture a backtrace for roughly one in every N bytes allocated and reports live and deallocated sampled memory grouped by call stack; reports can be written as gzip-compressed pprof heap profiles.
+//! * `AllocatorStatistics`, per-tier memory usage statistics of the current thread of a `GlobalThreadAndCoroutineSwitchableAllocator`, exportable in Prometheus text and JSON formats.
 //! * `GlobalThreadAndCoroutineSwitchableAllocator`, suitable for replacing the global allocator and provides switchable allocators for global, thread local and context (coroutine) local needs; must be created using the macro `global_thread_and_coroutine_switchable_allocator`.
 //!
 //! Allocators use a `MemorySource` to obtain and release memory.
@@ -50,7 +57,16 @@
 //! * `AllocToAllocatorAdaptor`, an adaptor of `Alloc` to `Allocator`.
 //!
 //! When using `GlobalThreadAndCoroutineSwitchableAllocator`, it is possible to save and restore the allocator state for the currently running context (coroutine).
+//! A `CoroutineAllocationContext` bundles this state and swaps it in and out in one step using `resume_on()` and `suspend()`.
+//! A `CoroutineMemoryQuota` can be attached when swapping in a coroutine's allocator to limit the memory it allocates from all tiers whilst it runs.
 //! It is also possible to create a lockless, fast thread-local allocator which make use of NUMA memory, unlike a conventional malloc.
+//! Additional local allocators, eg for request-scoped or connection-scoped lifetimes, can be pushed onto and popped from a bounded per-thread stack using `push_local_allocator()`.
+//! Memory of a thread-local allocator that is deallocated by another thread is queued back to its owning thread, and is reclaimed on that thread's next allocation or by `drain_remote_frees()`.
+//! Thread-local allocators can be dropped automatically when their thread exits using `drop_thread_local_allocator_on_thread_exit()`, which reports any memory still allocated from them before dropping them.
+//! Thread-local allocators can also be created on demand the first time they are needed on a thread by passing a `ThreadLocalAllocatorFactory` as an optional seventh argument to `global_thread_and_coroutine_switchable_allocator!`; if creation fails, the global allocator is used instead. Thread-local allocators so created are dropped when their thread exits; an optional ninth argument reports any memory still allocated from them.
+//! An `ExhaustionPolicy`, the optional eighth argument, lets allocations spill from an exhausted coroutine-local allocator to the thread-local allocator and then to the global allocator; spills are counted by `exhaustion_spills()`.
+//! The memory ranges of local allocators are registered in a process-wide `MemoryRangeRegistry`, so the owning local allocator's tier and thread can be found for any pointer in constant time using `MemoryRangeRegistry::owner_of()`; deallocations and reallocations are routed using it.
+//! Memory of a coroutine-local allocator that is deallocated whilst it is swapped out is queued back to it, and is reclaimed when it is next swapped in.
 //!
 //!
 //! ## Future
@@ -84,37 +100,66 @@ use self::binary_search_trees::*;
 use self::binary_search_trees::red_black_tree::*;
 use self::extensions::*;
 use self::memory_sources::*;
+use backtrace::Backtrace;
+use backtrace::resolve;
+use backtrace::Symbol;
 use either::*;
+use errno::errno;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use libc::c_void;
+use libc::clock_gettime;
+use libc::CLOCK_MONOTONIC;
+use libc::EINTR;
+use libc::STDERR_FILENO;
+use libc::timespec;
+use libc::write;
 use likely::*;
 use linux_support::memory::mapping::*;
+use magic_ring_buffer::MagicRingBuffer;
 use magic_ring_buffer::memory_sizes::MemorySize;
 use std::alloc::Allocator as Alloc;
 use std::alloc::AllocError;
 use std::alloc::Layout;
 use std::alloc::GlobalAlloc;
 use std::alloc::System;
+use std::any::type_name;
 use std::collections::Bound;
 use std::collections::Bound::*;
+use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::cell::Cell;
+use std::cell::RefCell;
 use std::cell::UnsafeCell;
 use std::cmp::max;
+use std::cmp::min;
 use std::cmp::Ordering;
+use std::error;
 use std::fmt;
+use std::fmt::Arguments;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::fs::File;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::hint::spin_loop;
+use std::io;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Write;
 use std::marker::PhantomData;
 use std::mem::align_of;
 use std::mem::ManuallyDrop;
 use std::mem::replace;
 use std::mem::size_of;
+use std::mem::take;
 use std::mem::transmute;
 use std::num::NonZeroU32;
 use std::num::NonZeroU64;
 use std::num::NonZeroUsize;
 use std::ops::Add;
+use std::ops::AddAssign;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::ops::Shr;
@@ -125,11 +170,28 @@ use std::panic::catch_unwind;
 use std::panic::RefUnwindSafe;
 use std::panic::resume_unwind;
 use std::panic::UnwindSafe;
+use std::path::Path;
+use std::process::abort;
 use std::ptr::drop_in_place;
 use std::ptr::NonNull;
 use std::ptr::null;
 use std::ptr::null_mut;
+use std::slice::from_raw_parts;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicPtr;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::AcqRel;
+use std::sync::atomic::Ordering::Acquire;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::atomic::Ordering::Release;
+use std::sync::atomic::Ordering::SeqCst;
+use std::thread::current;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 use swiss_army_knife::get_unchecked::GetUnchecked;
 use swiss_army_knife::non_zero::new_non_null;
 use swiss_army_knife::non_zero::new_non_zero_u32;
@@ -152,16 +214,51 @@ pub mod extensions;
 pub mod memory_sources;
 
 
+include!("abort_with_message.rs");
+include!("AllocationEvent.rs");
+include!("AllocationEventKind.rs");
+include!("AllocationTraceFormatError.rs");
+include!("AllocationTraceReader.rs");
+include!("AllocationTraceReplayer.rs");
+include!("AllocationTraceReplayReport.rs");
+include!("AllocationTraceRingBuffer.rs");
+include!("AllocationTraceWriter.rs");
+include!("AllocatorStatistics.rs");
+include!("CoroutineAllocationContext.rs");
+include!("CoroutineMemoryQuota.rs");
+include!("CoroutineMemoryQuotaPolicy.rs");
 include!("CurrentAllocatorInUse.rs");
+include!("CurrentAllocatorInUseGuard.rs");
+include!("ExhaustionPolicy.rs");
+include!("ExhaustionSpills.rs");
 include!("GloballyAllocated.rs");
 include!("GlobalThreadAndCoroutineSwitchableAllocator.rs");
 include!("GlobalThreadAndCoroutineSwitchableAllocatorInstance.rs");
+include!("global_thread_and_coroutine_switchable_allocator.rs");
 include!("LifetimeHint.rs");
 include!("LocalAllocator.rs");
+include!("LocalAllocatorStack.rs");
+include!("LocalAllocatorStackGuard.rs");
 include!("LocalAllocatorMemoryUsage.rs");
+include!("LocalAllocatorMemoryUsageSnapshot.rs");
 include!("MemoryAddress.rs");
 include!("MemoryRange.rs");
+include!("MemoryRangeOwner.rs");
+include!("MemoryRangeRegistry.rs");
 include!("PerThreadState.rs");
+include!("PprofHeapProfileEncoder.rs");
+include!("RemoteFreeQueue.rs");
+include!("SamplingHeapProfileCallStack.rs");
+include!("SamplingHeapProfileFrame.rs");
+include!("SamplingHeapProfileReport.rs");
+include!("SamplingHeapProfileSymbol.rs");
+include!("SamplingHeapProfiler.rs");
+include!("SizeClassHistogram.rs");
+include!("SizeClassHistogramSnapshot.rs");
+include!("StackedLocalAllocator.rs");
+include!("ThreadExitTeardown.rs");
+include!("ThreadLocalAllocatorFactory.rs");
 
 
-#[cfg(test)] global_thread_and_coroutine_switchable_allocator!(MyGlobalAllocator, BumpAllocator<ArenaMemorySource<MemoryMapSource>>, MultipleBinarySearchTreeAllocator<MemoryMapSource>, GlobalAllocToAllocatorAdaptor<System>, GlobalAllocToAllocatorAdaptor(System));
+#[cfg(test)] use magic_ring_buffer::memory_sizes::MemorySize64Kb;
+#[cfg(test)] global_thread_and_coroutine_switchable_allocator!(MyGlobalAllocator, MemorySize64Kb, BumpAllocator<CoroutineHeapMemorySource<MemorySize64Kb>>, MultipleBinarySearchTreeAllocator<MemoryMapSource>, GlobalAllocToAllocatorAdaptor<System>, GlobalAllocToAllocatorAdaptor(System));
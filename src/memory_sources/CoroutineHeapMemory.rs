@@ -23,6 +23,8 @@ impl<CoroutineHeapSize: MemorySize> Debug for CoroutineHeapMemory<CoroutineHeapS
 impl<CoroutineHeapSize: MemorySize> CoroutineHeapMemory<CoroutineHeapSize>
 {
 	/// Into a memory source.
+	///
+	/// The memory source's memory range is registered in the `MemoryRangeRegistry` when the coroutine local allocator using it is first swapped in, and deregistered when the memory source is dropped.
 	#[inline(always)]
 	pub const fn into_memory_source(&self) -> CoroutineHeapMemorySource<CoroutineHeapSize>
 	{
@@ -9,6 +9,27 @@
 #[repr(C, align(4096))]
 pub struct CoroutineHeapMemorySource<CoroutineHeapSize: MemorySize>(NonNull<CoroutineHeapMemory<CoroutineHeapSize>>);
 
+impl<CoroutineHeapSize: MemorySize> Drop for CoroutineHeapMemorySource<CoroutineHeapSize>
+{
+	/// Deregisters the memory range if it was registered when swapped in; deallocations queued whilst it was swapped out are discarded, as the memory is released wholesale.
+	#[inline(always)]
+	fn drop(&mut self)
+	{
+		let memory_range = self.memory_range();
+		if let Some(memory_range_owner) = MemoryRangeRegistry::owner_of(memory_range.from)
+		{
+			if memory_range_owner.memory_range() == memory_range
+			{
+				if let Some(remote_free_queue) = memory_range_owner.remote_free_queue()
+				{
+					remote_free_queue.retire_discarding_remote_frees();
+				}
+				MemoryRangeRegistry::deregister(memory_range_owner)
+			}
+		}
+	}
+}
+
 impl<CoroutineHeapSize: MemorySize> MemorySource for CoroutineHeapMemorySource<CoroutineHeapSize>
 {
 	#[inline(always)]
@@ -9,22 +9,38 @@
 /// When dropped, any memory obtained with this allocator is ***NOT*** freed.
 ///
 /// However, it is appropriate as a 'backing store' for other memory sources.
+///
+/// Its memory range is registered in the `MemoryRangeRegistry` whilst it is live.
 #[derive(Debug)]
-pub struct MemoryMapSource(MappedMemory);
+pub struct MemoryMapSource
+{
+	mapped_memory: MappedMemory,
+
+	memory_range_owner: &'static MemoryRangeOwner,
+}
+
+impl Drop for MemoryMapSource
+{
+	#[inline(always)]
+	fn drop(&mut self)
+	{
+		MemoryRangeRegistry::deregister(self.memory_range_owner)
+	}
+}
 
 impl MemorySource for MemoryMapSource
 {
 	#[inline(always)]
 	fn size(&self) -> NonZeroUsize
 	{
-		let size = self.0.mapped_size_in_bytes();
+		let size = self.mapped_memory.mapped_size_in_bytes();
 		new_non_zero_usize(size)
 	}
 	
 	#[inline(always)]
 	fn allocations_start_from(&self) -> MemoryAddress
 	{
-		self.0.virtual_address().into()
+		self.mapped_memory.virtual_address().into()
 	}
 }
 
@@ -34,6 +50,15 @@ impl MemoryMapSource
 	#[inline(always)]
 	pub fn new(size: NonZeroU64, settings: MappedMemorySettings) -> Result<Self, MemoryMapError>
 	{
-		settings.anonymous_memory_map(size).map(Self)
+		let mapped_memory = settings.anonymous_memory_map(size)?;
+		let memory_range_owner = MemoryRangeRegistry::register(MemoryRange::new(mapped_memory.virtual_address().into(), MemoryAddress::from(mapped_memory.virtual_address()).add(mapped_memory.mapped_size_in_bytes())));
+		Ok
+		(
+			Self
+			{
+				mapped_memory,
+				memory_range_owner,
+			}
+		)
 	}
 }